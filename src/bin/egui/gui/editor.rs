@@ -111,12 +111,16 @@ pub enum EditorResponse {
 
     // Palette editor
     SavePalette,
+    ImportPalette,
+    ExportPalette,
 
     // Tileset editor
     SaveTset,
+    ImportTsetPng,
 
     // Tilemap editor
     SaveTmap,
+    ImportTmapPng,
     ExportPng,
 
     // Metadata editor
@@ -187,17 +191,26 @@ impl Editor {
     }
 
     fn draw_palette(ui: &mut Ui, contents: &NCLR, transparency: &mut bool) -> EditorResponse {
+        let mut response = EditorResponse::None;
         ui.horizontal(|ui| {
             Frame::group(ui.style()).show(ui, |ui| {
                 ui.set_width(350.0);
                 ui.set_height(350.0);
                 ui.vertical(|ui| {
                     if contents.is_8_bit {
-                        ui.add(PalPreview {
-                            color_amt: contents.color_amt,
-                            palette: &contents.palettes[&0],
-                            is_8_bit: contents.is_8_bit,
-                            transparency: *transparency,
+                        // Extended palettes have up to 16 palettes of 256 colors, which don't fit at once
+                        ScrollArea::vertical().show(ui, |ui| {
+                            for (num, pal) in &contents.palettes {
+                                if contents.palettes.len() > 1 {
+                                    ui.label(format!("Extended palette {}", num));
+                                }
+                                ui.add(PalPreview {
+                                    color_amt: contents.color_amt,
+                                    palette: pal,
+                                    is_8_bit: contents.is_8_bit,
+                                    transparency: *transparency,
+                                });
+                            }
                         });
                     } else {
                         for (num, pal) in &contents.palettes {
@@ -216,15 +229,18 @@ impl Editor {
             });
             ui.vertical(|ui| {
                 ui.checkbox(transparency, "Enable transparency");
-                //TODO: importing/exporting from a text format would be real nice
-                //ui.button("Import .pal file");
-                //ui.button("Export .pal file");
+                if ui.button("Import palette file").clicked() {
+                    response = EditorResponse::ImportPalette;
+                }
+                if ui.button("Export palette file").clicked() {
+                    response = EditorResponse::ExportPalette;
+                }
             })
         });
         if ui.button("Save").clicked() {
             EditorResponse::SavePalette
         } else {
-            EditorResponse::None
+            response
         }
     }
 
@@ -369,9 +385,14 @@ impl Editor {
                 })
             });
         });
-        if ui.button("Save").clicked() {
-            response = EditorResponse::SaveTset;
-        }
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                response = EditorResponse::SaveTset;
+            }
+            if ui.button("Import PNG image").clicked() {
+                response = EditorResponse::ImportTsetPng;
+            }
+        });
 
         if update_img {
             Self::update_tileset_img(contents, project, palette, image, view);
@@ -380,7 +401,7 @@ impl Editor {
         response
     }
 
-    fn update_tileset_img(
+    pub fn update_tileset_img(
         ncgr: &NCGR,
         project: &NuclearProject,
         palette: &Option<String>,
@@ -402,19 +423,27 @@ impl Editor {
                 );
 
                 let pal = nclr.palettes.get(&(view.palette as u16)).unwrap();
-                let mut rgba = vec![];
-
-                for px in &img {
-                    rgba.extend(
-                        pal.get(*px as usize)
-                            .ok_or(Error::MalformedData {
-                                file: "{current tileset}".to_string(),
-                            })
-                            .manage()
-                            .to_rgb888(),
-                    );
-                    rgba.push(255);
-                }
+                let rgba = img.and_then(|img| {
+                    let mut rgba = vec![];
+                    for px in &img {
+                        let color = pal.get(*px as usize).ok_or(Error::MissingReference {
+                            what: "Tileset".to_string(),
+                            kind: "color".to_string(),
+                            index: *px as usize,
+                        })?;
+                        rgba.extend(color.to_rgb888());
+                        rgba.push(255);
+                    }
+                    Ok((img, rgba))
+                });
+                let (img, mut rgba) = match rgba {
+                    Ok(c) => c,
+                    Err(e) => {
+                        message::error("Can't render tileset", &format!("Details:\n\n{}", e));
+                        *image = None;
+                        return;
+                    }
+                };
 
                 while rgba.len() % (view.width * 4) != 0 {
                     rgba.push(0);
@@ -455,6 +484,11 @@ impl Editor {
             update_img = true;
         }
 
+        ui.label(format!(
+            "Screen mode: {:?} ({} colors)",
+            contents.mode,
+            if contents.is_8_bit { 256 } else { 16 }
+        ));
         ui.label("Tileset associated with this tilemap:");
         let before = tileset.clone();
         ComboBox::from_label("")
@@ -475,7 +509,9 @@ impl Editor {
         }
 
         ui.horizontal(|ui| {
-            //ui.button("Import PNG image")
+            if ui.button("Import PNG image").clicked() {
+                response = EditorResponse::ImportTmapPng
+            }
             if ui.button("Export to PNG image").clicked() {
                 response = EditorResponse::ExportPng
             }
@@ -508,7 +544,7 @@ impl Editor {
         response
     }
 
-    fn update_tilemap_img(
+    pub fn update_tilemap_img(
         nscr: &NSCR,
         project: &NuclearProject,
         tileset: &Option<String>,
@@ -516,9 +552,17 @@ impl Editor {
         image: &mut Option<RetainedImage>,
     ) {
         if let Some(c) = tileset {
-            let Some(pixels) = Self::render_tilemap_img(nscr, project, c, tileset_cache) else {
-                *image = None;
-                return;
+            let pixels = match Self::render_tilemap_img(nscr, project, c, tileset_cache) {
+                Ok(Some(c)) => c,
+                Ok(None) => {
+                    *image = None;
+                    return;
+                }
+                Err(e) => {
+                    message::error("Can't render tilemap", &format!("Details:\n\n{}", e));
+                    *image = None;
+                    return;
+                }
             };
 
             *image = Some(RetainedImage::from_color_image(
@@ -533,18 +577,24 @@ impl Editor {
         }
     }
 
+    /// Renders the tilemap to RGBA, or nothing if its tileset doesn't have a palette
     pub fn render_tilemap_img(
         nscr: &NSCR,
         project: &NuclearProject,
         tileset: &str,
         tileset_cache: &mut Option<NCGR>,
-    ) -> Option<Vec<u8>> {
-        let tset_wrapper = project.tilesets.get(tileset)?;
-        let palette = project
-            .get_nclr(tset_wrapper.associated_palette.as_ref()?)
-            .manage()?;
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let Some(tset_wrapper) = project.tilesets.get(tileset) else {
+            return Ok(None);
+        };
+        let Some(palette) = &tset_wrapper.associated_palette else {
+            return Ok(None);
+        };
+        let Some(palette) = project.get_nclr(palette)? else {
+            return Ok(None);
+        };
         if tileset_cache.is_none() {
-            *tileset_cache = Some(tset_wrapper.get_inner().manage());
+            *tileset_cache = Some(tset_wrapper.get_inner()?);
         }
 
         let data = nscr.render(&palette, tileset_cache.as_ref().unwrap())?;
@@ -553,7 +603,7 @@ impl Editor {
         for i in (0..data.len()).step_by(3) {
             pixels.extend([data[i], data[i + 1], data[i + 2], 255])
         }
-        Some(pixels)
+        Ok(Some(pixels))
     }
 
     fn draw_metadata(
@@ -10,6 +10,9 @@ pub enum MenuBarResponse {
     Metadata,
     None,
     ImportFile(FormatType),
+    ExportFiles(FormatType),
+    ImportPortable,
+    ExportPortable,
 }
 
 impl MenuBarResponse {
@@ -32,20 +35,24 @@ pub fn menu_bar(app: &mut NuclearApp, ctx: &Context) -> MenuBarResponse {
                 if ui.button("Open").clicked() {
                     response.set_if_none(MenuBarResponse::OpenProj)
                 }
+                if ui.button("Import portable project").clicked() {
+                    response.set_if_none(MenuBarResponse::ImportPortable)
+                }
 
                 ui.set_enabled(app.project.is_some());
 
                 if ui.button("Save as").clicked() {
                     message::warning("Not implemented!", "Can't 'save as' yet")
                 }
+                if ui.button("Export portable project").clicked() {
+                    response.set_if_none(MenuBarResponse::ExportPortable)
+                }
                 /*
                 ui.separator();
                 ui.menu_button("Open recent", |ui| {
                     ui.button("1. -");
                     ui.button("2. -");
                 });
-                ui.button("Import portable project");
-                ui.button("Export portable project");
                 ui.separator();
                 */
                 ui.menu_button("Import", |ui| {
@@ -54,12 +61,12 @@ pub fn menu_bar(app: &mut NuclearApp, ctx: &Context) -> MenuBarResponse {
                     }
                     //ui.button("BNCAD");
                 });
-                /*
                 ui.menu_button("Export", |ui| {
-                    ui.button("Nintendo files");
-                    ui.button("BNCAD");
+                    if ui.button("Nintendo files").clicked() {
+                        response.set_if_none(MenuBarResponse::ExportFiles(FormatType::Nintendo))
+                    }
+                    //ui.button("BNCAD");
                 });
-                */
             });
 
             ui.menu_button("Edit", |ui| {
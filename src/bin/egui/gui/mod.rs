@@ -1,10 +1,14 @@
-use std::{fs::File, path::Path};
+use std::{
+    fs::{self, File},
+    path::Path,
+};
 
 use crate::{addon::NuclearResult, message, widgets::tab::Tab};
+use bytestream::ByteOrder;
 use eframe::egui::{CentralPanel, Context, RichText, ScrollArea, SidePanel, Ui};
 use nuclear::{
     extend::{FileType, FormatType},
-    img::export,
+    img::{export, PaletteFormat, NCLR},
     proj::NuclearProject,
 };
 
@@ -252,6 +256,57 @@ impl eframe::App for NuclearApp {
                     }
                 }
             }
+            MenuBarResponse::ExportFiles(FormatType::Nintendo) => {
+                if let Some(path) =
+                    message::open_folder("Choose folder for exported files", Path::new(""))
+                {
+                    let project = self.project.as_ref().unwrap();
+                    let written = project.export_all(&path, ByteOrder::LittleEndian).manage();
+                    message::info(
+                        "Exported correctly!",
+                        &format!("Exported {} files to {}.", written.len(), path.display()),
+                    )
+                }
+            }
+            MenuBarResponse::ImportPortable => {
+                if let Some(file) = message::open_file(
+                    "Open portable project",
+                    Path::new(""),
+                    Some((&["*.nucp"], "nuclear portable project")),
+                ) {
+                    if let Some(path) = message::open_folder(
+                        "Choose folder to extract the project to",
+                        Path::new(""),
+                    ) {
+                        if self.close_project() {
+                            let mut f = File::open(&file).manage();
+                            match NuclearProject::import_portable(&mut f, &path) {
+                                Ok(c) => self.project = Some(c),
+                                Err(e) => message::error(
+                                    "Failed to import project",
+                                    &format!(
+                                        "Portable project {} could not be imported:\n{}",
+                                        file.display(),
+                                        e
+                                    ),
+                                ),
+                            }
+                        }
+                    }
+                }
+            }
+            MenuBarResponse::ExportPortable => {
+                if let Some(path) = message::save_file("Save portable project", Path::new("")) {
+                    let project = self.project.as_ref().unwrap();
+                    project
+                        .export_portable(&mut File::create(&path).manage())
+                        .manage();
+                    message::info(
+                        "Exported correctly!",
+                        &format!("Saved portable project to {}.", path.display()),
+                    )
+                }
+            }
             MenuBarResponse::None => {}
         }
 
@@ -320,7 +375,67 @@ impl eframe::App for NuclearApp {
                                 message::info("Project metadata", "Saved project metadata!");
                             }
                             EditorResponse::SavePalette => {
-                                todo!("Save palette event");
+                                let Editor::Palette { name, contents, ..} =  &self.editors[self.selected_tab] else {
+                                    unreachable!();
+                                };
+                                let project = self.project.as_mut().unwrap();
+                                project.insert_nclr(name, contents).manage();
+                                project.save().manage();
+
+                                message::info("Saved correctly!", &format!("Saved palette {}.", name))
+                            }
+                            EditorResponse::ImportPalette => {
+                                let Editor::Palette { contents, ..} = &mut self.editors[self.selected_tab] else {
+                                    unreachable!();
+                                };
+                                if let Some(path) = message::open_file(
+                                    "Import palette file",
+                                    Path::new(""),
+                                    Some((&["*.pal", "*.gpl", "*.act", "*.aco"], "Palette files")),
+                                ) {
+                                    let data = fs::read(&path).manage();
+                                    match PaletteFormat::detect(&data) {
+                                        Some(format) => {
+                                            let fname = path.display().to_string();
+                                            match NCLR::import_palette(&fname, &data, format, contents.is_8_bit) {
+                                                Ok(c) => {
+                                                    // Only the colors change, the rest of the file is kept
+                                                    contents.palettes = c.palettes;
+                                                    contents.is_8_bit = c.is_8_bit;
+                                                    contents.color_amt = c.color_amt;
+                                                }
+                                                Err(e) => message::error("Can't import palette", &e.to_string()),
+                                            }
+                                        }
+                                        None => message::error(
+                                            "Unsupported file format",
+                                            &format!("Can't open palette file {}", path.display()),
+                                        ),
+                                    }
+                                }
+                            }
+                            EditorResponse::ExportPalette => {
+                                let Editor::Palette { contents, ..} = &self.editors[self.selected_tab] else {
+                                    unreachable!();
+                                };
+                                if let Some(path) = message::save_file("Export palette file", Path::new("")) {
+                                    let format = match path
+                                        .extension()
+                                        .and_then(|c| PaletteFormat::from_extension(&c.to_string_lossy()))
+                                        .unwrap_or(PaletteFormat::JascPal)
+                                    {
+                                        // Both JASC and RIFF palettes use .pal, so ask which one is wanted
+                                        PaletteFormat::JascPal if message::yes_no(
+                                            "Choose palette format",
+                                            "Save as a binary RIFF palette?\n\nChoose No for a JASC-PAL text palette.",
+                                        ) => PaletteFormat::RiffPal,
+                                        c => c,
+                                    };
+                                    match contents.export_palette(format) {
+                                        Ok(c) => fs::write(&path, c).manage(),
+                                        Err(e) => message::error("Can't export palette", &e.to_string()),
+                                    }
+                                }
                             }
                             EditorResponse::SaveTset => {
                                 let Editor::Tileset { name, contents, palette, ..} =  &self.editors[self.selected_tab] else {
@@ -337,6 +452,34 @@ impl eframe::App for NuclearApp {
 
                                 message::info("Saved correctly!", &format!("Saved tileset {}.", name))
                             }
+                            EditorResponse::ImportTsetPng => {
+                                let Editor::Tileset { contents, palette, image, view, ..} = &mut self.editors[self.selected_tab] else {
+                                    unreachable!();
+                                };
+                                if let Some(path) = message::open_file(
+                                    "Import PNG image",
+                                    Path::new(""),
+                                    Some((&["*.png"], "PNG images")),
+                                ) {
+                                    let project = self.project.as_ref().unwrap();
+                                    let nclr = palette.as_ref().and_then(|c| project.get_nclr(c).manage());
+                                    let colors = nclr
+                                        .as_ref()
+                                        .filter(|_| view.palette >= 0)
+                                        .and_then(|c| c.palettes.get(&(view.palette as u16)));
+                                    match contents.import_indexed_png(
+                                        File::open(&path).manage(),
+                                        colors.map(|c| c.as_slice()),
+                                    ) {
+                                        Ok(()) => {
+                                            view.start_at = 0;
+                                            view.length = view.length.min(contents.tiles.len(contents.is_8_bit));
+                                            Editor::update_tileset_img(contents, project, palette, image, view);
+                                        }
+                                        Err(e) => message::error("Can't import image", &e.to_string()),
+                                    }
+                                }
+                            }
                             EditorResponse::SaveTmap => {
                                 let Editor::Tilemap { name, contents, tileset, ..} = &self.editors[self.selected_tab] else {
                                     unreachable!();
@@ -352,6 +495,65 @@ impl eframe::App for NuclearApp {
 
                                 message::info("Saved correctly!", &format!("Saved tilemap {}.", name))
                             }
+                            EditorResponse::ImportTmapPng => {
+                                let Editor::Tilemap { name, contents, tileset, tileset_cache, image, ..} = &mut self.editors[self.selected_tab] else {
+                                    unreachable!();
+                                };
+                                let project = self.project.as_mut().unwrap();
+                                let palette = tileset
+                                    .as_ref()
+                                    .and_then(|c| project.tilesets.get(c))
+                                    .and_then(|c| c.associated_palette.clone());
+
+                                if let (Some(tset_name), Some(pal_name)) = (tileset.clone(), palette) {
+                                    if let Some(path) = message::open_file(
+                                        "Import PNG image",
+                                        Path::new(""),
+                                        Some((&["*.png"], "PNG images")),
+                                    ) {
+                                        let loaded = match (project.get_ncgr(&tset_name), project.get_nclr(&pal_name)) {
+                                            (Ok(Some(ncgr)), Ok(Some(nclr))) => Ok((ncgr, nclr)),
+                                            (Err(e), _) | (_, Err(e)) => Err(e.to_string()),
+                                            (Ok(None), _) => Err(format!("Tileset {} doesn't exist anymore!", tset_name)),
+                                            (_, Ok(None)) => Err(format!("Palette {} doesn't exist anymore!", pal_name)),
+                                        };
+                                        let imported = loaded.and_then(|(mut ncgr, mut nclr)| {
+                                            let file = File::open(&path).map_err(|e| e.to_string())?;
+                                            let mut nscr = contents.clone();
+                                            let changes = nscr
+                                                .import_png(file, &mut ncgr, &mut nclr)
+                                                .map_err(|e| e.to_string())?;
+                                            Ok((nscr, ncgr, nclr, changes))
+                                        });
+                                        match imported {
+                                            Ok((nscr, ncgr, nclr, changes)) => {
+                                                // Tiles and colors are only ever added, so the tileset and palette
+                                                // can be saved without breaking anything else that uses them
+                                                project.insert_ncgr(&tset_name, &ncgr).manage();
+                                                project.tilesets.get_mut(&tset_name).unwrap().associated_palette = Some(pal_name.clone());
+                                                project.insert_nclr(&pal_name, &nclr).manage();
+                                                project.insert_nscr(name, &nscr).manage();
+                                                project.tilemaps.get_mut(name).unwrap().associated_tileset = Some(tset_name.clone());
+                                                project.save().manage();
+
+                                                *contents = nscr;
+                                                *tileset_cache = None;
+                                                Editor::update_tilemap_img(contents, project, tileset, tileset_cache, image);
+                                                message::info(
+                                                    "Imported correctly!",
+                                                    &format!(
+                                                        "Changed {} tiles, adding {} tiles to {} and {} colors to {}.",
+                                                        changes.changed_refs, changes.new_tiles, tset_name, changes.new_colors, pal_name
+                                                    ),
+                                                )
+                                            }
+                                            Err(e) => message::error("Can't import image", &e),
+                                        }
+                                    }
+                                } else {
+                                    message::error("Can't import image", "Tilemap needs an associated tileset with a palette to import images!");
+                                }
+                            }
                             EditorResponse::ExportPng =>  {
                                 let Editor::Tilemap { contents, tileset, tileset_cache, ..} = &mut self.editors[self.selected_tab] else {
                                     unreachable!();
@@ -359,18 +561,19 @@ impl eframe::App for NuclearApp {
 
                                 if let Some(c) = tileset {
                                     if let Some(path) = message::save_file("Choose path for exported PNG", Path::new("")) {
-                                        if let Some(pixels) =
-                                            Editor::render_tilemap_img(contents, self.project.as_ref().unwrap(), c, tileset_cache) {
-                                            export::export_image(
-                                                &mut File::create(path).manage(),
-                                                &pixels,
-                                                contents.width as u32,
-                                                contents.height as u32,
-                                                png::ColorType::Rgba,
-                                            ).manage();
-                                            message::info("Exported PNG correctly!", "Tilemap successfully exported")
-                                        } else {
-                                            message::error("Can't export image", "Failed to render image for some reason, are you sure it's valid?");
+                                        match Editor::render_tilemap_img(contents, self.project.as_ref().unwrap(), c, tileset_cache) {
+                                            Ok(Some(pixels)) => {
+                                                export::export_image(
+                                                    &mut File::create(path).manage(),
+                                                    &pixels,
+                                                    contents.width as u32,
+                                                    contents.height as u32,
+                                                    png::ColorType::Rgba,
+                                                ).manage();
+                                                message::info("Exported PNG correctly!", "Tilemap successfully exported")
+                                            }
+                                            Ok(None) => message::error("Can't export image", "Tileset needs an associated palette to be exported!"),
+                                            Err(e) => message::error("Can't export image", &format!("Failed to render image:\n\n{}", e)),
                                         }
                                     }
                                 } else {
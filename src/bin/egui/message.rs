@@ -33,6 +33,11 @@ pub fn error(title: &str, contents: &str) {
     tinyfiledialogs::message_box_ok(&title, &contents, MessageBoxIcon::Error);
 }
 
+pub fn open_file(title: &str, path: &Path, filter: Option<(&[&str], &str)>) -> Option<PathBuf> {
+    let title = sanitize_string(title);
+    tinyfiledialogs::open_file_dialog(&title, path.as_os_str().to_str()?, filter).map(|c| c.into())
+}
+
 pub fn open_files(
     title: &str,
     path: &Path,
@@ -0,0 +1,354 @@
+use crate::error::{Error, Result};
+
+/// Compression formats supported by the NDS BIOS that nuclear can read and write
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// LZ77 variant used by the BIOS, with matches of up to 18 bytes
+    LZ10,
+    /// Extended LZ77 variant, with matches of up to 65808 bytes
+    LZ11,
+}
+
+impl Compression {
+    /// Guesses the compression format of some data by looking at its header
+    ///
+    /// Nintendo files always start with an ASCII magic, so a type byte is enough to tell them apart
+    pub fn detect(data: &[u8]) -> Self {
+        if data.len() < 4 {
+            return Self::None;
+        }
+        match data[0] {
+            0x10 => Self::LZ10,
+            0x11 => Self::LZ11,
+            _ => Self::None,
+        }
+    }
+
+    fn type_byte(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::LZ10 => 0x10,
+            Self::LZ11 => 0x11,
+        }
+    }
+}
+
+/// Decompresses LZ10/LZ11 data, detecting the format from its header
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    match Compression::detect(data) {
+        Compression::LZ10 => decompress_lz10(data),
+        Compression::LZ11 => decompress_lz11(data),
+        Compression::None => Err(Error::MalformedCompressedData {
+            reason: "data doesn't have a LZ10 or LZ11 header".to_string(),
+        }),
+    }
+}
+
+/// Decompresses the data if it has a LZ10/LZ11 header, otherwise returns it as-is
+pub fn decompress_if_needed(data: Vec<u8>) -> Result<Vec<u8>> {
+    match Compression::detect(&data) {
+        Compression::None => Ok(data),
+        _ => decompress(&data),
+    }
+}
+
+/// Compresses data in the given format. [Compression::None] returns the data as-is
+pub fn compress(data: &[u8], format: Compression) -> Result<Vec<u8>> {
+    match format {
+        Compression::None => Ok(data.to_vec()),
+        Compression::LZ10 => compress_lz10(data),
+        Compression::LZ11 => compress_lz11(data),
+    }
+}
+
+/// Decompresses LZ10 (type 0x10) data
+pub fn decompress_lz10(data: &[u8]) -> Result<Vec<u8>> {
+    let (size, mut pos) = read_header(data, Compression::LZ10)?;
+    let mut out = output_buffer(size, data);
+
+    while out.len() < size {
+        let flags = next_byte(data, &mut pos)?;
+        for bit in (0..8).rev() {
+            if out.len() >= size {
+                break;
+            }
+            if flags >> bit & 1 == 0 {
+                out.push(next_byte(data, &mut pos)?);
+                continue;
+            }
+            let b0 = next_byte(data, &mut pos)? as usize;
+            let b1 = next_byte(data, &mut pos)? as usize;
+            let length = (b0 >> 4) + 3;
+            let disp = ((b0 & 0xF) << 8 | b1) + 1;
+            copy_match(&mut out, disp, length, size)?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decompresses LZ11 (type 0x11) data
+pub fn decompress_lz11(data: &[u8]) -> Result<Vec<u8>> {
+    let (size, mut pos) = read_header(data, Compression::LZ11)?;
+    let mut out = output_buffer(size, data);
+
+    while out.len() < size {
+        let flags = next_byte(data, &mut pos)?;
+        for bit in (0..8).rev() {
+            if out.len() >= size {
+                break;
+            }
+            if flags >> bit & 1 == 0 {
+                out.push(next_byte(data, &mut pos)?);
+                continue;
+            }
+            let b0 = next_byte(data, &mut pos)? as usize;
+            let (length, disp) = match b0 >> 4 {
+                0 => {
+                    let b1 = next_byte(data, &mut pos)? as usize;
+                    let b2 = next_byte(data, &mut pos)? as usize;
+                    (
+                        ((b0 & 0xF) << 4 | b1 >> 4) + 0x11,
+                        ((b1 & 0xF) << 8 | b2) + 1,
+                    )
+                }
+                1 => {
+                    let b1 = next_byte(data, &mut pos)? as usize;
+                    let b2 = next_byte(data, &mut pos)? as usize;
+                    let b3 = next_byte(data, &mut pos)? as usize;
+                    (
+                        ((b0 & 0xF) << 12 | b1 << 4 | b2 >> 4) + 0x111,
+                        ((b2 & 0xF) << 8 | b3) + 1,
+                    )
+                }
+                c => {
+                    let b1 = next_byte(data, &mut pos)? as usize;
+                    (c + 1, ((b0 & 0xF) << 8 | b1) + 1)
+                }
+            };
+            copy_match(&mut out, disp, length, size)?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Compresses data to the LZ10 (type 0x10) format
+pub fn compress_lz10(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = write_header(data, Compression::LZ10)?;
+    let mut finder = MatchFinder::new(data);
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let flag_pos = out.len();
+        out.push(0);
+        for bit in (0..8).rev() {
+            if pos >= data.len() {
+                break;
+            }
+            match finder.find(pos, 3, 0x12) {
+                Some((length, disp)) => {
+                    out[flag_pos] |= 1 << bit;
+                    out.push(((length - 3) << 4 | (disp - 1) >> 8) as u8);
+                    out.push((disp - 1) as u8);
+                    finder.advance(pos, length);
+                    pos += length;
+                }
+                None => {
+                    out.push(data[pos]);
+                    finder.advance(pos, 1);
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    pad_to_4(&mut out);
+    Ok(out)
+}
+
+/// Compresses data to the LZ11 (type 0x11) format
+pub fn compress_lz11(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = write_header(data, Compression::LZ11)?;
+    let mut finder = MatchFinder::new(data);
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let flag_pos = out.len();
+        out.push(0);
+        for bit in (0..8).rev() {
+            if pos >= data.len() {
+                break;
+            }
+            match finder.find(pos, 3, 0x10110) {
+                Some((length, disp)) => {
+                    out[flag_pos] |= 1 << bit;
+                    let disp = disp - 1;
+                    if length <= 0x10 {
+                        out.push(((length - 1) << 4 | disp >> 8) as u8);
+                    } else if length <= 0x110 {
+                        let extra = length - 0x11;
+                        out.push((extra >> 4) as u8);
+                        out.push(((extra & 0xF) << 4 | disp >> 8) as u8);
+                    } else {
+                        let extra = length - 0x111;
+                        out.push((0x10 | extra >> 12) as u8);
+                        out.push((extra >> 4) as u8);
+                        out.push(((extra & 0xF) << 4 | disp >> 8) as u8);
+                    }
+                    out.push(disp as u8);
+                    finder.advance(pos, length);
+                    pos += length;
+                }
+                None => {
+                    out.push(data[pos]);
+                    finder.advance(pos, 1);
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    pad_to_4(&mut out);
+    Ok(out)
+}
+
+fn read_header(data: &[u8], format: Compression) -> Result<(usize, usize)> {
+    if data.len() < 4 || data[0] != format.type_byte() {
+        Err(Error::MalformedCompressedData {
+            reason: format!("expected type byte {:#04X}", format.type_byte()),
+        })?
+    }
+    let size = u32::from_le_bytes([data[1], data[2], data[3], 0]) as usize;
+    if size != 0 {
+        return Ok((size, 4));
+    }
+
+    // Sizes over 16MB (and empty data) are stored in an extra word
+    if data.len() < 8 {
+        Err(Error::MalformedCompressedData {
+            reason: "header is truncated".to_string(),
+        })?
+    }
+    Ok((
+        u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize,
+        8,
+    ))
+}
+
+/// Makes the buffer for decompressed data - the size in the header can't be trusted to reserve
+/// memory up front, since any file starting with a type byte looks compressed, so it's capped to
+/// what the compressed data could reasonably hold and grows from there
+fn output_buffer(size: usize, data: &[u8]) -> Vec<u8> {
+    Vec::with_capacity(size.min(data.len().saturating_mul(8)))
+}
+
+fn write_header(data: &[u8], format: Compression) -> Result<Vec<u8>> {
+    let size = data.len();
+    if size > u32::MAX as usize {
+        Err(Error::MalformedCompressedData {
+            reason: "data is too big to be compressed".to_string(),
+        })?
+    }
+    let mut out = vec![format.type_byte()];
+    if size != 0 && size < 0x1000000 {
+        out.extend(&(size as u32).to_le_bytes()[..3]);
+    } else {
+        out.extend([0, 0, 0]);
+        out.extend((size as u32).to_le_bytes());
+    }
+    Ok(out)
+}
+
+fn next_byte(data: &[u8], pos: &mut usize) -> Result<u8> {
+    let byte = *data.get(*pos).ok_or(Error::MalformedCompressedData {
+        reason: "compressed data ended unexpectedly".to_string(),
+    })?;
+    *pos += 1;
+    Ok(byte)
+}
+
+fn copy_match(out: &mut Vec<u8>, disp: usize, length: usize, size: usize) -> Result<()> {
+    if disp > out.len() {
+        Err(Error::MalformedCompressedData {
+            reason: format!(
+                "back-reference to {} bytes before the start of the data",
+                disp - out.len()
+            ),
+        })?
+    }
+    for _ in 0..length.min(size - out.len()) {
+        out.push(out[out.len() - disp]);
+    }
+    Ok(())
+}
+
+fn pad_to_4(out: &mut Vec<u8>) {
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+}
+
+/// Finds back-references in a 4KB sliding window, using hash chains of the next 3 bytes
+struct MatchFinder<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl<'a> MatchFinder<'a> {
+    const WINDOW: usize = 0x1000;
+    /// Minimum displacement, so the output can be decompressed straight into VRAM (16-bit writes)
+    const MIN_DISP: usize = 2;
+    const NONE: usize = usize::MAX;
+
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            head: vec![Self::NONE; 0x10000],
+            prev: vec![Self::NONE; data.len()],
+        }
+    }
+
+    fn hash(&self, pos: usize) -> Option<usize> {
+        let bytes = self.data.get(pos..pos + 3)?;
+        Some((bytes[0] as usize) << 8 ^ (bytes[1] as usize) << 4 ^ bytes[2] as usize)
+    }
+
+    /// Returns the longest match at `pos` as (length, displacement)
+    fn find(&self, pos: usize, min_len: usize, max_len: usize) -> Option<(usize, usize)> {
+        let hash = self.hash(pos)?;
+        let max_len = max_len.min(self.data.len() - pos);
+        let mut best: Option<(usize, usize)> = None;
+
+        let mut candidate = self.head[hash];
+        while candidate != Self::NONE && pos - candidate <= Self::WINDOW {
+            let disp = pos - candidate;
+            if disp >= Self::MIN_DISP {
+                let length = (0..max_len)
+                    .take_while(|i| self.data[candidate + i] == self.data[pos + i])
+                    .count();
+                if length >= min_len && best.is_none_or(|(c, _)| length > c) {
+                    best = Some((length, disp));
+                    if length == max_len {
+                        break;
+                    }
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        best
+    }
+
+    /// Registers the bytes from `pos` to `pos + length` as possible match starts
+    fn advance(&mut self, pos: usize, length: usize) {
+        for i in pos..pos + length {
+            if let Some(hash) = self.hash(i) {
+                self.prev[i] = self.head[hash];
+                self.head[hash] = i;
+            }
+        }
+    }
+}
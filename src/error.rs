@@ -39,10 +39,154 @@ pub enum Error {
     #[error("Data in file {file} is invalid")]
     MalformedData { file: String },
 
+    /// Section ends before one of its fields
+    #[error("Section {section} of file {file} ends before field {field} at offset {offset:#X}")]
+    TruncatedSection {
+        file: String,
+        section: String,
+        field: String,
+        offset: usize,
+    },
+
+    /// Field has a value that the format doesn't define
+    #[error(
+        "Field {field} at offset {offset:#X} of section {section} in file {file} has unknown value {value:#X}"
+    )]
+    UnknownFieldValue {
+        file: String,
+        section: String,
+        field: String,
+        offset: usize,
+        value: u64,
+    },
+
+    /// Field points to data outside of its section
+    #[error(
+        "Field {field} at offset {offset:#X} of section {section} in file {file} points to {target:#X}, past the end of the section"
+    )]
+    OffsetOutOfBounds {
+        file: String,
+        section: String,
+        field: String,
+        offset: usize,
+        target: usize,
+    },
+
+    /// Archive or ROM doesn't contain the requested file
+    #[error("File {path} doesn't exist in {file}")]
+    FileNotFound { file: String, path: String },
+
+    /// Stored checksum doesn't match the data
+    #[error("Checksum of {file} doesn't match: expected {expected:08X}, got {got:08X}")]
+    ChecksumMismatch {
+        file: String,
+        expected: u32,
+        got: u32,
+    },
+
+    /// File was made with a newer version of nuclear
+    #[error("File {file} has version {version}, but only up to version {max} is supported")]
+    UnsupportedVersion {
+        file: String,
+        version: u32,
+        max: u32,
+    },
+
+    /// LZ10/LZ11 compressed data couldn't be decompressed
+    #[error("Compressed data is invalid: {reason}")]
+    MalformedCompressedData { reason: String },
+
     /// Error when loading project files
     #[error("Error when reading {0}: {1}")]
     FileFormatWrong(PathBuf, String),
 
+    //
+    // Conversion errors
+    //
+    /// Image given for conversion has the wrong size
+    #[error("Image of size {width}x{height} can't be converted: {reason}")]
+    InvalidImageSize {
+        width: usize,
+        height: usize,
+        reason: String,
+    },
+
+    /// Image has more colors than the palette can hold
+    #[error("Image has {got} different colors, but only {max} fit in the palette")]
+    TooManyColors { max: usize, got: usize },
+
+    /// Image was expected to use a palette, but stores colors directly
+    #[error("Image must be an indexed (paletted) PNG")]
+    ImageNotIndexed,
+
+    /// Pixel uses a color that doesn't fit in the palette or the tile's color depth
+    #[error("Pixel at ({x}, {y}) uses color {index}, but the highest usable color is {max}")]
+    ColorIndexTooHigh {
+        x: usize,
+        y: usize,
+        index: usize,
+        max: usize,
+    },
+
+    /// Image's palette has a different color than the palette it's being imported with
+    #[error("Color {index} of the image is {got:?}, but it's {expected:?} in the palette")]
+    PaletteMismatch {
+        index: usize,
+        expected: [u8; 3],
+        got: [u8; 3],
+    },
+
+    /// Image has more unique tiles than a tilemap can reference
+    #[error("Image needs {count} unique tiles, but tilemaps can only reference {limit}")]
+    TooManyTiles { count: usize, limit: usize },
+
+    /// Screens converted together have more unique tiles than a tilemap can reference
+    #[error(
+        "Screens need {count} unique tiles, but tilemaps can only reference {limit} - went over the limit with {}",
+        screens.join(", ")
+    )]
+    TooManySharedTiles {
+        count: usize,
+        limit: usize,
+        /// Screens that added tiles past the limit, in order
+        screens: Vec<String>,
+    },
+
+    /// Tile reference can't be stored in the tilemap's screen mode
+    #[error("Tile reference {index} can't be stored in {mode} mode: {reason}")]
+    InvalidTileRef {
+        index: usize,
+        mode: String,
+        reason: String,
+    },
+
+    /// Tile of an image has colors that aren't in any palette, and there's no room to add them
+    #[error("Tile at ({x}, {y}) has colors that don't fit in any palette")]
+    TileColorsDontFit { x: usize, y: usize },
+
+    //
+    // Rendering errors
+    //
+    /// Data being rendered refers to a tile, palette or color that doesn't exist
+    #[error("{what} uses {kind} {index}, which doesn't exist")]
+    MissingReference {
+        what: String,
+        kind: String,
+        index: usize,
+    },
+
+    /// Tile doesn't have the 64 pixels of an 8x8 tile
+    #[error("Tile {index} has {len} pixels, but tiles need 64")]
+    MalformedTile { index: usize, len: usize },
+
+    /// Raw tile data is shorter than the amount of tiles it should contain
+    #[error("Tile data has {got} bytes, but {expected} are needed for {tiles} tiles")]
+    NotEnoughTileData {
+        tiles: usize,
+        expected: usize,
+        got: usize,
+    },
+
     //
     // Wrappers
     //
@@ -54,7 +198,7 @@ pub enum Error {
     #[error("System error: {0}")]
     IOError(io::Error),
 
-    /// Wrapper for [png::EncodingError::Format]
+    /// Wrapper for [png::EncodingError::Format] and [png::DecodingError::Format]
     #[error("PNG format error - {0}")]
     PngFormatError(String),
 
@@ -66,6 +210,10 @@ pub enum Error {
     #[error("PNG format error - image data exceeded limits of image")]
     PngLimitError,
 
+    /// Wrapper for [gif::EncodingError]
+    #[error("GIF encoding error: {0}")]
+    GifError(gif::EncodingError),
+
     /// Wrapper for [serde_json::Error]
     #[error("Saving or loading JSON file failed: {0}")]
     SerdeError(serde_json::Error),
@@ -99,6 +247,26 @@ impl From<png::EncodingError> for Error {
     }
 }
 
+impl From<png::DecodingError> for Error {
+    fn from(error: png::DecodingError) -> Self {
+        match error {
+            png::DecodingError::IoError(c) => Self::IOError(c),
+            png::DecodingError::Format(c) => Self::PngFormatError(c.to_string()),
+            png::DecodingError::Parameter(c) => Self::PngError(c),
+            png::DecodingError::LimitsExceeded => Self::PngLimitError,
+        }
+    }
+}
+
+impl From<gif::EncodingError> for Error {
+    fn from(error: gif::EncodingError) -> Self {
+        match error {
+            gif::EncodingError::Io(c) => Self::IOError(c),
+            c => Self::GifError(c),
+        }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(error: serde_json::Error) -> Self {
         Self::SerdeError(error)
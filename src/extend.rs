@@ -12,6 +12,20 @@ pub enum FileType {
     Animation,
 }
 
+impl FileType {
+    /// Guesses the type of a Nintendo file from its magic
+    pub fn from_magic(magic: &[u8]) -> Option<Self> {
+        Some(match magic {
+            b"RLCN" => Self::Palette,
+            b"RGCN" => Self::Tileset,
+            b"RCSN" => Self::Tilemap,
+            b"RECN" => Self::Frames,
+            b"RNAN" => Self::Animation,
+            _ => None?,
+        })
+    }
+}
+
 impl FormatType {
     pub fn filters(&self) -> Option<(&[&str], &str)> {
         match self {
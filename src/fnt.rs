@@ -0,0 +1,214 @@
+use crate::error::{Error, Result};
+use bytestream::{ByteOrder, StreamReader, StreamWriter};
+
+#[derive(Debug, Clone)]
+/// Directory from a FNT (File Name Table), as used by NARC archives and the NDS ROM file system
+pub struct Directory {
+    /// ID of the directory, starting from 0xF000 for the root
+    pub id: u16,
+    /// Name of the directory (empty for the root)
+    pub name: String,
+    /// ID of the first file in the directory - the rest of files are numbered consecutively
+    pub first_file: u16,
+    pub entries: Vec<DirEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DirEntry {
+    File(String),
+    Dir(Directory),
+}
+
+impl Directory {
+    /// Creates an empty root directory, with files starting from the given ID
+    pub fn root(first_file: u16) -> Self {
+        Self {
+            id: 0xF000,
+            name: String::new(),
+            first_file,
+            entries: vec![],
+        }
+    }
+
+    /// Parses a whole FNT, returning its root directory
+    pub fn from_fnt(data: &[u8], fname: &str, o: ByteOrder) -> Result<Self> {
+        let mut table = data;
+        u32::read_from(&mut table, o)?;
+        u16::read_from(&mut table, o)?;
+        let dir_count = u16::read_from(&mut table, o)?;
+
+        let mut main_table = vec![];
+        let mut table = data;
+        for _ in 0..dir_count {
+            let offset = u32::read_from(&mut table, o)? as usize;
+            let first_file = u16::read_from(&mut table, o)?;
+            u16::read_from(&mut table, o)?; // Parent ID
+            main_table.push((offset, first_file));
+        }
+
+        Self::read_dir(data, fname, &main_table, 0xF000, String::new(), o, 0)
+    }
+
+    fn read_dir(
+        data: &[u8],
+        fname: &str,
+        main_table: &[(usize, u16)],
+        id: u16,
+        name: String,
+        o: ByteOrder,
+        depth: usize,
+    ) -> Result<Self> {
+        let malformed = |offset: usize| Error::MalformedData {
+            file: format!("{} (directory at {:#X})", fname, offset),
+        };
+
+        let &(offset, first_file) = id
+            .checked_sub(0xF000)
+            .and_then(|c| main_table.get(c as usize))
+            .ok_or_else(|| malformed(0))?; // Only the root can be missing here
+                                           // Avoid infinite recursion with directories that contain themselves
+        if depth > main_table.len() {
+            Err(malformed(offset))?
+        }
+        let mut sub_table = data.get(offset..).ok_or_else(|| malformed(offset))?;
+
+        let mut entries = vec![];
+        let mut ids = first_file..=u16::MAX;
+        loop {
+            let kind = u8::read_from(&mut sub_table, o).map_err(|_| malformed(offset))?;
+            if kind == 0 {
+                break;
+            }
+            let len = (kind & 0x7F) as usize;
+            let entry_name = sub_table.get(..len).ok_or_else(|| malformed(offset))?;
+            let entry_name = String::from_utf8_lossy(entry_name).to_string();
+            sub_table = &sub_table[len..];
+            if kind & 0x80 == 0 {
+                // Files are numbered consecutively, so their IDs can't go past the last one
+                ids.next().ok_or_else(|| malformed(offset))?;
+                entries.push(DirEntry::File(entry_name));
+            } else {
+                let sub_id = u16::read_from(&mut sub_table, o).map_err(|_| malformed(offset))?;
+                if !(0xF000..0xF000 + main_table.len()).contains(&(sub_id as usize)) {
+                    Err(malformed(offset))?
+                }
+                entries.push(DirEntry::Dir(Self::read_dir(
+                    data,
+                    fname,
+                    main_table,
+                    sub_id,
+                    entry_name,
+                    o,
+                    depth + 1,
+                )?));
+            }
+        }
+
+        Ok(Self {
+            id,
+            name,
+            first_file,
+            entries,
+        })
+    }
+
+    /// Creates a FNT with this directory as the root
+    pub fn to_fnt(&self, o: ByteOrder) -> Result<Vec<u8>> {
+        let mut dirs = vec![];
+        self.collect_dirs(0xF000, &mut dirs);
+        dirs.sort_by_key(|c| c.0.id);
+        // Directory IDs are indexes into the main table, so they can't have gaps
+        for (i, (dir, _)) in dirs.iter().enumerate() {
+            if dir.id as usize != 0xF000 + i {
+                Err(Error::Generic(format!(
+                    "Directory \"{}\" has ID {:#X}, but directories must be numbered from 0xF000 without gaps",
+                    dir.name, dir.id
+                )))?
+            }
+        }
+
+        let mut main_table = vec![];
+        let mut sub_tables = vec![];
+        let main_size = dirs.len() * 8;
+        for (dir, parent) in &dirs {
+            ((main_size + sub_tables.len()) as u32).write_to(&mut main_table, o)?;
+            dir.first_file.write_to(&mut main_table, o)?;
+            if dir.id == 0xF000 {
+                (dirs.len() as u16).write_to(&mut main_table, o)?;
+            } else {
+                parent.write_to(&mut main_table, o)?;
+            }
+
+            for entry in &dir.entries {
+                let (name, is_dir) = match entry {
+                    DirEntry::File(c) => (c, false),
+                    DirEntry::Dir(c) => (&c.name, true),
+                };
+                if name.is_empty() || name.len() > 0x7F {
+                    Err(Error::Generic(format!(
+                        "File name \"{}\" must be between 1 and 127 bytes long",
+                        name
+                    )))?
+                }
+                sub_tables.push(name.len() as u8 | if is_dir { 0x80 } else { 0 });
+                sub_tables.extend(name.as_bytes());
+                if let DirEntry::Dir(c) = entry {
+                    c.id.write_to(&mut sub_tables, o)?;
+                }
+            }
+            sub_tables.push(0);
+        }
+
+        main_table.extend(sub_tables);
+        Ok(main_table)
+    }
+
+    fn collect_dirs<'a>(&'a self, parent: u16, out: &mut Vec<(&'a Directory, u16)>) {
+        out.push((self, parent));
+        for entry in &self.entries {
+            if let DirEntry::Dir(c) = entry {
+                c.collect_dirs(self.id, out);
+            }
+        }
+    }
+
+    /// Lists every file under this directory as (path, ID), paths being separated by `/`
+    pub fn files(&self) -> Vec<(String, u16)> {
+        let mut out = vec![];
+        self.collect_files("", &mut out);
+        out
+    }
+
+    fn collect_files(&self, prefix: &str, out: &mut Vec<(String, u16)>) {
+        let mut ids = self.first_file..=u16::MAX;
+        for entry in &self.entries {
+            match entry {
+                DirEntry::File(c) => {
+                    // Files past the last ID can't be referenced, and parsed tables never have them
+                    let Some(id) = ids.next() else {
+                        continue;
+                    };
+                    out.push((format!("{}{}", prefix, c), id));
+                }
+                DirEntry::Dir(c) => c.collect_files(&format!("{}{}/", prefix, c.name), out),
+            }
+        }
+    }
+
+    /// Gets the ID of the file at the given path, relative to this directory
+    pub fn find(&self, path: &str) -> Option<u16> {
+        let path = path.trim_start_matches('/');
+        self.files()
+            .into_iter()
+            .find(|(c, _)| c == path)
+            .map(|(_, id)| id)
+    }
+
+    /// Gets the path of the file with the given ID, if it has a name
+    pub fn path_of(&self, id: u16) -> Option<String> {
+        self.files()
+            .into_iter()
+            .find(|(_, c)| *c == id)
+            .map(|(path, _)| path)
+    }
+}
@@ -4,11 +4,12 @@
 // that said, export::export_image *is* useful
 
 use crate::{
-    error::Result,
-    img::{ColorBGR555, NCGR, NCLR, NSCR},
+    error::{Error, Result},
+    img::{nanr::RenderedFrame, ColorBGR555, NCGR, NCLR, NSCR},
 };
 use png::{BitDepth, ColorType, Encoder};
 use std::{
+    collections::HashMap,
     fs::{self, File},
     io::{BufWriter, Write},
     path::PathBuf,
@@ -55,7 +56,7 @@ pub fn export_tilesheet<W: Write>(
     width: usize,
     transparency: bool,
 ) -> Result<()> {
-    let img_data = tiles.tiles.render(tiles.is_8_bit, None, width);
+    let img_data = tiles.tiles.render(tiles.is_8_bit, None, width)?;
     let height = (img_data.len() / 0x8 / width) as u32;
 
     let w = &mut BufWriter::new(f);
@@ -89,7 +90,7 @@ pub fn export_tilemap<W: Write>(f: &mut W, pal: &NCLR, tiles: &NCGR, map: &NSCR)
     encoder.set_color(ColorType::Rgb);
 
     let mut writer = encoder.write_header()?;
-    writer.write_image_data(&map.render(pal, tiles).unwrap())?;
+    writer.write_image_data(&map.render(pal, tiles)?)?;
 
     Ok(())
 }
@@ -111,3 +112,109 @@ pub fn export_image<W: Write>(
 
     Ok(())
 }
+
+/// Exports rendered animation frames (see [super::NANR::render_sequence]) as an animated PNG
+pub fn export_apng<W: Write>(f: &mut W, frames: &[RenderedFrame], looping: bool) -> Result<()> {
+    let Some(first) = frames.first() else {
+        Err(Error::Generic(
+            "Can't export an animation with no frames".to_string(),
+        ))?
+    };
+    let w = &mut BufWriter::new(f);
+    let mut encoder = Encoder::new(w, first.image.width as u32, first.image.height as u32);
+
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_animated(frames.len() as u32, if looping { 0 } else { 1 })?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        // The DS runs at 60fps
+        writer.set_frame_delay(frame.duration, 60)?;
+        writer.write_image_data(&frame.image.pixels)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Exports rendered animation frames (see [super::NANR::render_sequence]) as a GIF
+///
+/// If the animation has 255 colors or less, they're kept exactly - otherwise, they get quantized
+pub fn export_gif<W: Write>(f: &mut W, frames: &[RenderedFrame], looping: bool) -> Result<()> {
+    let Some(first) = frames.first() else {
+        Err(Error::Generic(
+            "Can't export an animation with no frames".to_string(),
+        ))?
+    };
+    let (Ok(width), Ok(height)) = (
+        u16::try_from(first.image.width),
+        u16::try_from(first.image.height),
+    ) else {
+        Err(Error::InvalidImageSize {
+            width: first.image.width,
+            height: first.image.height,
+            reason: "GIFs can't be bigger than 65535x65535".to_string(),
+        })?
+    };
+
+    // Index 0 is used for transparency
+    let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut palette = vec![0, 0, 0];
+    'outer: for frame in frames {
+        for px in frame.image.pixels.chunks(4) {
+            if px[3] != 0 && !colors.contains_key(&[px[0], px[1], px[2]]) {
+                if colors.len() == 255 {
+                    colors.clear();
+                    break 'outer;
+                }
+                colors.insert([px[0], px[1], px[2]], colors.len() as u8 + 1);
+                palette.extend(&px[..3]);
+            }
+        }
+    }
+
+    let mut encoder = gif::Encoder::new(
+        f,
+        width,
+        height,
+        if colors.is_empty() { &[] } else { &palette },
+    )?;
+    encoder.set_repeat(if looping {
+        gif::Repeat::Infinite
+    } else {
+        gif::Repeat::Finite(0)
+    })?;
+
+    // Time elapsed so far in 60fps frames, so rounding errors don't add up over the animation
+    let mut elapsed = 0u64;
+    for frame in frames {
+        let mut gif_frame = if colors.is_empty() {
+            let mut pixels = frame.image.pixels.clone();
+            gif::Frame::from_rgba_speed(width, height, &mut pixels, 10)
+        } else {
+            let mut indices = vec![];
+            for px in frame.image.pixels.chunks(4) {
+                indices.push(if px[3] == 0 {
+                    0
+                } else {
+                    colors[&[px[0], px[1], px[2]]]
+                });
+            }
+            let mut c = gif::Frame::from_indexed_pixels(width, height, &indices, Some(0));
+            c.palette = None;
+            c
+        };
+        // GIF delays are in 1/100 of a second, while the DS runs at 60fps
+        let start = (elapsed * 100 + 30) / 60;
+        elapsed += frame.duration as u64;
+        let end = (elapsed * 100 + 30) / 60;
+        gif_frame.delay = u16::try_from(end - start).map_err(|_| {
+            Error::Generic(format!(
+                "A frame lasting {} 60fps frames is too long for a GIF",
+                frame.duration
+            ))
+        })?;
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame)?;
+    }
+    Ok(())
+}
@@ -1,21 +1,27 @@
+use crate::error::{Error, Result};
 use bytestream::{ByteOrder, StreamReader, StreamWriter};
-use std::io;
+use std::io::{self, Read};
 
+pub mod nanr;
+pub mod ncer;
 pub mod ncgr;
 pub mod nclr;
 pub mod nscr;
-
-//pub mod nanr;
-//pub mod ncer;
+pub mod palette;
+pub mod quantize;
 
 /// Only kept for the examples, renders different formats to .png
 pub mod export;
 
+pub use nanr::NANR;
+pub use ncer::NCER;
 pub use ncgr::{Tile, NCGR};
 pub use nclr::NCLR;
 pub use nscr::NSCR;
+pub use palette::PaletteFormat;
+pub use quantize::{QuantizeOptions, QuantizedImage};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 /// Color format the Nintendo DS uses (BGR555)
 pub struct ColorBGR555 {
     pub r: u8,
@@ -27,9 +33,56 @@ pub struct ColorBGR555 {
 
 impl ColorBGR555 {
     /// Converts the color to the RGB888 (24-bit) format
+    ///
+    /// Bits are replicated into the lower part of each channel, so 0x1F becomes 0xFF instead of 0xF8
     pub fn to_rgb888(&self) -> [u8; 3] {
-        [self.r * 0x8, self.g * 0x8, self.b * 0x8]
+        [
+            Self::expand(self.r),
+            Self::expand(self.g),
+            Self::expand(self.b),
+        ]
+    }
+
+    /// Converts a RGB888 (24-bit) color to BGR555
+    pub fn from_rgb888(rgb: [u8; 3], rounding: Rounding) -> Self {
+        Self {
+            r: Self::reduce(rgb[0], rounding),
+            g: Self::reduce(rgb[1], rounding),
+            b: Self::reduce(rgb[2], rounding),
+            x: false,
+        }
+    }
+
+    fn expand(channel: u8) -> u8 {
+        let channel = channel & 0x1F;
+        channel << 3 | channel >> 2
     }
+
+    fn reduce(channel: u8, rounding: Rounding) -> u8 {
+        match rounding {
+            Rounding::Truncate => channel >> 3,
+            Rounding::Nearest => {
+                // The closest value is always next to the truncated one
+                let low = channel >> 3;
+                (low.saturating_sub(1)..=(low + 1).min(0x1F))
+                    .min_by_key(|c| channel.abs_diff(Self::expand(*c)))
+                    .unwrap_or(low)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How RGB888 channels get reduced to the 5 bits each channel has in BGR555
+pub enum Rounding {
+    /// Picks the closest color, best for images with arbitrary colors
+    #[default]
+    Nearest,
+    /// Drops the lower 3 bits of each channel, like most other tools do
+    ///
+    /// This undoes [ColorBGR555::to_rgb888] exactly, and also reads colors from tools that
+    /// multiply by 8 instead of replicating bits, so it's the safest choice for round trips
+    Truncate,
 }
 
 impl StreamReader for ColorBGR555 {
@@ -54,3 +107,125 @@ impl StreamWriter for ColorBGR555 {
         num.write_to(f, o)
     }
 }
+
+#[derive(Debug, Clone)]
+/// Image whose pixels are indices into a palette
+pub struct IndexedImage {
+    pub width: usize,
+    pub height: usize,
+    /// Palette index of each pixel, row by row
+    pub pixels: Vec<u8>,
+    /// Colors of the image's palette, in RGB888
+    pub palette: Vec<[u8; 3]>,
+}
+
+impl IndexedImage {
+    /// Reads an indexed PNG of any bit depth
+    pub fn from_png<R: Read>(f: R) -> Result<Self> {
+        let mut decoder = png::Decoder::new(f);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info()?;
+
+        let info = reader.info();
+        if info.color_type != png::ColorType::Indexed {
+            Err(Error::ImageNotIndexed)?
+        }
+        let width = info.width as usize;
+        let height = info.height as usize;
+        let depth = info.bit_depth as usize;
+        let palette = match &info.palette {
+            Some(c) => c.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            None => vec![],
+        };
+
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf)?;
+
+        // Pixels under 8 bits are packed, leftmost pixel in the highest bits
+        let mask = ((1u16 << depth) - 1) as u8;
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in buf[..frame.buffer_size()].chunks(frame.line_size) {
+            for x in 0..width {
+                let bit = x * depth;
+                pixels.push(row[bit / 8] >> (8 - depth - bit % 8) & mask);
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            palette,
+        })
+    }
+}
+
+/// Reads a PNG of any color type as RGBA (32-bit) data, along with its width and height
+pub(crate) fn read_png_rgba<R: Read>(f: R) -> Result<(Vec<u8>, usize, usize)> {
+    let mut decoder = png::Decoder::new(f);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    let (width, height) = (frame.width as usize, frame.height as usize);
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in buf[..frame.buffer_size()].chunks(frame.line_size) {
+        let row = &row[..width * frame.color_type.samples()];
+        match frame.color_type {
+            png::ColorType::Rgba => rgba.extend(row),
+            png::ColorType::Rgb => row
+                .chunks(3)
+                .for_each(|c| rgba.extend([c[0], c[1], c[2], 0xFF])),
+            png::ColorType::GrayscaleAlpha => row
+                .chunks(2)
+                .for_each(|c| rgba.extend([c[0], c[0], c[0], c[1]])),
+            png::ColorType::Grayscale => row.iter().for_each(|c| rgba.extend([*c, *c, *c, 0xFF])),
+            // Should've been expanded into RGB(A) by the decoder
+            png::ColorType::Indexed => {
+                Err(Error::PngFormatError("palette wasn't expanded".to_string()))?
+            }
+        }
+    }
+
+    Ok((rgba, width, height))
+}
+
+/// Parses a LABL section: a table of offsets, followed by the null-terminated names they point to
+///
+/// The amount of names isn't stored anywhere, so offsets are read until they stop making sense
+pub(crate) fn read_labels(data: &[u8], o: ByteOrder) -> Option<Vec<String>> {
+    let mut offsets = vec![];
+    let mut table = data;
+    while table.len() >= 4 {
+        let offset = u32::read_from(&mut table, o).ok()? as usize;
+        let names_start = (offsets.len() + 1) * 4;
+        if names_start + offset >= data.len() || offsets.last().is_some_and(|c| *c >= offset) {
+            break;
+        }
+        offsets.push(offset);
+    }
+
+    let names = &data[offsets.len() * 4..];
+    let mut labels = vec![];
+    for offset in offsets {
+        let name = names.get(offset..)?;
+        let end = name.iter().position(|c| *c == 0)?;
+        labels.push(String::from_utf8_lossy(&name[..end]).to_string());
+    }
+    Some(labels)
+}
+
+/// Creates a LABL section from a list of names
+pub(crate) fn write_labels(labels: &[String], o: ByteOrder) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    let mut names = vec![];
+    for label in labels {
+        (names.len() as u32).write_to(&mut out, o)?;
+        names.extend(label.as_bytes());
+        names.push(0);
+    }
+    out.extend(names);
+    out.resize(out.len() + (4 - out.len() % 4) % 4, 0);
+    Ok(out)
+}
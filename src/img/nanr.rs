@@ -1,3 +1,592 @@
-//TODO
+use crate::{
+    error::{Error, Result},
+    img::{
+        ncer::{CellImage, NCERCell},
+        read_labels, write_labels, NCER, NCGR, NCLR,
+    },
+    ndsfile::{ExtraSection, NDSFile, NDSFileType, ParseMode, Section, SectionReader},
+};
+use bytestream::{ByteOrder, StreamWriter};
+use std::collections::HashMap;
 
-struct Labels;
+#[derive(Debug, Clone)]
+/// NANR (Nintendo ANimation Resource) cell animation format
+pub struct NANR {
+    /// The animations themselves
+    pub sequences: Vec<AnimSequence>,
+    /// Names of the animations, from the LABL section
+    pub labels: Option<Vec<String>>,
+    /// Contents of the UEXT (user extension) section
+    pub uext: Option<Vec<u8>>,
+    /// Last two words of the ABNK header, usually 0
+    pub abnk_unknown: [u32; 2],
+    /// Format version from the file's header
+    pub version: u16,
+    /// Sections the format doesn't define, only read with [ParseMode::Lenient]
+    pub extra_sections: Vec<ExtraSection>,
+}
+
+#[derive(Debug, Clone)]
+/// A single animation, made of NCER cells
+pub struct AnimSequence {
+    pub frames: Vec<AnimFrame>,
+    /// Frame the animation goes back to when looping
+    pub loop_start: u16,
+    /// Kind of animation - 1 for cell animations, 2 for multi-cell animations
+    pub anim_type: u16,
+    /// Kind of data in each frame - 0 for [FrameData::Index], 1 for [FrameData::Transform] and 2
+    /// for [FrameData::Translation]. Kept even if the sequence has no frames
+    pub frame_type: u16,
+    pub playback: PlaybackMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimFrame {
+    pub data: FrameData,
+    /// How long the frame is shown, in 60fps frames
+    pub duration: u16,
+    /// Padding after the duration, usually 0xBEEF
+    pub padding: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// What to show during a frame. All frames in a sequence must use the same variant
+pub enum FrameData {
+    /// Just a cell
+    Index { cell: u16 },
+    /// A cell with rotation, scale and translation
+    Transform {
+        cell: u16,
+        /// Clockwise rotation, 0x10000 being a full turn
+        rotation: u16,
+        /// Horizontal scale, in 20.12 fixed point
+        scale_x: i32,
+        /// Vertical scale, in 20.12 fixed point
+        scale_y: i32,
+        x: i16,
+        y: i16,
+    },
+    /// A cell with translation
+    Translation {
+        cell: u16,
+        /// Padding after the cell index, usually 0
+        padding: u16,
+        x: i16,
+        y: i16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Plays once
+    Forward,
+    /// Loops from the end back to the loop start frame
+    ForwardLoop,
+    /// Plays forward, then backwards
+    PingPong,
+    /// Plays forward, then backwards, in a loop
+    PingPongLoop,
+}
+
+impl NDSFileType for NANR {
+    /// Creates a NANR struct from the NDSFile given
+    fn from_ndsfile_with(file: &NDSFile, mode: ParseMode) -> Result<Self> {
+        if file.magic != "RNAN" {
+            Err(Error::WrongFileKind {
+                file: file.fname.to_string(),
+                ftype: Some("NANR/NDS animation data".to_string()),
+                expected: "RNAN".to_string(),
+                got: file.magic.to_string(),
+            })?
+        }
+
+        let o = file.byteorder;
+        let mut abnk = None;
+        let mut labels = None;
+        let mut uext = None;
+
+        let mut extra_sections = vec![];
+
+        for (i, section) in file.sections.iter().enumerate() {
+            match section.magic.as_ref() {
+                "KNBA" => abnk = Some(Self::read_abnk(SectionReader::new(file, section))?),
+                "LBAL" => {
+                    labels = Some(read_labels(&section.contents, o).ok_or(
+                        Error::MalformedData {
+                            file: file.fname.clone(),
+                        },
+                    )?)
+                }
+                "TXEU" => uext = Some(section.contents.clone()),
+                _ => mode.unknown_section(file, i, &mut extra_sections)?,
+            }
+        }
+
+        let Some((sequences, abnk_unknown)) = abnk else {
+            Err(Error::MissingRequiredSection {
+                file: file.fname.clone(),
+                s_name: "ABNK".to_string(),
+            })?
+        };
+        Ok(Self {
+            sequences,
+            labels,
+            uext,
+            abnk_unknown,
+            version: file.version,
+            extra_sections,
+        })
+    }
+
+    /// Creates an NDSFile from the NANR struct given
+    fn to_ndsfile(&self, fname: String, o: ByteOrder) -> Result<NDSFile> {
+        let mut sections = vec![Section {
+            magic: "KNBA".to_string(),
+            contents: self.write_abnk(o)?,
+        }];
+        if let Some(c) = &self.labels {
+            sections.push(Section {
+                magic: "LBAL".to_string(),
+                contents: write_labels(c, o)?,
+            });
+        }
+        if let Some(c) = &self.uext {
+            sections.push(Section {
+                magic: "TXEU".to_string(),
+                contents: c.clone(),
+            });
+        }
+        ExtraSection::insert_into(&self.extra_sections, &mut sections);
+
+        Ok(NDSFile {
+            byteorder: o,
+            version: self.version,
+            magic: "RNAN".to_string(),
+            fname,
+            sections,
+        })
+    }
+}
+
+impl NANR {
+    fn read_abnk(mut r: SectionReader) -> Result<(Vec<AnimSequence>, [u32; 2])> {
+        let seq_count = r.read::<u16>("sequence count")?;
+        r.read::<u16>("frame count")?; // Recalculated when writing
+        let seq_offset = r.read_offset("sequence offset", 0)?;
+        let frame_offset = r.read_offset("frame offset", 0)?;
+        let data_offset = r.read_offset("frame data offset", 0)?;
+        let unknown = [r.read::<u32>("unknown")?, r.read::<u32>("unknown")?];
+
+        let mut sequences = vec![];
+        for i in 0..seq_count as usize {
+            r.seek(seq_offset + i * 0x10);
+            let frame_count = r.read::<u16>("frame count")?;
+            let loop_start = r.read::<u16>("loop start")?;
+            let frame_type = r.read::<u16>("frame type")?;
+            if frame_type > 2 {
+                Err(r.unknown_value("frame type", frame_type))?
+            }
+            let anim_type = r.read::<u16>("animation type")?;
+            let playback_value = r.read::<u32>("playback mode")?;
+            let playback = PlaybackMode::new(playback_value)
+                .ok_or_else(|| r.unknown_value("playback mode", playback_value))?;
+            let frames_start = r.read_offset("frames offset", frame_offset)?;
+
+            let mut frames = vec![];
+            for j in 0..frame_count as usize {
+                r.seek(frames_start + j * 8);
+                let offset = r.read_offset("frame data offset", data_offset)?;
+                let duration = r.read::<u16>("frame duration")?;
+                let padding = r.read::<u16>("padding")?;
+
+                r.seek(offset);
+                let data = match frame_type {
+                    0 => FrameData::Index {
+                        cell: r.read::<u16>("cell")?,
+                    },
+                    1 => FrameData::Transform {
+                        cell: r.read::<u16>("cell")?,
+                        rotation: r.read::<u16>("rotation")?,
+                        scale_x: r.read::<i32>("scale X")?,
+                        scale_y: r.read::<i32>("scale Y")?,
+                        x: r.read::<i16>("X")?,
+                        y: r.read::<i16>("Y")?,
+                    },
+                    _ => FrameData::Translation {
+                        cell: r.read::<u16>("cell")?,
+                        padding: r.read::<u16>("padding")?,
+                        x: r.read::<i16>("X")?,
+                        y: r.read::<i16>("Y")?,
+                    },
+                };
+                frames.push(AnimFrame {
+                    data,
+                    duration,
+                    padding,
+                });
+            }
+
+            sequences.push(AnimSequence {
+                frames,
+                loop_start,
+                anim_type,
+                frame_type,
+                playback,
+            });
+        }
+        Ok((sequences, unknown))
+    }
+
+    fn write_abnk(&self, o: ByteOrder) -> Result<Vec<u8>> {
+        let mut seq_buffer = vec![];
+        let mut frame_buffer = vec![];
+        let mut data_buffer = vec![];
+        // Frames that show the same thing share their data
+        let mut data_offsets: HashMap<&FrameData, u32> = HashMap::new();
+
+        for seq in &self.sequences {
+            seq.check_frame_types()?;
+            let frame_type = seq.frame_type;
+            (seq.frames.len() as u16).write_to(&mut seq_buffer, o)?;
+            seq.loop_start.write_to(&mut seq_buffer, o)?;
+            frame_type.write_to(&mut seq_buffer, o)?;
+            seq.anim_type.write_to(&mut seq_buffer, o)?;
+            seq.playback.to_u32().write_to(&mut seq_buffer, o)?;
+            (frame_buffer.len() as u32).write_to(&mut seq_buffer, o)?;
+
+            for frame in &seq.frames {
+                let offset = match data_offsets.get(&frame.data) {
+                    Some(c) => *c,
+                    None => {
+                        if frame_type != 0 {
+                            data_buffer
+                                .resize(data_buffer.len() + (4 - data_buffer.len() % 4) % 4, 0);
+                        }
+                        let offset = data_buffer.len() as u32;
+                        frame.data.write_to(&mut data_buffer, o)?;
+                        data_offsets.insert(&frame.data, offset);
+                        offset
+                    }
+                };
+                offset.write_to(&mut frame_buffer, o)?;
+                frame.duration.write_to(&mut frame_buffer, o)?;
+                frame.padding.write_to(&mut frame_buffer, o)?;
+            }
+        }
+        data_buffer.resize(data_buffer.len() + (4 - data_buffer.len() % 4) % 4, 0);
+
+        let frame_count: usize = self.sequences.iter().map(|c| c.frames.len()).sum();
+        let seq_offset = 0x18u32;
+        let frame_offset = seq_offset + seq_buffer.len() as u32;
+        let data_offset = frame_offset + frame_buffer.len() as u32;
+
+        let mut abnk = vec![];
+        (self.sequences.len() as u16).write_to(&mut abnk, o)?;
+        (frame_count as u16).write_to(&mut abnk, o)?;
+        seq_offset.write_to(&mut abnk, o)?;
+        frame_offset.write_to(&mut abnk, o)?;
+        data_offset.write_to(&mut abnk, o)?;
+        for word in self.abnk_unknown {
+            word.write_to(&mut abnk, o)?;
+        }
+        abnk.extend(seq_buffer);
+        abnk.extend(frame_buffer);
+        abnk.extend(data_buffer);
+        Ok(abnk)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A rendered animation frame, in RGBA (32-bit) format
+pub struct RenderedFrame {
+    /// Image for this frame. All frames of a sequence share the same size and position
+    pub image: CellImage,
+    /// How long the frame is shown, in 60fps frames
+    pub duration: u16,
+}
+
+impl NANR {
+    /// Renders every frame of the specified sequence, in the order they're played
+    ///
+    /// Ping-pong sequences include the way back, so playing the frames in a loop matches the game
+    pub fn render_sequence(
+        &self,
+        index: usize,
+        ncer: &NCER,
+        ncgr: &NCGR,
+        nclr: &NCLR,
+    ) -> Result<Vec<RenderedFrame>> {
+        let seq = self
+            .sequences
+            .get(index)
+            .ok_or_else(|| Error::MissingReference {
+                what: "Render request".to_string(),
+                kind: "sequence".to_string(),
+                index,
+            })?;
+        let mut order: Vec<usize> = (0..seq.frames.len()).collect();
+        if let PlaybackMode::PingPong | PlaybackMode::PingPongLoop = seq.playback {
+            order.extend((1..seq.frames.len().saturating_sub(1)).rev());
+        }
+
+        let mut images = vec![];
+        for frame in &seq.frames {
+            let cell = ncer.render_cell(frame.data.cell() as usize, ncgr, nclr)?;
+            images.push(frame.data.transform(&cell)?);
+        }
+
+        // All frames get drawn on a canvas that fits every one of them
+        let min_x = images.iter().map(|c| c.x).min().unwrap_or(0);
+        let min_y = images.iter().map(|c| c.y).min().unwrap_or(0);
+        let max_x = images
+            .iter()
+            .map(|c| c.x + c.width as i32)
+            .max()
+            .unwrap_or(0);
+        let max_y = images
+            .iter()
+            .map(|c| c.y + c.height as i32)
+            .max()
+            .unwrap_or(0);
+        let width = (max_x - min_x) as usize;
+        let height = (max_y - min_y) as usize;
+        check_object_space(width, height, "the frames of a sequence")?;
+
+        let mut frames = vec![];
+        for i in order {
+            let img = &images[i];
+            let mut pixels = vec![0u8; width * height * 4];
+            let offset_x = (img.x - min_x) as usize;
+            let offset_y = (img.y - min_y) as usize;
+            for y in 0..img.height {
+                let src = y * img.width * 4;
+                let dst = ((y + offset_y) * width + offset_x) * 4;
+                pixels[dst..dst + img.width * 4]
+                    .copy_from_slice(&img.pixels[src..src + img.width * 4]);
+            }
+            frames.push(RenderedFrame {
+                image: CellImage {
+                    width,
+                    height,
+                    x: min_x,
+                    y: min_y,
+                    pixels,
+                },
+                duration: seq.frames[i].duration,
+            });
+        }
+        Ok(frames)
+    }
+}
+
+impl AnimSequence {
+    /// Whether the sequence plays forever
+    pub fn is_looping(&self) -> bool {
+        matches!(
+            self.playback,
+            PlaybackMode::ForwardLoop | PlaybackMode::PingPongLoop
+        )
+    }
+
+    /// Checks that every frame in the sequence has data of the sequence's [frame type](Self::frame_type)
+    pub fn check_frame_types(&self) -> Result<()> {
+        if self
+            .frames
+            .iter()
+            .any(|c| c.data.frame_type() != self.frame_type)
+        {
+            Err(Error::Generic(format!(
+                "All frames in an animation sequence of frame type {} must be of that type",
+                self.frame_type
+            )))?
+        }
+        Ok(())
+    }
+}
+
+impl AnimFrame {
+    /// Gets the NCER cell shown in this frame
+    pub fn cell<'a>(&self, ncer: &'a NCER) -> Option<&'a NCERCell> {
+        ncer.cells.get(self.data.cell() as usize)
+    }
+}
+
+impl FrameData {
+    /// Index of the NCER cell shown in this frame
+    pub fn cell(&self) -> u16 {
+        match self {
+            Self::Index { cell } => *cell,
+            Self::Transform { cell, .. } => *cell,
+            Self::Translation { cell, .. } => *cell,
+        }
+    }
+
+    /// Applies the frame's rotation, scale and translation to a rendered cell
+    ///
+    /// Scales that would make the cell bigger than the object space are an error.
+    pub fn transform(&self, img: &CellImage) -> Result<CellImage> {
+        let (rotation, scale_x, scale_y, x, y) = match *self {
+            Self::Index { .. } => return Ok(img.clone()),
+            Self::Translation { x, y, .. } => {
+                return Ok(CellImage {
+                    x: img.x + x as i32,
+                    y: img.y + y as i32,
+                    ..img.clone()
+                })
+            }
+            Self::Transform {
+                rotation,
+                scale_x,
+                scale_y,
+                x,
+                y,
+                ..
+            } => (rotation, scale_x, scale_y, x, y),
+        };
+
+        let angle = rotation as f64 / 65536.0 * std::f64::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        let sx = scale_x as f64 / 4096.0;
+        let sy = scale_y as f64 / 4096.0;
+        if sx == 0.0 || sy == 0.0 {
+            return Ok(CellImage {
+                width: 0,
+                height: 0,
+                x: x as i32,
+                y: y as i32,
+                pixels: vec![],
+            });
+        }
+
+        // Transforms a point relative to the cell's origin
+        let forward = |px: f64, py: f64| {
+            let (px, py) = (px * sx, py * sy);
+            (px * cos - py * sin, px * sin + py * cos)
+        };
+        let left = img.x as f64;
+        let top = img.y as f64;
+        let right = left + img.width as f64;
+        let bottom = top + img.height as f64;
+        let corners = [
+            forward(left, top),
+            forward(right, top),
+            forward(left, bottom),
+            forward(right, bottom),
+        ];
+        let min_x = corners.iter().map(|c| c.0).fold(f64::MAX, f64::min).floor();
+        let min_y = corners.iter().map(|c| c.1).fold(f64::MAX, f64::min).floor();
+        let max_x = corners.iter().map(|c| c.0).fold(f64::MIN, f64::max).ceil();
+        let max_y = corners.iter().map(|c| c.1).fold(f64::MIN, f64::max).ceil();
+        // Sizes are checked before converting them, since scales come straight from the file
+        let width = (max_x - min_x) as usize;
+        let height = (max_y - min_y) as usize;
+        check_object_space(width, height, "a transformed cell")?;
+        let (min_x, min_y) = (min_x as i32, min_y as i32);
+
+        // Every output pixel samples the source pixel it comes from
+        let mut pixels = vec![0u8; width * height * 4];
+        for j in 0..height {
+            for i in 0..width {
+                let dx = (min_x + i as i32) as f64 + 0.5;
+                let dy = (min_y + j as i32) as f64 + 0.5;
+                let px = (dx * cos + dy * sin) / sx - left;
+                let py = (-dx * sin + dy * cos) / sy - top;
+                if px < 0.0 || py < 0.0 || px >= img.width as f64 || py >= img.height as f64 {
+                    continue;
+                }
+                let src = (py as usize * img.width + px as usize) * 4;
+                let dst = (j * width + i) * 4;
+                pixels[dst..dst + 4].copy_from_slice(&img.pixels[src..src + 4]);
+            }
+        }
+
+        Ok(CellImage {
+            width,
+            height,
+            x: min_x + x as i32,
+            y: min_y + y as i32,
+            pixels,
+        })
+    }
+
+    fn frame_type(&self) -> u16 {
+        match self {
+            Self::Index { .. } => 0,
+            Self::Transform { .. } => 1,
+            Self::Translation { .. } => 2,
+        }
+    }
+
+    fn write_to(&self, f: &mut Vec<u8>, o: ByteOrder) -> Result<()> {
+        match self {
+            Self::Index { cell } => cell.write_to(f, o)?,
+            Self::Transform {
+                cell,
+                rotation,
+                scale_x,
+                scale_y,
+                x,
+                y,
+            } => {
+                cell.write_to(f, o)?;
+                rotation.write_to(f, o)?;
+                scale_x.write_to(f, o)?;
+                scale_y.write_to(f, o)?;
+                x.write_to(f, o)?;
+                y.write_to(f, o)?;
+            }
+            Self::Translation {
+                cell,
+                padding,
+                x,
+                y,
+            } => {
+                cell.write_to(f, o)?;
+                padding.write_to(f, o)?;
+                x.write_to(f, o)?;
+                y.write_to(f, o)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PlaybackMode {
+    pub fn new(value: u32) -> Option<Self> {
+        use PlaybackMode::*;
+        Some(match value {
+            1 => Forward,
+            2 => ForwardLoop,
+            3 => PingPong,
+            4 => PingPongLoop,
+            _ => None?,
+        })
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        use PlaybackMode::*;
+        match self {
+            Forward => 1,
+            ForwardLoop => 2,
+            PingPong => 3,
+            PingPongLoop => 4,
+        }
+    }
+}
+
+/// Size of the area objects can be placed in, which no animation frame can be bigger than
+const OBJECT_SPACE: [usize; 2] = [512, 256];
+
+fn check_object_space(width: usize, height: usize, what: &str) -> Result<()> {
+    if width > OBJECT_SPACE[0] || height > OBJECT_SPACE[1] {
+        Err(Error::InvalidImageSize {
+            width,
+            height,
+            reason: format!(
+                "{} can't be bigger than the {}x{} object space",
+                what, OBJECT_SPACE[0], OBJECT_SPACE[1]
+            ),
+        })?
+    }
+    Ok(())
+}
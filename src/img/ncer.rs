@@ -1,39 +1,72 @@
 use crate::{
     error::{Error, Result},
-    ndsfile::NDSFile,
+    img::{ncgr::NCGRTiles, read_labels, write_labels, NCGR, NCLR},
+    ndsfile::{ExtraSection, NDSFile, NDSFileType, ParseMode, Section, SectionReader},
 };
+use bytestream::{ByteOrder, StreamWriter};
 
 #[derive(Debug, Clone)]
+/// NCER (Nintendo CEll Resource) sprite layout format
 pub struct NCER {
-    pub sprites: Vec<Vec<NCERCell>>,
-    pub bounds: u32,
+    /// The cells themselves, each of them being a group of OAM objects
+    pub cells: Vec<NCERCell>,
+    /// Attributes of the cell bank - bit 0 tells whether every cell has a bounding box, see
+    /// [NCER::has_bounds]
+    pub bank_attributes: u16,
+    /// How the tile numbers of each object map to the NCGR data
+    pub mapping: CharMapping,
+    /// VRAM transfer table, with the part of the character data each cell loads into VRAM
+    pub vram_transfer: Option<Vec<u8>>,
+    /// User extended attributes (UCAT block) at the end of the CEBK section, set per cell by
+    /// the game's tools
+    pub extended: Option<Vec<u8>>,
+    /// Names of the cells, from the LABL section
+    pub labels: Option<Vec<String>>,
+    /// Contents of the UEXT (user extension) section
+    pub uext: Option<Vec<u8>>,
+    /// Format version from the file's header
+    pub version: u16,
+    /// Sections the format doesn't define, only read with [ParseMode::Lenient]
+    pub extra_sections: Vec<ExtraSection>,
 }
 
 #[derive(Debug, Clone)]
-pub enum NCERCell {
-    Packed([u8; 3]),
-    RotScaleEnabled(RotScaleCell),
-    RotScaleDisabled(CellInternals),
+/// A single cell, made of one or more OAM objects
+pub struct NCERCell {
+    pub objects: Vec<CellInternals>,
+    /// Raw cell attributes (flip usage flags and bounding sphere radius)
+    pub attributes: u16,
+    /// Bounding box of the cell, only present if [NCER::has_bounds] is true
+    pub bounds: Option<CellBounds>,
 }
 
-// most likely not used in NCER due to the rotation/scaling attribute missing
-#[derive(Debug, Clone)]
-pub struct RotScaleCell {
-    _do_not_construct: (),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellBounds {
+    pub max_x: i16,
+    pub max_y: i16,
+    pub min_x: i16,
+    pub min_y: i16,
 }
 
 #[derive(Debug, Clone)]
+/// An OAM object, as stored in the three OAM attributes
 pub struct CellInternals {
-    pub x_coord: u8,
-    pub y_coord: u8,
+    pub x_coord: i16, // 9-bit, signed
+    pub y_coord: i8,
+    pub rot_scale: bool,
+    /// If [CellInternals::rot_scale] is set, this is the double size flag instead
     pub disable: bool,
     pub mode: u8, // 2-bit
     pub mosaic: bool,
     pub is_8_bit: bool,
     pub shape: CellShape, // 2-bit + 2-bit
+    /// If [CellInternals::rot_scale] is set, this is the lower 3 bits of the rot/scale parameter
     pub unused_attr1: u8, // 3-bit
     pub flip_x: bool,
     pub flip_y: bool,
+    pub tile: u16,    // 10-bit
+    pub priority: u8, // 2-bit
+    pub palette: u8,  // 4-bit
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,40 +85,453 @@ pub enum CellShape {
     Cell32x64,
 }
 
-impl NCER {
-    pub fn from_ndsfile(file: &NDSFile) -> Result<Self> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Character mapping mode for objects - in 1D mode, the size is the tile number boundary
+pub enum CharMapping {
+    Map1D32K,
+    Map1D64K,
+    Map1D128K,
+    Map1D256K,
+    Map2D,
+}
+
+impl NDSFileType for NCER {
+    /// Creates an NCER struct from the NDSFile given
+    fn from_ndsfile_with(file: &NDSFile, mode: ParseMode) -> Result<Self> {
         if file.magic != "RECN" {
             Err(Error::WrongFileKind {
                 file: file.fname.to_string(),
-                ftype: Some("NCER/NDS cell data".to_string().to_string()),
+                ftype: Some("NCER/NDS cell data".to_string()),
                 expected: "RECN".to_string(),
                 got: file.magic.to_string(),
             })?
         }
 
-        //let mut sprites = vec![];
-        let mut bounds = 0;
+        let o = file.byteorder;
+        let mut cebk = None;
+        let mut labl = None;
+        let mut uext = None;
 
-        for section in &file.sections {
-            let mut data: &[u8] = &section.contents;
+        let mut extra_sections = vec![];
+
+        for (i, section) in file.sections.iter().enumerate() {
             match section.magic.as_ref() {
-                "KBEC" => {
-                    todo!("CEBK section")
+                "KBEC" => cebk = Some(Self::read_cebk(SectionReader::new(file, section))?),
+                "LBAL" => labl = Some(&section.contents),
+                "TXEU" => uext = Some(section.contents.clone()),
+                _ => mode.unknown_section(file, i, &mut extra_sections)?,
+            }
+        }
+
+        let Some(mut ncer) = cebk else {
+            Err(Error::MissingRequiredSection {
+                file: file.fname.clone(),
+                s_name: "CEBK".to_string(),
+            })?
+        };
+        if let Some(c) = labl {
+            ncer.labels = Some(read_labels(c, o).ok_or(Error::MalformedData {
+                file: file.fname.clone(),
+            })?);
+        }
+        ncer.uext = uext;
+        ncer.version = file.version;
+        ncer.extra_sections = extra_sections;
+        Ok(ncer)
+    }
+
+    /// Creates an NDSFile from the NCER struct given
+    fn to_ndsfile(&self, fname: String, o: ByteOrder) -> Result<NDSFile> {
+        let mut sections = vec![Section {
+            magic: "KBEC".to_string(),
+            contents: self.write_cebk(o)?,
+        }];
+        if let Some(c) = &self.labels {
+            sections.push(Section {
+                magic: "LBAL".to_string(),
+                contents: write_labels(c, o)?,
+            });
+        }
+        if let Some(c) = &self.uext {
+            sections.push(Section {
+                magic: "TXEU".to_string(),
+                contents: c.clone(),
+            });
+        }
+        ExtraSection::insert_into(&self.extra_sections, &mut sections);
+
+        Ok(NDSFile {
+            byteorder: o,
+            version: self.version,
+            magic: "RECN".to_string(),
+            fname,
+            sections,
+        })
+    }
+}
+
+impl NCER {
+    /// Whether every cell stores a bounding box
+    pub fn has_bounds(&self) -> bool {
+        self.bank_attributes & 1 != 0
+    }
+
+    fn read_cebk(mut r: SectionReader) -> Result<Self> {
+        let cell_count = r.read::<u16>("cell count")? as usize;
+        let bank_attributes = r.read::<u16>("bank attributes")?;
+        let has_bounds = bank_attributes & 1 != 0;
+        let cells_offset = r.read_offset("cell data offset", 0)?;
+        let mapping_value = r.read::<u32>("character mapping")?;
+        let mapping = CharMapping::new(mapping_value)
+            .ok_or_else(|| r.unknown_value("character mapping", mapping_value))?;
+        let vram_offset = r.read_offset("VRAM transfer offset", 0)?;
+        r.read::<u32>("string bank offset")?; // Unused
+        let extended_offset = r.read_offset("extended data offset", 0)?;
+        let end = r.offset() + r.remaining();
+
+        let entry_size = if has_bounds { 0x10 } else { 0x8 };
+        let oam_offset = cells_offset + cell_count * entry_size;
+
+        let mut cells = vec![];
+        for i in 0..cell_count {
+            r.seek(cells_offset + i * entry_size);
+            let obj_count = r.read::<u16>("object count")? as usize;
+            let attributes = r.read::<u16>("cell attributes")?;
+            let offset = r.read_offset("OAM offset", oam_offset)?;
+            let bounds = if has_bounds {
+                Some(CellBounds {
+                    max_x: r.read::<i16>("max X")?,
+                    max_y: r.read::<i16>("max Y")?,
+                    min_x: r.read::<i16>("min X")?,
+                    min_y: r.read::<i16>("min Y")?,
+                })
+            } else {
+                None
+            };
+
+            r.seek(offset);
+            let mut objects = vec![];
+            for _ in 0..obj_count {
+                let attr0 = r.read::<u16>("OAM attribute 0")?;
+                // Shape 3 is prohibited
+                if attr0 >> 14 == 3 {
+                    Err(r.unknown_value("object shape", attr0 >> 14))?
                 }
-                "LBAL" => {
-                    todo!("LABL section")
+                let attrs = [
+                    attr0,
+                    r.read::<u16>("OAM attribute 1")?,
+                    r.read::<u16>("OAM attribute 2")?,
+                ];
+                objects.push(CellInternals::from_oam(attrs)?);
+            }
+
+            cells.push(NCERCell {
+                objects,
+                attributes,
+                bounds,
+            });
+        }
+
+        // Extra blocks are kept as raw data, each one lasting until the next one starts
+        let mut block = |start: usize, end: usize, field: &str| -> Result<Option<Vec<u8>>> {
+            if start == 0 {
+                return Ok(None);
+            }
+            r.seek(start);
+            Ok(Some(r.bytes(end - start, field)?.to_vec()))
+        };
+        let vram_end = if extended_offset > vram_offset {
+            extended_offset
+        } else {
+            end
+        };
+        let extended_end = if vram_offset > extended_offset {
+            vram_offset
+        } else {
+            end
+        };
+        let vram_transfer = block(vram_offset, vram_end, "VRAM transfer data")?;
+        let extended = block(extended_offset, extended_end, "extended data")?;
+
+        Ok(Self {
+            cells,
+            bank_attributes,
+            mapping,
+            vram_transfer,
+            extended,
+            labels: None,
+            uext: None,
+            version: NDSFile::DEFAULT_VERSION,
+            extra_sections: vec![],
+        })
+    }
+
+    fn write_cebk(&self, o: ByteOrder) -> Result<Vec<u8>> {
+        let entry_size = if self.has_bounds() { 0x10 } else { 0x8 };
+
+        let mut cell_buffer = vec![];
+        let mut oam_buffer = vec![];
+        for cell in &self.cells {
+            (cell.objects.len() as u16).write_to(&mut cell_buffer, o)?;
+            cell.attributes.write_to(&mut cell_buffer, o)?;
+            (oam_buffer.len() as u32).write_to(&mut cell_buffer, o)?;
+            if self.has_bounds() {
+                let bounds = cell.bounds.unwrap_or(CellBounds {
+                    max_x: 0,
+                    max_y: 0,
+                    min_x: 0,
+                    min_y: 0,
+                });
+                bounds.max_x.write_to(&mut cell_buffer, o)?;
+                bounds.max_y.write_to(&mut cell_buffer, o)?;
+                bounds.min_x.write_to(&mut cell_buffer, o)?;
+                bounds.min_y.write_to(&mut cell_buffer, o)?;
+            }
+            for obj in &cell.objects {
+                for attr in obj.to_oam() {
+                    attr.write_to(&mut oam_buffer, o)?;
                 }
-                "TXEU" => {
-                    todo!("UEXT section")
+            }
+        }
+
+        let cells_offset = 0x18usize;
+        let mut end = cells_offset + self.cells.len() * entry_size + oam_buffer.len();
+        end += (4 - end % 4) % 4;
+        let vram_offset = match &self.vram_transfer {
+            Some(c) => {
+                let offset = end;
+                end += c.len() + (4 - c.len() % 4) % 4;
+                offset
+            }
+            None => 0,
+        };
+        let extended_offset = match &self.extended {
+            Some(_) => end,
+            None => 0,
+        };
+
+        let mut cebk = vec![];
+        (self.cells.len() as u16).write_to(&mut cebk, o)?;
+        self.bank_attributes.write_to(&mut cebk, o)?;
+        (cells_offset as u32).write_to(&mut cebk, o)?;
+        self.mapping.to_u32().write_to(&mut cebk, o)?;
+        (vram_offset as u32).write_to(&mut cebk, o)?;
+        0u32.write_to(&mut cebk, o)?;
+        (extended_offset as u32).write_to(&mut cebk, o)?;
+        cebk.extend(cell_buffer);
+        cebk.extend(oam_buffer);
+        for block in [&self.vram_transfer, &self.extended].into_iter().flatten() {
+            cebk.resize(cebk.len() + (4 - cebk.len() % 4) % 4, 0);
+            cebk.extend(block);
+        }
+        cebk.resize(cebk.len() + (4 - cebk.len() % 4) % 4, 0);
+        Ok(cebk)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A rendered cell, in RGBA (32-bit) format
+pub struct CellImage {
+    pub width: usize,
+    pub height: usize,
+    /// Horizontal position of the image's left edge, relative to the cell's origin
+    pub x: i32,
+    /// Vertical position of the image's top edge, relative to the cell's origin
+    pub y: i32,
+    pub pixels: Vec<u8>,
+}
+
+impl NCER {
+    /// Renders the specified cell to RGBA image data, using the given tiles and palettes
+    ///
+    /// Objects are drawn according to their priority, with earlier objects on top of later ones
+    /// when they have the same priority. Color 0 of every palette is transparent.
+    pub fn render_cell(&self, index: usize, ncgr: &NCGR, nclr: &NCLR) -> Result<CellImage> {
+        let cell = self
+            .cells
+            .get(index)
+            .ok_or_else(|| Error::MissingReference {
+                what: "Render request".to_string(),
+                kind: "cell".to_string(),
+                index,
+            })?;
+        let char_data = ncgr.tiles.to_raw(ncgr.is_8_bit);
+        let lineal = matches!(ncgr.tiles, NCGRTiles::Lineal(_));
+
+        // Canvas covers all objects
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for obj in &cell.objects {
+            let (x, y, w, h) = obj.area();
+            let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((x, y, x + w, y + h));
+            bounds = Some((
+                min_x.min(x),
+                min_y.min(y),
+                max_x.max(x + w),
+                max_y.max(y + h),
+            ));
+        }
+        let (min_x, min_y, max_x, max_y) = bounds.unwrap_or_default();
+        let width = (max_x - min_x) as usize;
+        let height = (max_y - min_y) as usize;
+        let mut pixels = vec![0u8; width * height * 4];
+
+        let mut order: Vec<usize> = (0..cell.objects.len()).collect();
+        order.sort_by_key(|c| std::cmp::Reverse((cell.objects[*c].priority, *c)));
+
+        for (i, obj) in order.into_iter().map(|c| (c, &cell.objects[c])) {
+            let missing = |kind: &str, id: usize| Error::MissingReference {
+                what: format!("Object {} of cell {}", i, index),
+                kind: kind.to_string(),
+                index: id,
+            };
+            let palette_id = if obj.is_8_bit { 0 } else { obj.palette as u16 };
+            let palette = (nclr.palettes.get(&palette_id))
+                .ok_or_else(|| missing("palette", palette_id as usize))?;
+            let (w, h) = obj.shape.dimensions();
+            let (area_x, area_y, area_w, area_h) = obj.area();
+            // Double size objects are drawn centered in their area, without transforming them
+            let x = area_x + (area_w - w as i32) / 2 - min_x;
+            let y = area_y + (area_h - h as i32) / 2 - min_y;
+
+            for py in 0..h {
+                for px in 0..w {
+                    let sx = if obj.flip_x && !obj.rot_scale {
+                        w - 1 - px
+                    } else {
+                        px
+                    };
+                    let sy = if obj.flip_y && !obj.rot_scale {
+                        h - 1 - py
+                    } else {
+                        py
+                    };
+                    let color = (self.pixel_index(obj, &char_data, lineal, sx, sy))
+                        .ok_or_else(|| missing("tile", obj.tile as usize))?;
+                    if color == 0 {
+                        continue;
+                    }
+                    let [r, g, b] = (palette.get(color as usize))
+                        .ok_or_else(|| missing("color", color as usize))?
+                        .to_rgb888();
+                    let pos = ((y as usize + py) * width + x as usize + px) * 4;
+                    pixels[pos..pos + 4].copy_from_slice(&[r, g, b, 255]);
                 }
-                c => Err(Error::UnknownSection {
-                    file: file.fname.clone(),
-                    s_name: c.to_string(),
-                })?,
             }
         }
 
-        todo!();
+        Ok(CellImage {
+            width,
+            height,
+            x: min_x,
+            y: min_y,
+            pixels,
+        })
+    }
+
+    /// Gets the color index of a pixel of an object from raw character data
+    fn pixel_index(
+        &self,
+        obj: &CellInternals,
+        char_data: &[u8],
+        lineal: bool,
+        x: usize,
+        y: usize,
+    ) -> Option<u8> {
+        let (w, _) = obj.shape.dimensions();
+        let base = match self.mapping {
+            CharMapping::Map2D => obj.tile as usize * 0x20,
+            c => (obj.tile as usize * 0x20) << c.boundary_shift(),
+        };
+
+        // Offset of the pixel in the object's data, in pixels
+        let offset = if lineal {
+            y * w + x
+        } else {
+            // Tiles are counted in 32-byte units, so 8-bit tiles take up two
+            let tile_size = if obj.is_8_bit { 2 } else { 1 };
+            let tile = match self.mapping {
+                CharMapping::Map2D => (y / 8) * 32 + (x / 8) * tile_size,
+                _ => ((y / 8) * (w / 8) + x / 8) * tile_size,
+            };
+            let unit_px = if obj.is_8_bit { 0x20 } else { 0x40 };
+            tile * unit_px + (y % 8) * 8 + x % 8
+        };
+
+        if obj.is_8_bit {
+            char_data.get(base + offset).copied()
+        } else {
+            let byte = char_data.get(base + offset / 2)?;
+            Some(if offset % 2 == 0 {
+                byte & 0xF
+            } else {
+                byte >> 4
+            })
+        }
+    }
+}
+
+impl CellInternals {
+    /// Area the object covers as (x, y, width, height), relative to the cell's origin
+    pub fn area(&self) -> (i32, i32, i32, i32) {
+        let (w, h) = self.shape.dimensions();
+        let scale = if self.rot_scale && self.disable { 2 } else { 1 };
+        (
+            self.x_coord as i32,
+            self.y_coord as i32,
+            w as i32 * scale,
+            h as i32 * scale,
+        )
+    }
+
+    /// Parses the three OAM attributes of an object
+    pub fn from_oam(attrs: [u16; 3]) -> Result<Self> {
+        let [attr0, attr1, attr2] = attrs;
+        let x = (attr1 & 0x1FF) as i16;
+        Ok(Self {
+            y_coord: attr0 as u8 as i8,
+            rot_scale: attr0 >> 8 & 1 != 0,
+            disable: attr0 >> 9 & 1 != 0,
+            mode: (attr0 >> 10 & 3) as u8,
+            mosaic: attr0 >> 12 & 1 != 0,
+            is_8_bit: attr0 >> 13 & 1 != 0,
+            shape: CellShape::new((attr0 >> 14) as u8, (attr1 >> 14) as u8)?,
+            x_coord: if x >= 0x100 { x - 0x200 } else { x },
+            unused_attr1: (attr1 >> 9 & 7) as u8,
+            flip_x: attr1 >> 12 & 1 != 0,
+            flip_y: attr1 >> 13 & 1 != 0,
+            tile: attr2 & 0x3FF,
+            priority: (attr2 >> 10 & 3) as u8,
+            palette: (attr2 >> 12) as u8,
+        })
+    }
+
+    /// Converts the object back into its three OAM attributes
+    pub fn to_oam(&self) -> [u16; 3] {
+        let (shape, size) = self.shape.to_bits();
+        let attr0 = self.y_coord as u8 as u16
+            | (self.rot_scale as u16) << 8
+            | (self.disable as u16) << 9
+            | (self.mode as u16 & 3) << 10
+            | (self.mosaic as u16) << 12
+            | (self.is_8_bit as u16) << 13
+            | (shape as u16) << 14;
+        let attr1 = (self.x_coord as u16 & 0x1FF)
+            | (self.unused_attr1 as u16 & 7) << 9
+            | (self.flip_x as u16) << 12
+            | (self.flip_y as u16) << 13
+            | (size as u16) << 14;
+        let attr2 =
+            (self.tile & 0x3FF) | (self.priority as u16 & 3) << 10 | (self.palette as u16) << 12;
+        [attr0, attr1, attr2]
+    }
+
+    /// Index of the rotation/scaling parameters used by this object, if it uses them
+    pub fn rot_scale_param(&self) -> Option<u8> {
+        if self.rot_scale {
+            Some(self.unused_attr1 | (self.flip_x as u8) << 3 | (self.flip_y as u8) << 4)
+        } else {
+            None
+        }
     }
 }
 
@@ -102,13 +548,87 @@ impl CellShape {
             (0, 2) => Cell32x32,
             (1, 2) => Cell32x16,
             (2, 2) => Cell16x32,
-            (0, 3) => Cell32x32,
-            (1, 3) => Cell32x16,
-            (2, 3) => Cell16x32,
+            (0, 3) => Cell64x64,
+            (1, 3) => Cell64x32,
+            (2, 3) => Cell32x64,
             _ => Err(Error::Generic(format!(
                 "Invalid values for CellFullShape: shape={}, size={}",
                 shape, size
             )))?,
         })
     }
+
+    /// Returns the shape and size values used in the OAM attributes
+    pub fn to_bits(&self) -> (u8, u8) {
+        use CellShape::*;
+        match self {
+            Cell8x8 => (0, 0),
+            Cell16x8 => (1, 0),
+            Cell8x16 => (2, 0),
+            Cell16x16 => (0, 1),
+            Cell32x8 => (1, 1),
+            Cell8x32 => (2, 1),
+            Cell32x32 => (0, 2),
+            Cell32x16 => (1, 2),
+            Cell16x32 => (2, 2),
+            Cell64x64 => (0, 3),
+            Cell64x32 => (1, 3),
+            Cell32x64 => (2, 3),
+        }
+    }
+
+    /// Width and height of the object, in pixels
+    pub fn dimensions(&self) -> (usize, usize) {
+        use CellShape::*;
+        match self {
+            Cell8x8 => (8, 8),
+            Cell16x8 => (16, 8),
+            Cell8x16 => (8, 16),
+            Cell16x16 => (16, 16),
+            Cell32x8 => (32, 8),
+            Cell8x32 => (8, 32),
+            Cell32x32 => (32, 32),
+            Cell32x16 => (32, 16),
+            Cell16x32 => (16, 32),
+            Cell64x64 => (64, 64),
+            Cell64x32 => (64, 32),
+            Cell32x64 => (32, 64),
+        }
+    }
+}
+
+impl CharMapping {
+    pub fn new(value: u32) -> Option<Self> {
+        use CharMapping::*;
+        Some(match value {
+            0 => Map1D32K,
+            1 => Map1D64K,
+            2 => Map1D128K,
+            3 => Map1D256K,
+            4 => Map2D,
+            _ => None?,
+        })
+    }
+
+    /// How many bits tile numbers are shifted left by, in 1D mapping
+    pub fn boundary_shift(&self) -> u32 {
+        use CharMapping::*;
+        match self {
+            Map1D32K | Map2D => 0,
+            Map1D64K => 1,
+            Map1D128K => 2,
+            Map1D256K => 3,
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        use CharMapping::*;
+        match self {
+            Map1D32K => 0,
+            Map1D64K => 1,
+            Map1D128K => 2,
+            Map1D256K => 3,
+            Map2D => 4,
+        }
+    }
 }
@@ -1,9 +1,13 @@
 use crate::{
     error::{Error, Result},
-    ndsfile::{NDSFile, NDSFileType, Section},
+    img::{ColorBGR555, IndexedImage, QuantizeOptions, QuantizedImage, Rounding, NCLR},
+    ndsfile::{ExtraSection, NDSFile, NDSFileType, ParseMode, Section, SectionReader},
+};
+use bytestream::{ByteOrder, StreamWriter};
+use std::{
+    io::{Read, Write},
+    ops::Range,
 };
-use bytestream::{ByteOrder, StreamReader, StreamWriter};
-use std::{io::Write, ops::Range};
 
 /// Represents an NDS tile
 pub type Tile = Vec<u8>;
@@ -19,6 +23,19 @@ pub struct NCGR {
     pub has_cpos: bool,
     /// Indicates whether the file's tile amount was set to 0xFFFF - believed to happen only in NCBR files
     pub ncbr_ff: bool,
+    /// Width and height of the tileset in tiles, as stored in the file - see [NCGR::dimensions]
+    pub size: Option<[u16; 2]>,
+    /// First word of the CPOS section, whose meaning is unknown - the tile size and amount after
+    /// it are made from the tiles when writing the file back
+    pub cpos_unknown: u32,
+    /// Character mapping mode the tiles were made for, stored after the color format
+    pub mapping_type: u32,
+    /// Bits of the tile mode field above the mode itself, whose meaning is unknown
+    pub tile_mode_flags: u32,
+    /// Format version from the file's header
+    pub version: u16,
+    /// Sections the format doesn't define, only read with [ParseMode::Lenient]
+    pub extra_sections: Vec<ExtraSection>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,7 +52,7 @@ pub enum NCGRTiles {
 
 impl NDSFileType for NCGR {
     /// Creates an NCGR struct from the NDSFile given
-    fn from_ndsfile(file: &NDSFile) -> Result<Self> {
+    fn from_ndsfile_with(file: &NDSFile, mode: ParseMode) -> Result<Self> {
         if file.magic != "RGCN" {
             Err(Error::WrongFileKind {
                 file: file.fname.to_string(),
@@ -47,62 +64,70 @@ impl NDSFileType for NCGR {
 
         let mut is_8_bit = false;
         let mut has_cpos = false;
+        let mut cpos_unknown = 0;
+        let mut mapping_type = 0;
+        let mut tile_mode_flags = 0;
         let mut ncbr_ff = false;
         let mut lineal_mode = false;
         let mut num_tiles = 0;
-        let mut tiles: Option<Vec<u8>> = None;
-        let o = file.byteorder;
+        let mut size = None;
+        let mut tiles: Option<&[u8]> = None;
+
+        let mut extra_sections = vec![];
 
-        for section in &file.sections {
-            let mut data: &[u8] = &section.contents;
+        for (i, section) in file.sections.iter().enumerate() {
+            let mut r = SectionReader::new(file, section);
             match section.magic.as_ref() {
                 "RAHC" => {
-                    num_tiles = u16::read_from(&mut data, o)?;
-                    let num_tiles_2 = u16::read_from(&mut data, o)?;
-                    is_8_bit = u32::read_from(&mut data, o)? == 4;
+                    let height = r.read::<u16>("height")?;
+                    let width = r.read::<u16>("width")?;
+                    is_8_bit = r.read::<u32>("color format")? == 4;
 
-                    u32::read_from(&mut data, o)?; // Padding
-                    lineal_mode = match u32::read_from(&mut data, o)? & 0xFF {
+                    mapping_type = r.read::<u32>("mapping type")?;
+                    let tile_mode = r.read::<u32>("tile mode")?;
+                    lineal_mode = match tile_mode & 0xFF {
                         0 => false,
                         1 => true,
-                        c => unimplemented!("Unknown tile mode #{}", c),
+                        c => Err(r.unknown_value("tile mode", c))?,
                     };
-                    let tile_data_size = u32::read_from(&mut data, o)?;
-                    u32::read_from(&mut data, o)?; // Unknown, always 0x18
+                    tile_mode_flags = tile_mode & !0xFF;
+                    let tile_data_size = r.read::<u32>("tile data size")?;
+                    // Always 0x18, right after this header
+                    let tile_data_offset = r.read_offset("tile data offset", 0)?;
+                    r.seek(tile_data_offset);
 
                     // For some reason some files do this - maybe only NCBR files?
-                    if num_tiles == 0xFFFF {
+                    if height == 0xFFFF {
                         ncbr_ff = true;
-                        num_tiles = (tile_data_size / if is_8_bit { 0x40 } else { 0x20 }) as u16;
+                        num_tiles = tile_data_size as usize / if is_8_bit { 0x40 } else { 0x20 };
                     } else {
-                        num_tiles *= num_tiles_2
+                        num_tiles = height as usize * width as usize;
+                        size = Some([width, height]);
                     }
 
-                    tiles = Some(vec![]);
-                    let tile_contents = tiles.as_mut().unwrap();
-                    for _ in 0..num_tiles as usize * if is_8_bit { 0x40 } else { 0x20 } {
-                        tile_contents.push(u8::read_from(&mut data, o)?)
-                    }
+                    let tile_size = if is_8_bit { 0x40 } else { 0x20 };
+                    tiles = Some(r.bytes(num_tiles * tile_size, "tile data")?);
                 }
-                "SOPC" => has_cpos = true, //This section contains nothing of interest
-                c => Err(Error::UnknownSection {
-                    file: file.fname.clone(),
-                    s_name: c.to_string(),
-                })?,
+                "SOPC" => {
+                    has_cpos = true;
+                    cpos_unknown = r.read::<u32>("unknown")?;
+                }
+                _ => mode.unknown_section(file, i, &mut extra_sections)?,
             }
         }
 
         if let Some(c) = tiles {
             Ok(Self {
-                tiles: NCGRTiles::from_tile_data(
-                    &mut c.as_ref(),
-                    num_tiles as usize,
-                    lineal_mode,
-                    is_8_bit,
-                ),
+                tiles: NCGRTiles::from_tile_data(c, num_tiles, lineal_mode, is_8_bit)?,
                 is_8_bit,
                 has_cpos,
                 ncbr_ff,
+                size,
+                cpos_unknown,
+                mapping_type,
+                tile_mode_flags,
+                version: file.version,
+                extra_sections,
             })
         } else {
             Err(Error::MissingRequiredSection {
@@ -119,33 +144,23 @@ impl NDSFileType for NCGR {
         if self.ncbr_ff {
             (-1i32).write_to(char_buff, o)?;
         } else {
-            (self.tiles.len(self.is_8_bit) as u16).write_to(char_buff, o)?;
-            if self.is_8_bit { 0x40u16 } else { 0x20 }.write_to(char_buff, o)?;
+            let [width, height] = self.dimensions();
+            height.write_to(char_buff, o)?;
+            width.write_to(char_buff, o)?;
         }
         if self.is_8_bit { 4u32 } else { 3u32 }.write_to(char_buff, o)?;
-        0u32.write_to(char_buff, o)?;
+        self.mapping_type.write_to(char_buff, o)?;
         let tile_data_size;
         match &self.tiles {
             NCGRTiles::Horizontal(c) => {
-                0u32.write_to(char_buff, o)?;
+                self.tile_mode_flags.write_to(char_buff, o)?;
                 tile_data_size = c.len() as u32 * if self.is_8_bit { 0x40 } else { 0x20 };
                 tile_data_size.write_to(char_buff, o)?;
                 0x18u32.write_to(char_buff, o)?;
-                if self.is_8_bit {
-                    for tile in c {
-                        char_buff.write_all(tile)?;
-                    }
-                } else {
-                    for tile in c {
-                        for i in 0..0x20 {
-                            let eightbit = (tile[i * 2 + 1] << 4) + tile[i * 2];
-                            eightbit.write_to(char_buff, o)?;
-                        }
-                    }
-                }
+                char_buff.write_all(&self.tiles.to_raw(self.is_8_bit))?;
             }
             NCGRTiles::Lineal(c) => {
-                1u32.write_to(char_buff, o)?;
+                (self.tile_mode_flags | 1).write_to(char_buff, o)?;
                 tile_data_size = c.len() as u32;
                 tile_data_size.write_to(char_buff, o)?;
                 0x18u32.write_to(char_buff, o)?;
@@ -155,12 +170,13 @@ impl NDSFileType for NCGR {
 
         // CPOS section
         let cpos_buff = &mut vec![];
-        0u32.write_to(cpos_buff, o)?;
+        self.cpos_unknown.write_to(cpos_buff, o)?;
         if self.is_8_bit { 0x40u16 } else { 0x20 }.write_to(cpos_buff, o)?;
-        tile_data_size.write_to(cpos_buff, o)?;
+        (self.tiles.len(self.is_8_bit) as u16).write_to(cpos_buff, o)?;
 
         let mut out = NDSFile {
             byteorder: o,
+            version: self.version,
             magic: "RGCN".to_string(),
             fname,
             sections: vec![Section {
@@ -175,56 +191,280 @@ impl NDSFileType for NCGR {
                 contents: cpos_buff.clone(),
             })
         }
+        ExtraSection::insert_into(&self.extra_sections, &mut out.sections);
 
         Ok(out)
     }
 }
 
+impl NCGR {
+    /// Creates a tileset from an indexed PNG, whose width and height must be multiples of 8
+    ///
+    /// If `palette` is given, every color the image uses must match it (as BGR555).
+    pub fn from_indexed_png<R: Read>(
+        f: R,
+        is_8_bit: bool,
+        lineal_mode: bool,
+        palette: Option<&[ColorBGR555]>,
+    ) -> Result<Self> {
+        Self::from_indexed_image(&IndexedImage::from_png(f)?, is_8_bit, lineal_mode, palette)
+    }
+
+    /// Creates a tileset and the palette it needs from a PNG of any kind, reducing its colors as
+    /// described by `options` - see [QuantizedImage::from_png]
+    ///
+    /// Tilesets can only have one palette, so `options.max_palettes` is ignored.
+    pub fn from_png<R: Read>(
+        f: R,
+        lineal_mode: bool,
+        options: &QuantizeOptions,
+    ) -> Result<(Self, NCLR)> {
+        let options = QuantizeOptions {
+            max_palettes: 1,
+            ..options.clone()
+        };
+        let img = QuantizedImage::from_png(f, &options)?;
+        let nclr = img.palette;
+        let palette = nclr.palettes.get(&0).cloned().unwrap_or_default();
+        let img = IndexedImage {
+            width: img.width,
+            height: img.height,
+            pixels: img.pixels,
+            palette: palette.iter().map(|c| c.to_rgb888()).collect(),
+        };
+        let ncgr = Self::from_indexed_image(&img, options.is_8_bit, lineal_mode, Some(&palette))?;
+        Ok((ncgr, nclr))
+    }
+
+    /// Creates a tileset from an indexed image - see [Self::from_indexed_png]
+    ///
+    /// Horizontal tiles are taken left to right, top to bottom. Lineal data is the image's pixels
+    /// as they are.
+    pub fn from_indexed_image(
+        img: &IndexedImage,
+        is_8_bit: bool,
+        lineal_mode: bool,
+        palette: Option<&[ColorBGR555]>,
+    ) -> Result<Self> {
+        let (width, height) = (img.width, img.height);
+        let size_error = |reason: &str| Error::InvalidImageSize {
+            width,
+            height,
+            reason: reason.to_string(),
+        };
+        if width == 0 || height == 0 {
+            Err(size_error("image is empty"))?
+        }
+        if !width.is_multiple_of(8) || !height.is_multiple_of(8) {
+            Err(size_error("width and height must be multiples of 8"))?
+        }
+        if width / 8 > u16::MAX as usize || height / 8 > u16::MAX as usize {
+            Err(size_error("image is too big"))?
+        }
+        if img.pixels.len() != width * height {
+            Err(size_error(&format!(
+                "expected {} pixels, got {}",
+                width * height,
+                img.pixels.len()
+            )))?
+        }
+
+        let mut max = if is_8_bit { 0xFF } else { 0xF };
+        if let Some(c) = palette {
+            max = max.min(c.len().saturating_sub(1));
+        }
+        let mut used = [false; 0x100];
+        for (i, index) in img.pixels.iter().enumerate() {
+            if *index as usize > max {
+                Err(Error::ColorIndexTooHigh {
+                    x: i % width,
+                    y: i / width,
+                    index: *index as usize,
+                    max,
+                })?
+            }
+            used[*index as usize] = true;
+        }
+        if let Some(palette) = palette {
+            for index in (0..=max).filter(|c| used[*c]) {
+                // Colors missing from the image's palette can't be checked against the NCLR
+                let Some(got) = img.palette.get(index) else {
+                    let i = img
+                        .pixels
+                        .iter()
+                        .position(|c| *c as usize == index)
+                        .unwrap_or_default();
+                    Err(Error::ColorIndexTooHigh {
+                        x: i % width,
+                        y: i / width,
+                        index,
+                        max: img.palette.len().saturating_sub(1),
+                    })?
+                };
+                let (expected, color) = (
+                    palette[index],
+                    ColorBGR555::from_rgb888(*got, Rounding::Truncate),
+                );
+                if (expected.r, expected.g, expected.b) != (color.r, color.g, color.b) {
+                    Err(Error::PaletteMismatch {
+                        index,
+                        expected: expected.to_rgb888(),
+                        got: *got,
+                    })?
+                }
+            }
+        }
+
+        let tiles = if lineal_mode {
+            if is_8_bit {
+                NCGRTiles::Lineal(img.pixels.clone())
+            } else {
+                NCGRTiles::Lineal(img.pixels.chunks(2).map(|c| (c[1] << 4) | c[0]).collect())
+            }
+        } else {
+            let mut tiles = vec![];
+            for tile_y in 0..height / 8 {
+                for tile_x in 0..width / 8 {
+                    let mut tile = Vec::with_capacity(64);
+                    for y in tile_y * 8..tile_y * 8 + 8 {
+                        let start = y * width + tile_x * 8;
+                        tile.extend(&img.pixels[start..start + 8]);
+                    }
+                    tiles.push(tile);
+                }
+            }
+            NCGRTiles::Horizontal(tiles)
+        };
+
+        Ok(Self {
+            tiles,
+            is_8_bit,
+            has_cpos: false,
+            ncbr_ff: false,
+            size: Some([(width / 8) as u16, (height / 8) as u16]),
+            cpos_unknown: 0,
+            mapping_type: 0,
+            tile_mode_flags: 0,
+            version: NDSFile::DEFAULT_VERSION,
+            extra_sections: vec![],
+        })
+    }
+
+    /// Replaces the tiles with the ones from an indexed PNG - see [Self::import_indexed_image]
+    pub fn import_indexed_png<R: Read>(
+        &mut self,
+        f: R,
+        palette: Option<&[ColorBGR555]>,
+    ) -> Result<()> {
+        self.import_indexed_image(&IndexedImage::from_png(f)?, palette)
+    }
+
+    /// Replaces the tiles with the ones from an indexed image as wide as the tileset, keeping its
+    /// color depth, tile layout and the rest of its fields
+    pub fn import_indexed_image(
+        &mut self,
+        img: &IndexedImage,
+        palette: Option<&[ColorBGR555]>,
+    ) -> Result<()> {
+        let width = self.dimensions()[0] as usize * 8;
+        if img.width != width {
+            Err(Error::InvalidImageSize {
+                width: img.width,
+                height: img.height,
+                reason: format!("the tileset is {} pixels wide", width),
+            })?
+        }
+        let lineal_mode = matches!(self.tiles, NCGRTiles::Lineal(_));
+        let imported = Self::from_indexed_image(img, self.is_8_bit, lineal_mode, palette)?;
+        self.tiles = imported.tiles;
+        self.size = imported.size;
+        Ok(())
+    }
+
+    /// Width and height of the tileset in tiles - if the stored size doesn't fit the amount of
+    /// tiles, it's made into rows of 32 tiles (or a single row if that doesn't fit either)
+    pub fn dimensions(&self) -> [u16; 2] {
+        let count = self.tiles.len(self.is_8_bit);
+        match self.size {
+            Some([w, h]) if w as usize * h as usize == count => [w, h],
+            _ if count.is_multiple_of(32) => [32, (count / 32) as u16],
+            _ => [count as u16, 1],
+        }
+    }
+}
+
 impl NCGRTiles {
     /// Parses NCGR tile data into an NCGRTiles
     pub fn from_tile_data(
-        data: &mut &[u8],
+        data: &[u8],
         num_tiles: usize,
         is_lineal: bool,
         is_8_bit: bool,
-    ) -> Self {
-        if is_lineal {
+    ) -> Result<Self> {
+        let tile_size = if is_8_bit { 0x40 } else { 0x20 };
+        if data.len() < num_tiles * tile_size {
+            Err(Error::NotEnoughTileData {
+                tiles: num_tiles,
+                expected: num_tiles * tile_size,
+                got: data.len(),
+            })?
+        }
+
+        Ok(if is_lineal {
             Self::Lineal(data.to_vec())
+        } else if is_8_bit {
+            Self::Horizontal(
+                data.chunks(0x40)
+                    .take(num_tiles)
+                    .map(<[u8]>::to_vec)
+                    .collect(),
+            )
         } else {
-            let mut tilesvec = vec![];
-            if is_8_bit {
-                for _ in 0..num_tiles {
-                    let mut tile = vec![];
-                    for _ in 0..0x40 {
-                        tile.push(u8::read_from(data, ByteOrder::LittleEndian).unwrap());
-                    }
-                    tilesvec.push(tile);
-                }
-            } else {
-                for _ in 0..num_tiles {
-                    let mut tile = vec![];
-                    for _ in 0..0x20 {
-                        let eightbit = u8::read_from(data, ByteOrder::LittleEndian).unwrap();
-                        tile.push(eightbit & 0xF);
-                        tile.push(eightbit >> 4);
+            let tiles = data.chunks(0x20).take(num_tiles);
+            Self::Horizontal(
+                tiles
+                    .map(|tile| tile.iter().flat_map(|c| [c & 0xF, c >> 4]).collect())
+                    .collect(),
+            )
+        })
+    }
+
+    /// Converts the NCGRTiles back into raw character data, as it's laid out in VRAM
+    pub fn to_raw(&self, is_8_bit: bool) -> Vec<u8> {
+        match self {
+            Self::Horizontal(c) => {
+                let mut out = vec![];
+                for tile in c {
+                    if is_8_bit {
+                        out.extend(tile);
+                    } else {
+                        for px in tile.chunks(2) {
+                            out.push((px[1] << 4) + px[0]);
+                        }
                     }
-                    tilesvec.push(tile);
                 }
+                out
             }
-            Self::Horizontal(tilesvec)
+            Self::Lineal(c) => c.clone(),
         }
     }
 
     /// Converts the NCGRTiles into a [Vec<Tile>] to be referred by NSCR
-    pub fn to_tiles(&self, is_8_bit: bool) -> Option<Vec<Tile>> {
+    pub fn to_tiles(&self, is_8_bit: bool) -> Result<Vec<Tile>> {
         match self {
-            NCGRTiles::Horizontal(c) => Some(c.to_vec()),
+            NCGRTiles::Horizontal(c) => Ok(c.to_vec()),
             NCGRTiles::Lineal(_) => {
-                let imgdata = self.render(is_8_bit, None, 32);
+                let imgdata = self.render(is_8_bit, None, 32)?;
                 let height = imgdata.len() / 256 / 8;
-                if imgdata.len() % (256 * 8) != 0 {
+                if !imgdata.len().is_multiple_of(256 * 8) {
                     // this tileset shouldn't be used for NSCR
-                    return None;
+                    Err(Error::InvalidImageSize {
+                        width: 256,
+                        height: imgdata.len() / 256,
+                        reason:
+                            "lineal tilesets need whole rows of 32 tiles to be used by tilemaps"
+                                .to_string(),
+                    })?
                 }
                 let mut tiles = vec![];
                 for i in 0..height {
@@ -232,13 +472,13 @@ impl NCGRTiles {
                         let mut tile = vec![];
                         for k in 0..8 {
                             tile.extend(
-                                &imgdata[(i * 8 + k) * 256 + j * 8..(i * 8 + k) * 256 + j * 8 + 7],
+                                &imgdata[(i * 8 + k) * 256 + j * 8..(i * 8 + k) * 256 + j * 8 + 8],
                             );
                         }
                         tiles.push(tile);
                     }
                 }
-                Some(tiles)
+                Ok(tiles)
             }
         }
     }
@@ -249,24 +489,27 @@ impl NCGRTiles {
         is_8_bit: bool,
         range: Option<Range<usize>>,
         render_width: usize,
-    ) -> Vec<u8> {
+    ) -> Result<Vec<u8>> {
         match self {
             Self::Horizontal(c) => Self::render_tiles(c, range, render_width),
             Self::Lineal(c) => {
                 let tile_size = if is_8_bit { 0x40 } else { 0x20 };
                 let tile_data = match range {
-                    Some(d) => &c[d.start * tile_size..d.end * tile_size],
+                    Some(d) => {
+                        let tiles = Self::check_range(d, c.len() / tile_size)?;
+                        &c[tiles.start * tile_size..tiles.end * tile_size]
+                    }
                     None => c,
                 };
                 if is_8_bit {
-                    tile_data.to_vec()
+                    Ok(tile_data.to_vec())
                 } else {
                     let mut out = vec![];
                     for byte in tile_data {
                         out.push(byte & 0xF);
                         out.push(byte >> 4);
                     }
-                    out
+                    Ok(out)
                 }
             }
         }
@@ -274,14 +517,24 @@ impl NCGRTiles {
 
     /// Converts a vector of [Tile] into indexed image data to be displayed
     pub fn render_tiles(
-        tiles: &Vec<Tile>,
+        tiles: &[Tile],
         range: Option<Range<usize>>,
         render_width: usize,
-    ) -> Vec<u8> {
-        let tiles = match range {
-            Some(d) => &tiles[d],
-            None => tiles,
+    ) -> Result<Vec<u8>> {
+        let (first, tiles) = match range {
+            Some(d) => {
+                let d = Self::check_range(d, tiles.len())?;
+                (d.start, &tiles[d])
+            }
+            None => (0, tiles),
         };
+        if let Some((i, tile)) = tiles.iter().enumerate().find(|(_, c)| c.len() != 64) {
+            Err(Error::MalformedTile {
+                index: first + i,
+                len: tile.len(),
+            })?
+        }
+        let render_width = render_width.max(1);
         let mut imgdata: Vec<u8> = vec![];
 
         let mut current_scanlines = [
@@ -321,7 +574,20 @@ impl NCGRTiles {
                 *scanline = vec![];
             }
         }
-        imgdata
+        Ok(imgdata)
+    }
+
+    /// Makes sure a range of tiles is inside the tileset
+    fn check_range(range: Range<usize>, len: usize) -> Result<Range<usize>> {
+        if range.start > range.end || range.end > len {
+            Err(Error::MissingReference {
+                what: format!("Tile range {}..{}", range.start, range.end),
+                kind: "tile".to_string(),
+                // First tile that doesn't exist, or the start of a backwards range
+                index: if range.end > len { len } else { range.start },
+            })?
+        }
+        Ok(range)
     }
 
     /// Obtain number of tiles
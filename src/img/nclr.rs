@@ -1,15 +1,11 @@
 use crate::{
     error::{Error, Result},
     img::ColorBGR555,
-    ndsfile::{NDSFile, NDSFileType, Section},
+    ndsfile::{ExtraSection, NDSFile, NDSFileType, ParseMode, Section, SectionReader},
 };
 
-use bytestream::{ByteOrder, StreamReader, StreamWriter};
-use std::{
-    collections::BTreeMap,
-    io::{Read, Write},
-    ops::Deref,
-};
+use bytestream::{ByteOrder, StreamWriter};
+use std::{collections::BTreeMap, io::Write, ops::Deref};
 
 #[derive(Debug, Clone)]
 /// NCLR (Nintendo CoLor Resource) palette format
@@ -20,11 +16,28 @@ pub struct NCLR {
     pub is_8_bit: bool,
     /// The amount of colors in each palette
     pub color_amt: u32,
+    /// Indicates whether the palettes are extended palettes, which let 8-bit tiles pick one of
+    /// up to 16 palettes of 256 colors
+    pub is_extended: bool,
+    /// Format version from the file's header
+    pub version: u16,
+    /// The 6 bytes after the PCMP palette count, usually 0xBEEF padding and the offset (8) of
+    /// the palette IDs
+    pub pcmp_unknown: [u8; 6],
+    /// Colors after the last palette listed in the PCMP section, which no palette ID points to
+    pub unreferenced_colors: Vec<ColorBGR555>,
+    /// Bytes between the PLTT header and the colors, for files whose colors don't start right
+    /// after it
+    pub pltt_padding: Vec<u8>,
+    /// Bytes of the PLTT section after its colors, usually alignment padding
+    pub pltt_trailing: Vec<u8>,
+    /// Sections the format doesn't define, only read with [ParseMode::Lenient]
+    pub extra_sections: Vec<ExtraSection>,
 }
 
 impl NDSFileType for NCLR {
     /// Creates a NCLR struct from the NDSFile given
-    fn from_ndsfile(file: &NDSFile) -> Result<Self> {
+    fn from_ndsfile_with(file: &NDSFile, mode: ParseMode) -> Result<Self> {
         if file.magic != "RLCN" {
             Err(Error::WrongFileKind {
                 file: file.fname.clone(),
@@ -37,73 +50,76 @@ impl NDSFileType for NCLR {
         let mut palettes = None;
         let mut ids = None;
         let mut is_8_bit = false;
-        let o = file.byteorder;
+        let mut is_extended = false;
+        let mut pcmp_unknown = Self::PCMP_UNKNOWN;
+        let mut pltt_padding = vec![];
+        let mut pltt_trailing = vec![];
+
+        let mut extra_sections = vec![];
 
-        for section in &file.sections {
-            let mut data: &[u8] = &section.contents;
+        for (i, section) in file.sections.iter().enumerate() {
+            let mut r = SectionReader::new(file, section);
             match section.magic.deref() {
                 "TTLP" => {
-                    let mut palette_vec = vec![];
-
-                    is_8_bit = match u32::read_from(&mut data, o)? {
+                    is_8_bit = match r.read::<u32>("color format")? {
                         3 => false,
                         4 => true,
-                        c => unimplemented!("Unknown color format #{}", c),
+                        c => Err(r.unknown_value("color format", c))?,
                     };
-                    u32::read_from(&mut data, o)?; //padding
-                    let data_size = u32::read_from(&mut data, o)?;
-                    let color_amt = u32::read_from(&mut data, o)?;
+                    is_extended = r.read::<u32>("extended palette flag")? != 0;
+                    let data_size = r.read::<u32>("palette data size")?;
+                    let data_offset = r.read_offset("palette data offset", 0)?;
+                    // Colors can't overlap the header
+                    let padding = (data_offset.checked_sub(r.offset())).ok_or_else(|| {
+                        r.unknown_value("palette data offset", data_offset as u64)
+                    })?;
+                    pltt_padding = r.bytes(padding, "padding")?.to_vec();
 
-                    let mut pos = 0;
-                    let mut palette = vec![];
-                    while pos < data_size {
-                        for _ in 0..color_amt {
-                            palette.push(ColorBGR555::read_from(&mut data, o)?);
-                            pos += 2;
-                        }
-                        palette_vec.push(palette);
-                        palette = vec![];
+                    let mut colors = vec![];
+                    for _ in 0..data_size / 2 {
+                        colors.push(r.read::<ColorBGR555>("color")?);
                     }
-
-                    if is_8_bit && palette_vec.len() != 1 {
-                        Err(Error::MalformedData {
-                            file: file.fname.clone(),
-                        })?
-                    }
-
-                    palettes = Some((palette_vec, color_amt));
+                    pltt_trailing = r.bytes(r.remaining(), "trailing data")?.to_vec();
+                    // 8-bit palettes can have less than 256 colors if the rest aren't used
+                    let color_amt = if is_8_bit {
+                        colors.len().min(0x100)
+                    } else {
+                        0x10
+                    };
+                    palettes = Some((colors, color_amt));
                 }
                 "PMCP" => {
-                    ids = Some(vec![]);
-                    let pal_count = u16::read_from(&mut data, o)?;
+                    let pal_count = r.read::<u16>("palette count")?;
 
-                    // Unknown 6 bytes
-                    let mut pcmp_unk = [0u8; 6];
-                    data.read_exact(&mut pcmp_unk)?;
+                    pcmp_unknown.copy_from_slice(r.bytes(6, "unknown")?);
 
+                    let mut pal_ids = vec![];
                     for _ in 0..pal_count {
-                        ids.as_mut().unwrap().push(u16::read_from(&mut data, o)?);
+                        pal_ids.push(r.read::<u16>("palette ID")?);
                     }
+                    ids = Some(pal_ids);
                 }
-                c => Err(Error::UnknownSection {
-                    file: file.fname.clone(),
-                    s_name: c.to_string(),
-                })?,
+                _ => mode.unknown_section(file, i, &mut extra_sections)?,
             }
         }
         let mut palette_map = BTreeMap::<u16, Vec<ColorBGR555>>::new();
         let mut color_amt = 0;
-        if let Some((pal, amt)) = palettes {
+        let mut unreferenced_colors = vec![];
+        if let Some((colors, amt)) = palettes {
             if let Some(id) = ids {
-                if id.len() > pal.len() {
+                let mut chunks = colors.chunks(amt.max(1));
+                if id.len() > chunks.len() {
                     Err(Error::MalformedData {
                         file: file.fname.clone(),
                     })?
                 }
-                for (i, p) in id.into_iter().enumerate() {
-                    palette_map.insert(p, pal.get(i).unwrap().to_vec());
+                for (p, colors) in id.into_iter().zip(chunks.by_ref()) {
+                    let mut palette = colors.to_vec();
+                    palette.resize(amt, ColorBGR555::default());
+                    palette_map.insert(p, palette);
                 }
-                color_amt = amt;
+                unreferenced_colors = chunks.flatten().copied().collect();
+                color_amt = amt as u32;
             } else {
                 Err(Error::MissingRequiredSection {
                     file: file.fname.clone(),
@@ -120,6 +136,13 @@ impl NDSFileType for NCLR {
             is_8_bit,
             palettes: palette_map,
             color_amt,
+            is_extended,
+            version: file.version,
+            pcmp_unknown,
+            unreferenced_colors,
+            pltt_padding,
+            pltt_trailing,
+            extra_sections,
         })
     }
 
@@ -130,14 +153,16 @@ impl NDSFileType for NCLR {
 
         //PLTT header
         if self.is_8_bit { 4u32 } else { 3u32 }.write_to(&mut pltt_buffer, o)?;
-        0u32.write_to(&mut pltt_buffer, o)?;
-        (self.palettes.len() as u32 * self.color_amt * 2).write_to(&mut pltt_buffer, o)?;
-        self.color_amt.write_to(&mut pltt_buffer, o)?;
+        (self.is_extended as u32).write_to(&mut pltt_buffer, o)?;
+        let color_count =
+            self.palettes.len() * self.color_amt as usize + self.unreferenced_colors.len();
+        (color_count as u32 * 2).write_to(&mut pltt_buffer, o)?;
+        (0x10 + self.pltt_padding.len() as u32).write_to(&mut pltt_buffer, o)?;
+        pltt_buffer.write_all(&self.pltt_padding)?;
 
         //PCMP header
         (self.palettes.len() as u16).write_to(&mut pcmp_buffer, o)?;
-        let pcmp_unk = [0xEFu8, 0xBE, 0x08, 0x00, 0x00, 0x00];
-        pcmp_buffer.write_all(&pcmp_unk)?;
+        pcmp_buffer.write_all(&self.pcmp_unknown)?;
 
         for (id, palette) in &self.palettes {
             id.write_to(&mut pcmp_buffer, o)?;
@@ -145,20 +170,33 @@ impl NDSFileType for NCLR {
                 color.write_to(&mut pltt_buffer, o)?;
             }
         }
+        for color in &self.unreferenced_colors {
+            color.write_to(&mut pltt_buffer, o)?;
+        }
+        pltt_buffer.write_all(&self.pltt_trailing)?;
+        let mut sections = vec![
+            Section {
+                magic: "TTLP".to_string(),
+                contents: pltt_buffer,
+            },
+            Section {
+                magic: "PMCP".to_string(),
+                contents: pcmp_buffer,
+            },
+        ];
+        ExtraSection::insert_into(&self.extra_sections, &mut sections);
+
         Ok(NDSFile {
             byteorder: o,
+            version: self.version,
             magic: "RLCN".to_string(),
             fname,
-            sections: vec![
-                Section {
-                    magic: "TTLP".to_string(),
-                    contents: pltt_buffer,
-                },
-                Section {
-                    magic: "PMCP".to_string(),
-                    contents: pcmp_buffer,
-                },
-            ],
+            sections,
         })
     }
 }
+
+impl NCLR {
+    /// Unknown PCMP bytes found in most files, used for palettes that weren't read from one
+    pub const PCMP_UNKNOWN: [u8; 6] = [0xEF, 0xBE, 0x08, 0x00, 0x00, 0x00];
+}
@@ -1,16 +1,43 @@
 use crate::{
     error::{Error, Result},
-    img::{ColorBGR555, Tile, NCGR, NCLR},
-    ndsfile::{NDSFile, NDSFileType, Section},
+    img::{
+        ncgr::NCGRTiles, ColorBGR555, QuantizeOptions, QuantizedImage, Rounding, Tile, NCGR, NCLR,
+    },
+    ndsfile::{ExtraSection, NDSFile, NDSFileType, ParseMode, Section, SectionReader},
+};
+use bytestream::{ByteOrder, StreamWriter};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
 };
-use bytestream::{ByteOrder, StreamReader, StreamWriter};
 
 #[derive(Debug, Clone)]
 /// NSCR (Nintendo SCreen Resource) tile image format
 pub struct NSCR {
     pub width: u16,
     pub height: u16,
+    /// Kind of background the tilemap is made for, which decides how its entries are stored
+    pub mode: ScreenMode,
+    /// Indicates whether the tilemap uses 8-bit color (true) or 4-bit color (false)
+    pub is_8_bit: bool,
     pub tiles: Vec<TileRef>,
+    /// Format version from the file's header
+    pub version: u16,
+    /// Sections the format doesn't define, only read with [ParseMode::Lenient]
+    pub extra_sections: Vec<ExtraSection>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Kind of background a tilemap is made for
+pub enum ScreenMode {
+    /// Text backgrounds - 16-bit entries with a tile number, flip flags and a palette
+    #[default]
+    Text,
+    /// Affine (rotation/scaling) backgrounds - 8-bit entries with just a tile number, always
+    /// using 256 colors
+    Affine,
+    /// Extended affine backgrounds - 16-bit entries like text backgrounds, using 256 colors
+    ExtendedAffine,
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +49,41 @@ pub struct TileRef {
     pub palette: u8,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// What changed when importing an image onto an existing tilemap, see [NSCR::import_rgba]
+pub struct ImportChanges {
+    /// Amount of tile references that now point to a different tile, palette or flip
+    pub changed_refs: usize,
+    /// Amount of tiles added to the end of the tileset
+    pub new_tiles: usize,
+    /// Amount of colors added to unused slots of the palettes
+    pub new_colors: usize,
+}
+
+#[derive(Debug, Clone)]
+/// Image of a single screen, to be converted along with others by [NSCR::gritify_shared]
+pub struct ScreenImage {
+    /// Name of the screen, used to tell which screens don't fit
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// RGBA (32-bit) image data, row by row
+    pub rgba: Vec<u8>,
+}
+
+impl ScreenImage {
+    /// Reads a screen from a PNG of any color type
+    pub fn from_png<R: Read>(name: impl Into<String>, f: R) -> Result<Self> {
+        let (rgba, width, height) = super::read_png_rgba(f)?;
+        Ok(Self {
+            name: name.into(),
+            width,
+            height,
+            rgba,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 /// A variant of [super::NCGR] with horizontal mode forced (width 256), for NSCR referencing use
 pub struct TilesForNSCR {
@@ -31,34 +93,83 @@ pub struct TilesForNSCR {
     pub is_8_bit: bool,
 }
 
+impl ScreenMode {
+    pub fn new(value: u16) -> Option<Self> {
+        use ScreenMode::*;
+        Some(match value {
+            0 => Text,
+            1 => Affine,
+            2 => ExtendedAffine,
+            _ => None?,
+        })
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        use ScreenMode::*;
+        match self {
+            Text => 0,
+            Affine => 1,
+            ExtendedAffine => 2,
+        }
+    }
+
+    /// Amount of tiles that tilemaps of this mode can reference
+    pub fn max_tiles(&self) -> usize {
+        match self {
+            Self::Affine => 0x100,
+            Self::Text | Self::ExtendedAffine => TileBank::MAX_TILES,
+        }
+    }
+}
+
+/// NSCR magic as stored in the file, which is reversed like in every Nintendo file
+const MAGIC: &str = "RCSN";
+
 impl NDSFileType for NSCR {
-    fn from_ndsfile(file: &NDSFile) -> Result<Self> {
-        if file.magic != "RCSN" {
+    fn from_ndsfile_with(file: &NDSFile, parse_mode: ParseMode) -> Result<Self> {
+        if file.magic != MAGIC {
             Err(Error::WrongFileKind {
                 file: file.fname.to_string(),
                 ftype: Some("NSCR/NDS image data".to_string()),
-                expected: "RSCN".to_string(),
+                expected: MAGIC.to_string(),
                 got: file.magic.to_string(),
             })?
         }
-        let o = file.byteorder;
-
         let mut width = 0;
         let mut height = 0;
+        let mut mode = ScreenMode::Text;
+        let mut is_8_bit = false;
         let mut tiles: Option<Vec<TileRef>> = None;
 
-        for section in &file.sections {
-            let mut data: &[u8] = &section.contents;
+        let mut extra_sections = vec![];
+
+        for (i, section) in file.sections.iter().enumerate() {
+            let mut r = SectionReader::new(file, section);
             match section.magic.as_ref() {
                 "NRCS" => {
-                    width = u16::read_from(&mut data, o)?;
-                    height = u16::read_from(&mut data, o)?;
-                    u32::read_from(&mut data, o)?; // padding?
-                    let data_size = u32::read_from(&mut data, o)?;
+                    width = r.read::<u16>("width")?;
+                    height = r.read::<u16>("height")?;
+                    is_8_bit = r.read::<u16>("color mode")? != 0;
+                    let screen_format = r.read::<u16>("screen format")?;
+                    mode = ScreenMode::new(screen_format)
+                        .ok_or_else(|| r.unknown_value("screen format", screen_format))?;
+                    let data_size = r.read::<u32>("screen data size")?;
 
                     let mut tile_vec = vec![];
+                    if mode == ScreenMode::Affine {
+                        for _ in 0..data_size {
+                            tile_vec.push(TileRef {
+                                tile: r.read::<u8>("tile reference")? as u16,
+                                flip_x: false,
+                                flip_y: false,
+                                palette: 0,
+                            })
+                        }
+                        tiles = Some(tile_vec);
+                        continue;
+                    }
                     for _ in 0..data_size / 2 {
-                        let int = u16::read_from(&mut data, o)?;
+                        let int = r.read::<u16>("tile reference")?;
                         let tile = int & 0x3FF;
                         let flip_x = (int & 0x400) != 0;
                         let flip_y = (int & 0x800) != 0;
@@ -72,10 +183,7 @@ impl NDSFileType for NSCR {
                     }
                     tiles = Some(tile_vec);
                 }
-                c => Err(Error::UnknownSection {
-                    file: file.fname.clone(),
-                    s_name: c.to_string(),
-                })?,
+                _ => parse_mode.unknown_section(file, i, &mut extra_sections)?,
             }
         }
 
@@ -83,7 +191,11 @@ impl NDSFileType for NSCR {
             Ok(Self {
                 width,
                 height,
+                mode,
+                is_8_bit,
                 tiles: c,
+                version: file.version,
+                extra_sections,
             })
         } else {
             Err(Error::MissingRequiredSection {
@@ -94,37 +206,77 @@ impl NDSFileType for NSCR {
     }
 
     fn to_ndsfile(&self, fname: String, o: ByteOrder) -> Result<NDSFile> {
+        self.check_refs()?;
         let scrn_buffer = &mut vec![];
         self.width.write_to(scrn_buffer, o)?;
         self.height.write_to(scrn_buffer, o)?;
-        0u32.write_to(scrn_buffer, o)?;
-        (self.tiles.len() as u32 * 2).write_to(scrn_buffer, o)?;
-        for tile_ref in &self.tiles {
-            let mut int = tile_ref.tile;
-            if tile_ref.flip_x {
-                int += 0x400
+        (self.is_8_bit as u16).write_to(scrn_buffer, o)?;
+        self.mode.to_u16().write_to(scrn_buffer, o)?;
+        if self.mode == ScreenMode::Affine {
+            (self.tiles.len() as u32).write_to(scrn_buffer, o)?;
+            for tile_ref in &self.tiles {
+                (tile_ref.tile as u8).write_to(scrn_buffer, o)?;
             }
-            if tile_ref.flip_y {
-                int += 0x800
+        } else {
+            (self.tiles.len() as u32 * 2).write_to(scrn_buffer, o)?;
+            for tile_ref in &self.tiles {
+                let mut int = tile_ref.tile;
+                if tile_ref.flip_x {
+                    int += 0x400
+                }
+                if tile_ref.flip_y {
+                    int += 0x800
+                }
+                int += (tile_ref.palette as u16) << 12;
+                int.write_to(scrn_buffer, o)?;
             }
-            int += (tile_ref.palette as u16) << 12;
-            int.write_to(scrn_buffer, o)?;
         }
+        let mut sections = vec![Section {
+            magic: "NRCS".to_string(),
+            contents: scrn_buffer.to_vec(),
+        }];
+        ExtraSection::insert_into(&self.extra_sections, &mut sections);
+
         Ok(NDSFile {
             byteorder: o,
-            magic: "RCSN".to_string(),
+            version: self.version,
+            magic: MAGIC.to_string(),
             fname,
-            sections: vec![Section {
-                magic: "NRCS".to_string(),
-                contents: scrn_buffer.to_vec(),
-            }],
+            sections,
         })
     }
 }
 
 impl NSCR {
+    /// Makes sure every tile reference can be stored in the tilemap's screen mode
+    fn check_refs(&self) -> Result<()> {
+        for (index, tile) in self.tiles.iter().enumerate() {
+            let reason = if tile.tile as usize >= self.mode.max_tiles() {
+                format!(
+                    "tile {} is over the limit of {}",
+                    tile.tile,
+                    self.mode.max_tiles() - 1
+                )
+            } else if self.mode == ScreenMode::Affine && (tile.flip_x || tile.flip_y) {
+                "affine tilemaps can't flip tiles".to_string()
+            } else if self.mode == ScreenMode::Affine && tile.palette != 0 {
+                "affine tilemaps can't choose a palette".to_string()
+            } else if tile.palette > 0xF {
+                format!("palette {} is over the limit of 15", tile.palette)
+            } else {
+                continue;
+            };
+            Err(Error::InvalidTileRef {
+                index,
+                mode: format!("{:?}", self.mode),
+                reason,
+            })?
+        }
+        Ok(())
+    }
+
     /// Renders the NSCR to truecolor 24bit image data
-    pub fn render(&self, nclr: &NCLR, ncgr: &NCGR) -> Option<Vec<u8>> {
+    pub fn render(&self, nclr: &NCLR, ncgr: &NCGR) -> Result<Vec<u8>> {
         let tiles = TilesForNSCR {
             tiles: ncgr.tiles.to_tiles(ncgr.is_8_bit)?,
             is_8_bit: ncgr.is_8_bit,
@@ -141,16 +293,37 @@ impl NSCR {
             vec![],
         ];
 
-        for tile in &self.tiles {
-            let palette = &nclr.palettes[&(tile.palette as u16)];
+        for (index, tile) in self.tiles.iter().enumerate() {
+            let missing = |kind: &str, i: usize| Error::MissingReference {
+                what: format!("Tile reference {}", index),
+                kind: kind.to_string(),
+                index: i,
+            };
+            // 256-color tiles only pick a palette when using extended palettes
+            let palette_id = if tiles.is_8_bit && !nclr.is_extended {
+                0
+            } else {
+                tile.palette
+            };
+            let palette = (nclr.palettes.get(&(palette_id as u16)))
+                .ok_or_else(|| missing("palette", palette_id as usize))?;
             let flip_x = tile.flip_x;
             let flip_y = tile.flip_y;
-            let tile = &tiles.tiles[tile.tile as usize];
+            let tile_id = tile.tile as usize;
+            let tile = (tiles.tiles.get(tile_id)).ok_or_else(|| missing("tile", tile_id))?;
+            if tile.len() != 64 {
+                Err(Error::MalformedTile {
+                    index: tile_id,
+                    len: tile.len(),
+                })?
+            }
             for (j, row) in rows.iter_mut().enumerate() {
                 let j_ = if flip_y { 7 - j } else { j };
                 for i in 0..8 {
                     let i = if flip_x { 7 - i } else { i };
-                    row.extend(palette[tile[j_ * 8 + i] as usize].to_rgb888());
+                    let color = tile[j_ * 8 + i] as usize;
+                    let color = palette.get(color).ok_or_else(|| missing("color", color))?;
+                    row.extend(color.to_rgb888());
                 }
             }
             if rows[0].len() / 3 == self.width as usize {
@@ -160,16 +333,562 @@ impl NSCR {
             }
         }
 
-        Some(data)
+        Ok(data)
     }
 
-    pub fn gritify(img: Vec<ColorBGR555>, size: [usize; 2]) -> Self {
-        // Step 1: divide image into tiles
+    /// Imports an edited PNG onto this tilemap, see [Self::import_rgba]
+    pub fn import_png<R: Read>(
+        &mut self,
+        f: R,
+        ncgr: &mut NCGR,
+        nclr: &mut NCLR,
+    ) -> Result<ImportChanges> {
+        let (img, width, height) = super::read_png_rgba(f)?;
+        self.import_rgba(&img, [width, height], ncgr, nclr)
+    }
 
+    /// Imports edited RGBA (32-bit) image data onto this tilemap, changing as little as possible
+    /// so other tilemaps that share the tileset or palette keep working
+    ///
+    /// Tiles that look the same as before keep their reference. Changed tiles reuse any tile of
+    /// the tileset (flipped or not) with any palette that can draw them, and new tiles are only
+    /// added to the end of the tileset. Missing colors go into palette slots that are black and
+    /// not used by any tile. Pixels with alpha under 50% use color 0.
+    ///
+    /// Nothing is modified if the image can't be imported.
+    pub fn import_rgba(
+        &mut self,
+        img: &[u8],
+        size: [usize; 2],
+        ncgr: &mut NCGR,
+        nclr: &mut NCLR,
+    ) -> Result<ImportChanges> {
+        let [width, height] = size;
+        if [width, height] != [self.width as usize, self.height as usize] {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: format!("the tilemap is {}x{}", self.width, self.height),
+            })?
+        }
+        if img.len() != width * height * 4 {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: format!("expected {} pixels, got {}", width * height, img.len() / 4),
+            })?
+        }
+        if width % 8 != 0 || height % 8 != 0 {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: "the size must be a multiple of 8".to_string(),
+            })?
+        }
+        if self.tiles.len() != (width / 8) * (height / 8) {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: format!(
+                    "the tilemap has {} tiles instead of {}",
+                    self.tiles.len(),
+                    (width / 8) * (height / 8)
+                ),
+            })?
+        }
+        let NCGRTiles::Horizontal(tiles) = &ncgr.tiles else {
+            Err(Error::Generic(
+                "Lineal tilesets can't be changed by importing an image".to_string(),
+            ))?
+        };
+
+        let color_amt = if ncgr.is_8_bit { 0x100 } else { 0x10 };
+        let mut bank = TileBank::from_tiles(tiles.clone());
+        let mut palettes = nclr.palettes.clone();
+        let mut refs = self.tiles.clone();
+        let mut changes = ImportChanges::default();
+        let mut used = [false; 0x100];
+        for tile in tiles {
+            for px in tile {
+                used[*px as usize] = true;
+            }
+        }
+        let same = |a: ColorBGR555, b: ColorBGR555| (a.r, a.g, a.b) == (b.r, b.g, b.b);
+        // 256-color tiles only pick a palette when using extended palettes, and affine tilemaps
+        // can't pick one at all
+        let fixed_palette = (ncgr.is_8_bit && !nclr.is_extended) || self.mode == ScreenMode::Affine;
+        let palette_of = |id: u8| if fixed_palette { 0 } else { id as u16 };
+
+        let tiles_x = width / 8;
+        for (i, tile_ref) in refs.iter_mut().enumerate() {
+            let (x, y) = (i % tiles_x * 8, i / tiles_x * 8);
+            let mut pixels = Vec::with_capacity(64);
+            for row in y..y + 8 {
+                for px in img[(row * width + x) * 4..(row * width + x + 8) * 4].chunks(4) {
+                    pixels.push((px[3] >= 0x80).then(|| {
+                        ColorBGR555::from_rgb888([px[0], px[1], px[2]], Rounding::Nearest)
+                    }));
+                }
+            }
+
+            // Leave the reference alone if it still looks the same
+            let matches = |tile: &[u8], palette: &[ColorBGR555]| {
+                pixels.iter().zip(tile).all(|(px, index)| match px {
+                    Some(c) => palette.get(*index as usize).is_some_and(|p| same(*p, *c)),
+                    None => *index == 0,
+                })
+            };
+            let current = bank
+                .tiles
+                .get(tile_ref.tile as usize)
+                .zip(palettes.get(&palette_of(tile_ref.palette)));
+            if let Some((tile, palette)) = current {
+                if matches(&flip_tile(tile, tile_ref.flip_x, tile_ref.flip_y), palette) {
+                    continue;
+                }
+            }
+
+            // Try the tile's palette first, then every other one
+            let mut order: Vec<u16> = palettes.keys().copied().collect();
+            if fixed_palette {
+                order.retain(|c| *c == 0);
+            }
+            order.sort_by_key(|c| *c != tile_ref.palette as u16);
+            let find_index = |palette: &[ColorBGR555], color: ColorBGR555| {
+                // Color 0 is transparent in most layers, so it's only used if nothing else matches
+                let mut found = palette
+                    .iter()
+                    .take(color_amt)
+                    .enumerate()
+                    .filter(|(_, c)| same(**c, color));
+                let first = found.next()?.0;
+                Some(
+                    found
+                        .next()
+                        .map_or(first, |c| if first == 0 { c.0 } else { first }),
+                )
+            };
+            let map_tile = |palette: &[ColorBGR555]| -> Option<Tile> {
+                pixels
+                    .iter()
+                    .map(|px| match px {
+                        Some(c) => find_index(palette, *c).map(|c| c as u8),
+                        None => Some(0),
+                    })
+                    .collect()
+            };
+
+            let mut mapped = order
+                .iter()
+                .find_map(|id| Some((*id, map_tile(&palettes[id])?)));
+            if mapped.is_none() {
+                // Add the missing colors to the first palette that has room for them
+                for id in &order {
+                    let palette = palettes.get_mut(id).unwrap();
+                    let mut missing: Vec<ColorBGR555> = vec![];
+                    for c in pixels.iter().flatten() {
+                        if find_index(palette, *c).is_none()
+                            && !missing.iter().any(|m| same(*m, *c))
+                        {
+                            missing.push(*c);
+                        }
+                    }
+                    let free: Vec<usize> = (1..palette.len().min(color_amt))
+                        .filter(|c| !used[*c] && same(palette[*c], ColorBGR555::default()))
+                        .collect();
+                    if missing.len() > free.len() {
+                        continue;
+                    }
+                    for (slot, color) in free.into_iter().zip(&missing) {
+                        palette[slot] = *color;
+                        used[slot] = true;
+                    }
+                    changes.new_colors += missing.len();
+                    mapped = map_tile(palette).map(|c| (*id, c));
+                    break;
+                }
+            }
+            let Some((palette, tile)) = mapped else {
+                Err(Error::TileColorsDontFit { x: x / 8, y: y / 8 })?
+            };
+
+            for px in &tile {
+                used[*px as usize] = true;
+            }
+            let count = bank.tiles.len();
+            let (tile, flip_x, flip_y) = if self.mode == ScreenMode::Affine {
+                (bank.insert_unflipped(tile), false, false)
+            } else {
+                bank.insert(tile)
+            };
+            if bank.tiles.len() > count {
+                changes.new_tiles += 1;
+            }
+            *tile_ref = TileRef {
+                tile,
+                flip_x,
+                flip_y,
+                palette: palette as u8,
+            };
+            changes.changed_refs += 1;
+        }
+
+        if bank.tiles.len() > self.mode.max_tiles() {
+            Err(Error::TooManyTiles {
+                count: bank.tiles.len(),
+                limit: self.mode.max_tiles(),
+            })?
+        }
+
+        self.tiles = refs;
+        ncgr.tiles = NCGRTiles::Horizontal(bank.tiles);
+        nclr.palettes = palettes;
+        Ok(changes)
+    }
+
+    /// Converts an image into a tilemap, along with the tileset and palette it needs, GRIT-style
+    ///
+    /// The palette is built in order of appearance, so the color of the top-left pixel will become
+    /// color 0 (the transparent/backdrop color)
+    pub fn gritify(
+        img: &[ColorBGR555],
+        size: [usize; 2],
+        is_8_bit: bool,
+    ) -> Result<(Self, NCGR, NCLR)> {
+        let mut palette: Vec<ColorBGR555> = vec![];
+        let mut indices = vec![];
+        for color in img {
+            let index = match palette.iter().position(|c| c == color) {
+                Some(c) => c,
+                None => {
+                    palette.push(*color);
+                    palette.len() - 1
+                }
+            };
+            indices.push(index as u8);
+        }
+
+        let max = if is_8_bit { 256 } else { 16 };
+        if palette.len() > max {
+            Err(Error::TooManyColors {
+                max,
+                got: palette.len(),
+            })?
+        }
+
+        Self::gritify_indexed(&indices, &palette, size, is_8_bit)
+    }
+
+    /// Converts RGBA (32-bit) image data into a tilemap, along with the tileset and palette it needs
+    ///
+    /// Color 0 is reserved for transparency, and will be used for every pixel with alpha under 50%.
+    /// Images with too many colors are reduced to fit in the palette.
+    pub fn gritify_rgba(
+        img: &[u8],
+        size: [usize; 2],
+        is_8_bit: bool,
+    ) -> Result<(Self, NCGR, NCLR)> {
+        let options = QuantizeOptions {
+            is_8_bit,
+            ..Default::default()
+        };
+        Self::gritify_quantized(&QuantizedImage::from_rgba(img, size[0], size[1], &options)?)
+    }
+
+    /// Converts RGBA (32-bit) image data into a 4-bit tilemap that uses up to `max_palettes`
+    /// palettes of 16 colors, along with the tileset and palettes it needs
+    ///
+    /// Tiles with similar colors are grouped into the same palette, and every tile references the
+    /// palette that represents it best. Color 0 of every palette is reserved for transparency.
+    pub fn gritify_rgba_multi(
+        img: &[u8],
+        size: [usize; 2],
+        max_palettes: usize,
+    ) -> Result<(Self, NCGR, NCLR)> {
+        let options = QuantizeOptions {
+            max_palettes,
+            ..Default::default()
+        };
+        Self::gritify_quantized(&QuantizedImage::from_rgba(img, size[0], size[1], &options)?)
+    }
+
+    /// Converts an image reduced by [QuantizedImage] into a tilemap, along with the tileset and
+    /// palettes it needs
+    ///
+    /// Tiles are shared between palettes, since the same tile can be drawn with any of them.
+    pub fn gritify_quantized(img: &QuantizedImage) -> Result<(Self, NCGR, NCLR)> {
+        let palette = img.palette.palettes.get(&0).cloned().unwrap_or_default();
+        let (mut nscr, ncgr, _) = Self::gritify_indexed(
+            &img.pixels,
+            &palette,
+            [img.width, img.height],
+            img.palette.is_8_bit,
+        )?;
+        for (tile, palette) in nscr.tiles.iter_mut().zip(&img.tile_palettes) {
+            tile.palette = *palette as u8;
+        }
+        Ok((nscr, ncgr, img.palette.clone()))
+    }
+
+    /// Converts several screens into tilemaps that share a single tileset and set of palettes,
+    /// reducing their colors as described by `options`
+    ///
+    /// Tiles are only stored once no matter which screen they're in (flipped or not), and the
+    /// palettes are built from the colors of every screen. If the tileset ends up with more tiles
+    /// than a tilemap can reference, the error lists every screen that added tiles past the limit.
+    pub fn gritify_shared(
+        screens: &[ScreenImage],
+        options: &QuantizeOptions,
+    ) -> Result<(Vec<Self>, NCGR, NCLR)> {
+        let images: Vec<_> = screens
+            .iter()
+            .map(|c| (c.rgba.as_slice(), [c.width, c.height]))
+            .collect();
+        let quantized = QuantizedImage::from_rgba_shared(&images, options)?;
+
+        let mut bank = TileBank::default();
+        let mut nscrs = vec![];
+        let mut over_limit = vec![];
+        for (screen, img) in screens.iter().zip(&quantized) {
+            let count = bank.tiles.len();
+            let mut nscr = Self::split_tiles(
+                &img.pixels,
+                [img.width, img.height],
+                options.is_8_bit,
+                &mut bank,
+            )?;
+            for (tile, palette) in nscr.tiles.iter_mut().zip(&img.tile_palettes) {
+                tile.palette = *palette as u8;
+            }
+            if bank.tiles.len() > TileBank::MAX_TILES && bank.tiles.len() > count {
+                over_limit.push(screen.name.clone());
+            }
+            nscrs.push(nscr);
+        }
+
+        if !over_limit.is_empty() {
+            Err(Error::TooManySharedTiles {
+                count: bank.tiles.len(),
+                limit: TileBank::MAX_TILES,
+                screens: over_limit,
+            })?
+        }
+
+        let nclr = match quantized.into_iter().next() {
+            Some(c) => c.palette,
+            None => QuantizedImage::from_rgba(&[], 0, 0, options)?.palette,
+        };
+        Ok((
+            nscrs,
+            NCGR {
+                tiles: NCGRTiles::Horizontal(bank.tiles),
+                is_8_bit: options.is_8_bit,
+                has_cpos: false,
+                ncbr_ff: false,
+                size: None,
+                cpos_unknown: 0,
+                mapping_type: 0,
+                tile_mode_flags: 0,
+                version: NDSFile::DEFAULT_VERSION,
+                extra_sections: vec![],
+            },
+            nclr,
+        ))
+    }
+
+    /// Converts indexed image data into a tilemap, along with the tileset and palette it needs
+    ///
+    /// The palette is kept as-is (padded up to 16 or 256 colors), and tiles that are equal to a previous
+    /// tile or to a flipped version of it are only stored once
+    pub fn gritify_indexed(
+        img: &[u8],
+        palette: &[ColorBGR555],
+        size: [usize; 2],
+        is_8_bit: bool,
+    ) -> Result<(Self, NCGR, NCLR)> {
+        let color_amt = if is_8_bit { 256 } else { 16 };
+        if palette.len() > color_amt {
+            Err(Error::TooManyColors {
+                max: color_amt,
+                got: palette.len(),
+            })?
+        }
+
+        // Step 1: divide image into tiles
         // Step 2: find equal and flipped tiles
+        let mut bank = TileBank::default();
+        let nscr = Self::split_tiles(img, size, is_8_bit, &mut bank)?;
+
+        if bank.tiles.len() > TileBank::MAX_TILES {
+            Err(Error::TooManyTiles {
+                count: bank.tiles.len(),
+                limit: TileBank::MAX_TILES,
+            })?
+        }
 
         // Step 3: convert
+        let mut palette = palette.to_vec();
+        palette.resize(color_amt, ColorBGR555::default());
+
+        Ok((
+            nscr,
+            NCGR {
+                tiles: NCGRTiles::Horizontal(bank.tiles),
+                is_8_bit,
+                has_cpos: false,
+                ncbr_ff: false,
+                size: None,
+                cpos_unknown: 0,
+                mapping_type: 0,
+                tile_mode_flags: 0,
+                version: NDSFile::DEFAULT_VERSION,
+                extra_sections: vec![],
+            },
+            NCLR {
+                palettes: BTreeMap::from([(0, palette)]),
+                is_8_bit,
+                color_amt: color_amt as u32,
+                is_extended: false,
+                version: NDSFile::DEFAULT_VERSION,
+                pcmp_unknown: NCLR::PCMP_UNKNOWN,
+                unreferenced_colors: vec![],
+                pltt_padding: vec![],
+                pltt_trailing: vec![],
+                extra_sections: vec![],
+            },
+        ))
+    }
+
+    /// Divides indexed image data into tiles, adding the ones that aren't in the bank yet (flipped
+    /// or not), and returns the tilemap that references them
+    fn split_tiles(
+        img: &[u8],
+        size: [usize; 2],
+        is_8_bit: bool,
+        bank: &mut TileBank,
+    ) -> Result<Self> {
+        let [width, height] = size;
+        if width % 8 != 0 || height % 8 != 0 {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: "width and height must be multiples of 8".to_string(),
+            })?
+        }
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: "image is too big for a tilemap".to_string(),
+            })?
+        }
+        if img.len() != width * height {
+            Err(Error::InvalidImageSize {
+                width,
+                height,
+                reason: format!("expected {} pixels, got {}", width * height, img.len()),
+            })?
+        }
+
+        let color_amt = if is_8_bit { 256 } else { 16 };
+        if let Some(c) = img.iter().find(|c| **c as usize >= color_amt) {
+            Err(Error::TooManyColors {
+                max: color_amt,
+                got: *c as usize + 1,
+            })?
+        }
+
+        let mut tiles = vec![];
+        for ty in 0..height / 8 {
+            for tx in 0..width / 8 {
+                let mut tile = Vec::with_capacity(64);
+                for row in 0..8 {
+                    let start = (ty * 8 + row) * width + tx * 8;
+                    tile.extend(&img[start..start + 8]);
+                }
+                let (tile, flip_x, flip_y) = bank.insert(tile);
+                tiles.push(TileRef {
+                    tile,
+                    flip_x,
+                    flip_y,
+                    palette: 0,
+                });
+            }
+        }
+
+        Ok(Self {
+            width: width as u16,
+            height: height as u16,
+            mode: ScreenMode::Text,
+            is_8_bit,
+            tiles,
+            version: NDSFile::DEFAULT_VERSION,
+            extra_sections: vec![],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Set of unique tiles, used when converting images to tilemaps
+pub(crate) struct TileBank {
+    pub tiles: Vec<Tile>,
+    /// Every tile in the bank (and its flipped versions) along with how to reference it
+    lookup: HashMap<Tile, (u16, bool, bool)>,
+}
+
+impl TileBank {
+    /// Amount of tiles that can be referenced by a [TileRef]
+    pub const MAX_TILES: usize = 0x400;
+
+    /// Adds a tile to the bank if it isn't already there (flipped or not), and returns the index
+    /// and flip flags needed to reference it
+    pub fn insert(&mut self, tile: Tile) -> (u16, bool, bool) {
+        if let Some(c) = self.lookup.get(&tile) {
+            return *c;
+        }
+        self.push(tile)
+    }
+
+    /// Adds a tile to the bank if there isn't an identical one already, for tilemaps that can't
+    /// flip tiles, and returns its index
+    pub fn insert_unflipped(&mut self, tile: Tile) -> u16 {
+        match self.tiles.iter().position(|c| *c == tile) {
+            Some(c) => c as u16,
+            None => self.push(tile).0,
+        }
+    }
+
+    /// Creates a bank with the tiles of an existing tileset, keeping all of them in order (even
+    /// if they're repeated)
+    pub fn from_tiles(tiles: Vec<Tile>) -> Self {
+        let mut bank = Self::default();
+        for tile in tiles {
+            bank.push(tile);
+        }
+        bank
+    }
 
-        todo!();
+    fn push(&mut self, tile: Tile) -> (u16, bool, bool) {
+        let index = self.tiles.len() as u16;
+        for (flip_x, flip_y) in [(false, false), (true, false), (false, true), (true, true)] {
+            self.lookup
+                .entry(flip_tile(&tile, flip_x, flip_y))
+                .or_insert((index, flip_x, flip_y));
+        }
+        self.tiles.push(tile);
+        (index, false, false)
+    }
+}
+
+/// Returns a flipped copy of an 8x8 tile
+pub(crate) fn flip_tile(tile: &[u8], flip_x: bool, flip_y: bool) -> Tile {
+    let mut out = Vec::with_capacity(64);
+    for j in 0..8 {
+        let j = if flip_y { 7 - j } else { j };
+        for i in 0..8 {
+            let i = if flip_x { 7 - i } else { i };
+            out.push(tile[j * 8 + i]);
+        }
     }
+    out
 }
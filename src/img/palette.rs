@@ -0,0 +1,321 @@
+use crate::{
+    error::{Error, Result},
+    img::{ColorBGR555, Rounding, NCLR},
+    ndsfile::NDSFile,
+};
+use bytestream::{ByteOrder, StreamReader, StreamWriter};
+use std::{collections::BTreeMap, io::Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Palette formats used by paint programs
+pub enum PaletteFormat {
+    /// Paint Shop Pro text palette (.pal), also used by Aseprite and GraphicsGale
+    JascPal,
+    /// GIMP text palette (.gpl)
+    Gpl,
+    /// Adobe Color Table (.act), 256 raw RGB colors
+    Act,
+    /// Microsoft RIFF palette (.pal)
+    RiffPal,
+    /// Adobe Color Swatch (.aco)
+    Aco,
+}
+
+impl PaletteFormat {
+    /// Guesses the format from a file extension - `.pal` is assumed to be JASC-PAL, as both JASC
+    /// and RIFF palettes use it. Use [PaletteFormat::detect] when reading files
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        Some(match ext.to_ascii_lowercase().as_str() {
+            "pal" => Self::JascPal,
+            "gpl" => Self::Gpl,
+            "act" => Self::Act,
+            "aco" => Self::Aco,
+            _ => None?,
+        })
+    }
+
+    /// Guesses the format from the contents of a file
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"JASC-PAL") {
+            Some(Self::JascPal)
+        } else if data.starts_with(b"GIMP Palette") {
+            Some(Self::Gpl)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"PAL ") {
+            Some(Self::RiffPal)
+        } else if data.len() == 0x300 || data.len() == 0x304 {
+            Some(Self::Act)
+        } else if data.starts_with(&[0, 1]) || data.starts_with(&[0, 2]) {
+            Some(Self::Aco)
+        } else {
+            None
+        }
+    }
+
+    /// Usual extension for files of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::JascPal | Self::RiffPal => "pal",
+            Self::Gpl => "gpl",
+            Self::Act => "act",
+            Self::Aco => "aco",
+        }
+    }
+}
+
+impl NCLR {
+    /// Reads a palette file made by a paint program, quantizing its colors to BGR555
+    ///
+    /// 8-bit palettes are padded to 256 colors. 4-bit palettes are split into up to 16 palettes
+    /// of 16 colors each, in order, so an image indexed with the whole palette keeps its colors.
+    pub fn import_palette(
+        fname: &str,
+        data: &[u8],
+        format: PaletteFormat,
+        is_8_bit: bool,
+    ) -> Result<Self> {
+        let colors = match format {
+            PaletteFormat::JascPal => read_jasc(data),
+            PaletteFormat::Gpl => read_gpl(data),
+            PaletteFormat::Act => read_act(data),
+            PaletteFormat::RiffPal => read_riff(data),
+            PaletteFormat::Aco => read_aco(data),
+        }
+        .ok_or_else(|| Error::MalformedData {
+            file: fname.to_string(),
+        })?;
+
+        if colors.len() > 0x100 {
+            Err(Error::TooManyColors {
+                max: 0x100,
+                got: colors.len(),
+            })?
+        }
+
+        let colors: Vec<_> = colors
+            .into_iter()
+            .map(|c| ColorBGR555::from_rgb888(c, Rounding::Truncate))
+            .collect();
+        let color_amt = if is_8_bit { 0x100 } else { 0x10 };
+        let mut palettes = BTreeMap::new();
+        for (id, chunk) in colors.chunks(color_amt).enumerate() {
+            let mut palette = chunk.to_vec();
+            palette.resize(color_amt, ColorBGR555::default());
+            palettes.insert(id as u16, palette);
+        }
+        if palettes.is_empty() {
+            palettes.insert(0, vec![ColorBGR555::default(); color_amt]);
+        }
+
+        Ok(Self {
+            palettes,
+            is_8_bit,
+            color_amt: color_amt as u32,
+            is_extended: false,
+            version: NDSFile::DEFAULT_VERSION,
+            pcmp_unknown: NCLR::PCMP_UNKNOWN,
+            unreferenced_colors: vec![],
+            pltt_padding: vec![],
+            pltt_trailing: vec![],
+            extra_sections: vec![],
+        })
+    }
+
+    /// Creates a palette file for paint programs with the colors of every palette
+    ///
+    /// Palettes are laid out by their ID, so palette N starts at color N * `color_amt` and an image
+    /// using the whole palette can reference all of them.
+    pub fn export_palette(&self, format: PaletteFormat) -> Result<Vec<u8>> {
+        let color_amt = self.color_amt as usize;
+        let mut colors = vec![];
+        for (id, palette) in &self.palettes {
+            let start = *id as usize * color_amt;
+            if colors.len() < start {
+                colors.resize(start, [0; 3]);
+            }
+            colors.extend(palette.iter().map(|c| c.to_rgb888()));
+        }
+        if colors.len() > 0x100 {
+            Err(Error::TooManyColors {
+                max: 0x100,
+                got: colors.len(),
+            })?
+        }
+
+        Ok(match format {
+            PaletteFormat::JascPal => write_jasc(&colors),
+            PaletteFormat::Gpl => write_gpl(&colors),
+            PaletteFormat::Act => write_act(&colors)?,
+            PaletteFormat::RiffPal => write_riff(&colors)?,
+            PaletteFormat::Aco => write_aco(&colors)?,
+        })
+    }
+}
+
+/// Parses lines of 3 numbers as colors, ignoring what's after them
+fn parse_rgb_line(line: &str) -> Option<[u8; 3]> {
+    let mut values = line.split_whitespace().map(|c| c.parse::<u8>());
+    Some([
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+    ])
+}
+
+fn read_jasc(data: &[u8]) -> Option<Vec<[u8; 3]>> {
+    let text = std::str::from_utf8(data).ok()?;
+    let mut lines = text.lines();
+    if lines.next()?.trim() != "JASC-PAL" {
+        None?
+    }
+    lines.next()?; // Version, always 0100
+    let count = lines.next()?.trim().parse::<usize>().ok()?;
+    let colors = lines
+        .take(count)
+        .map(parse_rgb_line)
+        .collect::<Option<Vec<_>>>()?;
+    (colors.len() == count).then_some(colors)
+}
+
+fn write_jasc(colors: &[[u8; 3]]) -> Vec<u8> {
+    let mut out = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+    for [r, g, b] in colors {
+        out += &format!("{} {} {}\r\n", r, g, b);
+    }
+    out.into_bytes()
+}
+
+fn read_gpl(data: &[u8]) -> Option<Vec<[u8; 3]>> {
+    let text = std::str::from_utf8(data).ok()?;
+    let mut lines = text.lines();
+    if lines.next()?.trim() != "GIMP Palette" {
+        None?
+    }
+    lines
+        .map(str::trim)
+        .filter(|c| {
+            !c.is_empty()
+                && !c.starts_with('#')
+                && !c.starts_with("Name:")
+                && !c.starts_with("Columns:")
+        })
+        .map(parse_rgb_line)
+        .collect()
+}
+
+fn write_gpl(colors: &[[u8; 3]]) -> Vec<u8> {
+    let mut out = "GIMP Palette\nName: nuclear\nColumns: 16\n#\n".to_string();
+    for (i, [r, g, b]) in colors.iter().enumerate() {
+        out += &format!("{:3} {:3} {:3}\tIndex {}\n", r, g, b, i);
+    }
+    out.into_bytes()
+}
+
+fn read_act(data: &[u8]) -> Option<Vec<[u8; 3]>> {
+    let mut count = 0x100;
+    match data.len() {
+        0x300 => (),
+        // Newer files store the amount of colors and the transparent index at the end
+        0x304 => count = (u16::from_be_bytes([data[0x300], data[0x301]]) as usize).min(0x100),
+        _ => None?,
+    }
+    Some(
+        data[..count * 3]
+            .chunks(3)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect(),
+    )
+}
+
+fn write_act(colors: &[[u8; 3]]) -> Result<Vec<u8>> {
+    let mut out = vec![0u8; 0x300];
+    for (i, color) in colors.iter().enumerate() {
+        out[i * 3..i * 3 + 3].copy_from_slice(color);
+    }
+    (colors.len() as u16).write_to(&mut out, ByteOrder::BigEndian)?;
+    0xFFFFu16.write_to(&mut out, ByteOrder::BigEndian)?; // No transparent color
+    Ok(out)
+}
+
+fn read_riff(data: &[u8]) -> Option<Vec<[u8; 3]>> {
+    let o = ByteOrder::LittleEndian;
+    let mut data = data.get(12..)?;
+    // Look for the data chunk, skipping any other chunks
+    loop {
+        let magic = data.get(..4)?;
+        data = &data[4..];
+        let size = u32::read_from(&mut data, o).ok()? as usize;
+        if magic == b"data" {
+            break;
+        }
+        data = data.get(size + size % 2..)?;
+    }
+    u16::read_from(&mut data, o).ok()?; // Version, 0x0300
+    let count = u16::read_from(&mut data, o).ok()? as usize;
+    Some(
+        data.get(..count * 4)?
+            .chunks(4)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect(),
+    )
+}
+
+fn write_riff(colors: &[[u8; 3]]) -> Result<Vec<u8>> {
+    let o = ByteOrder::LittleEndian;
+    let chunk_size = 4 + colors.len() as u32 * 4;
+    let mut out = vec![];
+    out.write_all(b"RIFF")?;
+    (chunk_size + 12).write_to(&mut out, o)?;
+    out.write_all(b"PAL data")?;
+    chunk_size.write_to(&mut out, o)?;
+    0x0300u16.write_to(&mut out, o)?;
+    (colors.len() as u16).write_to(&mut out, o)?;
+    for [r, g, b] in colors {
+        out.write_all(&[*r, *g, *b, 0])?;
+    }
+    Ok(out)
+}
+
+fn read_aco(data: &[u8]) -> Option<Vec<[u8; 3]>> {
+    let o = ByteOrder::BigEndian;
+    let mut data = data;
+    let version = u16::read_from(&mut data, o).ok()?;
+    let count = u16::read_from(&mut data, o).ok()?;
+    let mut colors = vec![];
+    for _ in 0..count {
+        let space = u16::read_from(&mut data, o).ok()?;
+        let mut values = [0u16; 4];
+        for value in &mut values {
+            *value = u16::read_from(&mut data, o).ok()?;
+        }
+        // Only RGB colors are supported
+        if space != 0 {
+            None?
+        }
+        colors.push([
+            (values[0] >> 8) as u8,
+            (values[1] >> 8) as u8,
+            (values[2] >> 8) as u8,
+        ]);
+        // Version 2 swatches have a UTF-16 name
+        if version == 2 {
+            let len = u32::read_from(&mut data, o).ok()? as usize;
+            data = data.get(len * 2..)?;
+        }
+    }
+    Some(colors)
+}
+
+fn write_aco(colors: &[[u8; 3]]) -> Result<Vec<u8>> {
+    let o = ByteOrder::BigEndian;
+    let mut out = vec![];
+    1u16.write_to(&mut out, o)?;
+    (colors.len() as u16).write_to(&mut out, o)?;
+    for color in colors {
+        0u16.write_to(&mut out, o)?; // RGB
+        for channel in color {
+            (*channel as u16 * 0x101).write_to(&mut out, o)?;
+        }
+        0u16.write_to(&mut out, o)?;
+    }
+    Ok(out)
+}
@@ -0,0 +1,467 @@
+use crate::{
+    error::{Error, Result},
+    img::{ColorBGR555, Rounding, NCLR},
+    ndsfile::NDSFile,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Read,
+};
+
+#[derive(Debug, Clone)]
+/// Settings for reducing a true color image to DS palettes, see [QuantizedImage::from_rgba]
+pub struct QuantizeOptions {
+    /// Whether to make one 256-color palette (true) or 16-color palettes (false)
+    pub is_8_bit: bool,
+    /// Reserves color 0 of every palette for transparency, used by pixels with alpha under 50%.
+    /// If disabled, alpha is ignored and color 0 is an ordinary color
+    pub transparent: bool,
+    /// Colors that every palette must contain, placed right after the transparent color
+    pub fixed_colors: Vec<ColorBGR555>,
+    /// Maximum amount of palettes to make. Only 4-bit images can use more than one (up to 16),
+    /// since each 8x8 tile of a tilemap can pick its own palette
+    pub max_palettes: usize,
+    /// Spreads the error of each pixel into its neighbors (Floyd-Steinberg), which smooths out
+    /// gradients at the cost of some noise
+    pub dither: bool,
+}
+
+impl Default for QuantizeOptions {
+    fn default() -> Self {
+        Self {
+            is_8_bit: false,
+            transparent: true,
+            fixed_colors: vec![],
+            max_palettes: 1,
+            dither: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Image reduced to BGR555 palettes, with its pixels split into 8x8 tiles that use one palette each
+pub struct QuantizedImage {
+    pub width: usize,
+    pub height: usize,
+    /// Index of each pixel in the palette of its tile, row by row
+    pub pixels: Vec<u8>,
+    /// Palette used by each 8x8 tile, row by row - tiles on the right and bottom edges can be
+    /// partial if the image size isn't a multiple of 8
+    pub tile_palettes: Vec<u16>,
+    /// The palettes themselves, with IDs starting at 0
+    pub palette: NCLR,
+}
+
+impl QuantizedImage {
+    /// Maximum amount of times tiles are reassigned to palettes when using more than one
+    const MAX_ITERATIONS: usize = 8;
+
+    /// Reads a PNG of any color type and reduces it like [Self::from_rgba]
+    pub fn from_png<R: Read>(f: R, options: &QuantizeOptions) -> Result<Self> {
+        let (rgba, width, height) = super::read_png_rgba(f)?;
+        Self::from_rgba(&rgba, width, height, options)
+    }
+
+    /// Reduces RGBA (32-bit) image data to the palettes described by `options`
+    ///
+    /// If the colors of a palette fit in it after converting them to BGR555, they're kept exactly,
+    /// in order of appearance. Otherwise they're reduced with median cut. When several palettes
+    /// can be used, tiles with similar colors are grouped into the same palette.
+    pub fn from_rgba(
+        rgba: &[u8],
+        width: usize,
+        height: usize,
+        options: &QuantizeOptions,
+    ) -> Result<Self> {
+        let mut images = Self::from_rgba_shared(&[(rgba, [width, height])], options)?;
+        Ok(images.remove(0))
+    }
+
+    /// Reduces several RGBA (32-bit) images to one set of palettes that all of them share, like
+    /// [Self::from_rgba] does for a single image
+    ///
+    /// Every image gets a copy of the same palettes, with the same IDs.
+    pub fn from_rgba_shared(
+        images: &[(&[u8], [usize; 2])],
+        options: &QuantizeOptions,
+    ) -> Result<Vec<Self>> {
+        for (rgba, [width, height]) in images {
+            if rgba.len() != width * height * 4 {
+                Err(Error::InvalidImageSize {
+                    width: *width,
+                    height: *height,
+                    reason: format!(
+                        "expected {} bytes of RGBA data, got {}",
+                        width * height * 4,
+                        rgba.len()
+                    ),
+                })?
+            }
+        }
+
+        let color_amt = if options.is_8_bit { 0x100 } else { 0x10 };
+        let reserved = options.transparent as usize + options.fixed_colors.len();
+        if reserved > color_amt {
+            Err(Error::TooManyColors {
+                max: color_amt - options.transparent as usize,
+                got: options.fixed_colors.len(),
+            })?
+        }
+        let max_palettes = if options.is_8_bit {
+            1
+        } else {
+            options.max_palettes.clamp(1, 16)
+        };
+
+        // The tiles of every image go one after the other, so they can all be grouped together
+        let mut layouts = vec![];
+        let mut tile_colors = vec![];
+        for (rgba, [width, height]) in images {
+            let layout = Layout {
+                width: *width,
+                height: *height,
+                first_tile: tile_colors.len(),
+                pixels: rgba
+                    .chunks(4)
+                    .map(|c| (!options.transparent || c[3] >= 0x80).then_some([c[0], c[1], c[2]]))
+                    .collect(),
+            };
+            tile_colors.resize(
+                tile_colors.len() + width.div_ceil(8) * height.div_ceil(8),
+                Histogram::default(),
+            );
+            for (i, px) in layout.pixels.iter().enumerate() {
+                if let Some(px) = px {
+                    tile_colors[layout.tile_of(i)].add(*px, 1);
+                }
+            }
+            layouts.push(layout);
+        }
+
+        // Build every palette from the colors of its tiles
+        let build_palettes = |tile_palettes: &[u16]| {
+            let palette_amt = tile_palettes.iter().max().map_or(1, |c| *c as usize + 1);
+            let mut palettes = BTreeMap::new();
+            for id in 0..palette_amt {
+                let mut histogram = Histogram::default();
+                for (tile, colors) in tile_colors.iter().enumerate() {
+                    if tile_palettes[tile] as usize == id {
+                        for (color, count) in &colors.colors {
+                            histogram.add(*color, *count);
+                        }
+                    }
+                }
+                let mut palette = vec![];
+                if options.transparent {
+                    palette.push(ColorBGR555::default());
+                }
+                palette.extend(&options.fixed_colors);
+                let free = color_amt - reserved;
+                let reduced = histogram.reduce(free, &options.fixed_colors);
+                palette.extend(reduced.into_iter().take(free));
+                palettes.insert(id as u16, palette);
+            }
+            palettes
+        };
+
+        let mut tile_palettes = vec![0; tile_colors.len()];
+        let mut palettes = build_palettes(&tile_palettes);
+        if max_palettes > 1 {
+            // Start from tiles grouped by their average color, then alternate between building
+            // the palettes and moving every tile to the palette that fits it best, until no tile
+            // moves anymore
+            tile_palettes = group_tiles(&tile_colors, max_palettes);
+            for _ in 0..Self::MAX_ITERATIONS {
+                palettes = build_palettes(&tile_palettes);
+                let best = tile_colors
+                    .iter()
+                    .map(|c| {
+                        palettes
+                            .iter()
+                            .min_by_key(|(_, palette)| c.error(palette, options.transparent))
+                            .map_or(0, |c| *c.0)
+                    })
+                    .collect::<Vec<_>>();
+                if best == tile_palettes {
+                    break;
+                }
+                tile_palettes = best;
+            }
+
+            // Leave no gaps between the palettes that ended up being used
+            let ids: Vec<u16> = tile_palettes
+                .iter()
+                .copied()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            palettes = ids
+                .iter()
+                .enumerate()
+                .map(|(new, old)| (new as u16, palettes.remove(old).unwrap_or_default()))
+                .collect();
+            for id in &mut tile_palettes {
+                *id = ids.binary_search(id).unwrap_or_default() as u16;
+            }
+        }
+
+        // Map every pixel to the closest color of its palette
+        let first = options.transparent as usize;
+        let mut cache = HashMap::new();
+        let mut closest = |color: [u8; 3], id: u16| -> u8 {
+            *cache.entry((color, id)).or_insert_with(|| {
+                let palette = &palettes[&id];
+                (first..palette.len().max(first + 1))
+                    .min_by_key(|c| {
+                        distance(
+                            color,
+                            palette.get(*c).copied().unwrap_or_default().to_rgb888(),
+                        )
+                    })
+                    .unwrap_or(first) as u8
+            })
+        };
+        let mut all_indices = vec![];
+        for layout in &layouts {
+            let width = layout.width;
+            let mut indices = vec![0; width * layout.height];
+            // Error carried over to the current and next rows, when dithering
+            let mut errors = vec![[0i32; 3]; (width + 2) * 2];
+            for y in 0..layout.height {
+                let (current, next) = errors.split_at_mut(width + 2);
+                for x in 0..width {
+                    let i = y * width + x;
+                    let Some(px) = layout.pixels[i] else {
+                        continue;
+                    };
+                    let id = tile_palettes[layout.tile_of(i)];
+                    if !options.dither {
+                        indices[i] = closest(px, id);
+                        continue;
+                    }
+
+                    let err = current[x + 1];
+                    let target =
+                        [0, 1, 2].map(|c| (px[c] as i32 + err[c] / 16).clamp(0, 0xFF) as u8);
+                    let index = closest(target, id);
+                    indices[i] = index;
+                    let got = palettes[&id][index as usize].to_rgb888();
+                    for c in 0..3 {
+                        let diff = target[c] as i32 - got[c] as i32;
+                        current[x + 2][c] += diff * 7;
+                        next[x][c] += diff * 3;
+                        next[x + 1][c] += diff * 5;
+                        next[x + 2][c] += diff;
+                    }
+                }
+                errors.copy_within(width + 2.., 0);
+                errors[width + 2..].fill([0; 3]);
+            }
+            all_indices.push(indices);
+        }
+
+        for palette in palettes.values_mut() {
+            palette.resize(color_amt, ColorBGR555::default());
+        }
+        let palette = NCLR {
+            palettes,
+            is_8_bit: options.is_8_bit,
+            color_amt: color_amt as u32,
+            is_extended: false,
+            version: NDSFile::DEFAULT_VERSION,
+            pcmp_unknown: NCLR::PCMP_UNKNOWN,
+            unreferenced_colors: vec![],
+            pltt_padding: vec![],
+            pltt_trailing: vec![],
+            extra_sections: vec![],
+        };
+        Ok(layouts
+            .iter()
+            .zip(all_indices)
+            .map(|(layout, pixels)| {
+                let tile_amt = layout.width.div_ceil(8) * layout.height.div_ceil(8);
+                Self {
+                    width: layout.width,
+                    height: layout.height,
+                    pixels,
+                    tile_palettes: tile_palettes[layout.first_tile..layout.first_tile + tile_amt]
+                        .to_vec(),
+                    palette: palette.clone(),
+                }
+            })
+            .collect())
+    }
+}
+
+/// Where the pixels of an image being quantized are, and which tiles they belong to
+struct Layout {
+    width: usize,
+    height: usize,
+    /// Position of the image's first tile among the tiles of every image
+    first_tile: usize,
+    /// Pixels of the image, or None if they're transparent
+    pixels: Vec<Option<[u8; 3]>>,
+}
+
+impl Layout {
+    /// Returns the tile a pixel belongs to
+    fn tile_of(&self, i: usize) -> usize {
+        let (x, y) = (i % self.width, i / self.width);
+        self.first_tile + (y / 8) * self.width.div_ceil(8) + x / 8
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Amount of times each color appears, in order of appearance
+struct Histogram {
+    colors: Vec<([u8; 3], u32)>,
+    lookup: HashMap<[u8; 3], usize>,
+}
+
+impl Histogram {
+    fn add(&mut self, color: [u8; 3], amount: u32) {
+        match self.lookup.get(&color) {
+            Some(c) => self.colors[*c].1 += amount,
+            None => {
+                self.lookup.insert(color, self.colors.len());
+                self.colors.push((color, amount));
+            }
+        }
+    }
+
+    /// How badly the colors are represented by a palette, as the sum of the squared distances
+    /// from each color to its closest one in the palette
+    fn error(&self, palette: &[ColorBGR555], transparent: bool) -> u64 {
+        let palette: Vec<_> = palette[transparent as usize..]
+            .iter()
+            .map(|c| c.to_rgb888())
+            .collect();
+        self.colors
+            .iter()
+            .map(|(color, count)| {
+                let closest = palette.iter().map(|c| distance(*color, *c)).min();
+                closest.unwrap_or(u32::MAX) as u64 * *count as u64
+            })
+            .sum()
+    }
+
+    /// Picks up to `amount` BGR555 colors that represent the histogram, leaving out the ones
+    /// that are already in `fixed`
+    fn reduce(&self, amount: usize, fixed: &[ColorBGR555]) -> Vec<ColorBGR555> {
+        if amount == 0 {
+            return vec![];
+        }
+        let mut exact = Histogram::default();
+        for (color, count) in &self.colors {
+            let color = ColorBGR555::from_rgb888(*color, Rounding::Nearest);
+            if !fixed.contains(&color) {
+                exact.add(color.to_rgb888(), *count);
+            }
+        }
+        if exact.colors.len() <= amount {
+            return exact
+                .colors
+                .iter()
+                .map(|(c, _)| ColorBGR555::from_rgb888(*c, Rounding::Nearest))
+                .collect();
+        }
+
+        let mut colors: Vec<ColorBGR555> = vec![];
+        for group in median_cut(&exact.colors, amount) {
+            let total: u64 = group.iter().map(|c| exact.colors[*c].1 as u64).sum();
+            let average = [0, 1, 2].map(|ch| {
+                let sum: u64 = group
+                    .iter()
+                    .map(|c| exact.colors[*c].0[ch] as u64 * exact.colors[*c].1 as u64)
+                    .sum();
+                ((sum + total / 2) / total) as u8
+            });
+            let color = ColorBGR555::from_rgb888(average, Rounding::Nearest);
+            if !colors.contains(&color) && !fixed.contains(&color) {
+                colors.push(color);
+            }
+        }
+        colors
+    }
+}
+
+/// Splits weighted points into up to `amount` groups of similar points, returning the indices of
+/// the points in each group
+///
+/// The group with the widest range in a single channel is split at its weighted median, until
+/// there are enough groups or none of them can be split anymore.
+fn median_cut(points: &[([u8; 3], u32)], amount: usize) -> Vec<Vec<usize>> {
+    if amount == 0 {
+        return vec![];
+    }
+    let mut groups = vec![(0..points.len()).collect::<Vec<_>>()];
+    groups.retain(|c| !c.is_empty());
+    while groups.len() < amount {
+        let widest = groups
+            .iter()
+            .enumerate()
+            .filter_map(|(i, group)| {
+                (0..3)
+                    .map(|ch| {
+                        let values = group.iter().map(|c| points[*c].0[ch]);
+                        let range = values.clone().max()? - values.min()?;
+                        Some((i, ch, range))
+                    })
+                    .max_by_key(|c| c.map(|c| c.2))?
+            })
+            .filter(|c| c.2 > 0)
+            .max_by_key(|c| c.2);
+        let Some((i, ch, _)) = widest else {
+            break;
+        };
+
+        let mut group = groups.remove(i);
+        group.sort_by_key(|c| points[*c].0[ch]);
+        let total: u64 = group.iter().map(|c| points[*c].1 as u64).sum();
+        let mut acc = 0;
+        let mut split = group.len();
+        for (j, c) in group.iter().enumerate() {
+            acc += points[*c].1 as u64;
+            if acc * 2 >= total {
+                split = j + 1;
+                break;
+            }
+        }
+        let other = group.split_off(split.clamp(1, group.len() - 1));
+        groups.insert(i, other);
+        groups.insert(i, group);
+    }
+    groups
+}
+
+/// Assigns a palette to each tile, grouping tiles by their average color
+fn group_tiles(tile_colors: &[Histogram], max_palettes: usize) -> Vec<u16> {
+    // Fully transparent tiles can use any palette, so they're left on palette 0
+    let tiles: Vec<usize> = (0..tile_colors.len())
+        .filter(|c| !tile_colors[*c].colors.is_empty())
+        .collect();
+    let points: Vec<_> = tiles
+        .iter()
+        .map(|c| {
+            let colors = &tile_colors[*c].colors;
+            let count: u64 = colors.iter().map(|c| c.1 as u64).sum();
+            let average = [0, 1, 2].map(|ch| {
+                let sum: u64 = colors.iter().map(|c| c.0[ch] as u64 * c.1 as u64).sum();
+                (sum / count) as u8
+            });
+            (average, count as u32)
+        })
+        .collect();
+    let mut out = vec![0; tile_colors.len()];
+    for (id, group) in median_cut(&points, max_palettes).iter().enumerate() {
+        for c in group {
+            out[tiles[*c]] = id as u16;
+        }
+    }
+    out
+}
+
+/// Squared distance between two colors
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    (0..3).map(|c| (a[c].abs_diff(b[c]) as u32).pow(2)).sum()
+}
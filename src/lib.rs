@@ -1,5 +1,9 @@
+pub mod compress;
 pub mod error;
 pub mod extend;
+pub mod fnt;
 pub mod img;
+pub mod narc;
+pub mod nds_rom;
 pub mod ndsfile;
 pub mod proj;
@@ -0,0 +1,167 @@
+use crate::{
+    error::{Error, Result},
+    fnt::Directory,
+    ndsfile::{ExtraSection, NDSFile, NDSFileType, ParseMode, Section, SectionReader},
+};
+use bytestream::{ByteOrder, StreamWriter};
+
+#[derive(Debug, Clone)]
+/// NARC (Nitro ARChive) format, used to pack many files into one
+pub struct NARC {
+    /// Contents of each file, by ID
+    pub files: Vec<Vec<u8>>,
+    /// Names of the files, if the archive has them
+    pub names: Option<Directory>,
+    /// Format version from the file's header
+    pub version: u16,
+    /// Sections the format doesn't define, only read with [ParseMode::Lenient]
+    pub extra_sections: Vec<ExtraSection>,
+}
+
+impl NDSFileType for NARC {
+    /// Creates a NARC struct from the NDSFile given
+    fn from_ndsfile_with(file: &NDSFile, mode: ParseMode) -> Result<Self> {
+        if file.magic != "NARC" {
+            Err(Error::WrongFileKind {
+                file: file.fname.to_string(),
+                ftype: Some("NARC/NDS archive".to_string()),
+                expected: "NARC".to_string(),
+                got: file.magic.to_string(),
+            })?
+        }
+
+        let malformed = || Error::MalformedData {
+            file: file.fname.clone(),
+        };
+        let o = file.byteorder;
+        let mut fat = None;
+        let mut names = None;
+        let mut image = None;
+
+        let mut extra_sections = vec![];
+
+        for (i, section) in file.sections.iter().enumerate() {
+            let data: &[u8] = &section.contents;
+            match section.magic.as_ref() {
+                "BTAF" => {
+                    let mut r = SectionReader::new(file, section);
+                    let count = r.read::<u16>("file count")?;
+                    r.read::<u16>("reserved")?;
+                    let mut entries = vec![];
+                    for _ in 0..count {
+                        let start = r.read::<u32>("file start")? as usize;
+                        let end = r.read::<u32>("file end")? as usize;
+                        entries.push((start, end));
+                    }
+                    fat = Some(entries);
+                }
+                "BTNF" => {
+                    let root = Directory::from_fnt(data, &file.fname, o)?;
+                    // Archives without names only have an empty root directory
+                    if !root.entries.is_empty() {
+                        names = Some(root);
+                    }
+                }
+                "GMIF" => image = Some(data),
+                _ => mode.unknown_section(file, i, &mut extra_sections)?,
+            }
+        }
+
+        let Some(fat) = fat else {
+            Err(Error::MissingRequiredSection {
+                file: file.fname.clone(),
+                s_name: "FATB".to_string(),
+            })?
+        };
+        let Some(image) = image else {
+            Err(Error::MissingRequiredSection {
+                file: file.fname.clone(),
+                s_name: "FIMG".to_string(),
+            })?
+        };
+
+        let mut files = vec![];
+        for (start, end) in fat {
+            if start > end {
+                Err(malformed())?
+            }
+            files.push(image.get(start..end).ok_or_else(malformed)?.to_vec());
+        }
+
+        Ok(Self {
+            files,
+            names,
+            version: file.version,
+            extra_sections,
+        })
+    }
+
+    /// Creates an NDSFile from the NARC struct given
+    fn to_ndsfile(&self, fname: String, o: ByteOrder) -> Result<NDSFile> {
+        let mut fat = vec![];
+        let mut image = vec![];
+        (self.files.len() as u16).write_to(&mut fat, o)?;
+        0u16.write_to(&mut fat, o)?;
+        for file in &self.files {
+            (image.len() as u32).write_to(&mut fat, o)?;
+            image.extend(file);
+            (image.len() as u32).write_to(&mut fat, o)?;
+            pad_to_4(&mut image);
+        }
+
+        let mut fnt = match &self.names {
+            Some(c) => c.to_fnt(o)?,
+            None => Directory::root(0).to_fnt(o)?,
+        };
+        pad_to_4(&mut fnt);
+
+        let mut sections = vec![
+            Section {
+                magic: "BTAF".to_string(),
+                contents: fat,
+            },
+            Section {
+                magic: "BTNF".to_string(),
+                contents: fnt,
+            },
+            Section {
+                magic: "GMIF".to_string(),
+                contents: image,
+            },
+        ];
+        ExtraSection::insert_into(&self.extra_sections, &mut sections);
+
+        Ok(NDSFile {
+            byteorder: o,
+            version: self.version,
+            magic: "NARC".to_string(),
+            fname,
+            sections,
+        })
+    }
+}
+
+impl NARC {
+    /// Gets the contents of the file at the given path
+    pub fn get(&self, path: &str) -> Option<&Vec<u8>> {
+        self.files.get(self.names.as_ref()?.find(path)? as usize)
+    }
+
+    /// Gets the path of each file, or `None` for files without a name
+    pub fn paths(&self) -> Vec<Option<String>> {
+        let mut out = vec![None; self.files.len()];
+        if let Some(names) = &self.names {
+            for (path, id) in names.files() {
+                if let Some(c) = out.get_mut(id as usize) {
+                    *c = Some(path);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Archive data is padded with 0xFF
+fn pad_to_4(data: &mut Vec<u8>) {
+    data.resize(data.len() + (4 - data.len() % 4) % 4, 0xFF);
+}
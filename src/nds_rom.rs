@@ -0,0 +1,441 @@
+use crate::{
+    compress::{self, Compression},
+    error::{Error, Result},
+    fnt::Directory,
+    ndsfile::{NDSFileIndex, NDSFileType, Section, SectionEntry},
+};
+use bytestream::{ByteOrder, StreamReader, StreamWriter};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Take, Write},
+    path::Path,
+};
+
+/// Size of the part of the cartridge header that's actually used
+pub const HEADER_SIZE: usize = 0x200;
+
+/// Files in the ROM's data area start at multiples of this
+pub const FILE_ALIGNMENT: u32 = 0x200;
+
+#[derive(Debug, Clone)]
+/// Cartridge header of a .nds ROM, only the fields relevant to the file system are parsed
+pub struct RomHeader {
+    pub title: String,
+    pub game_code: String,
+    pub maker_code: String,
+    /// Cartridge size, as 128KB << capacity
+    pub capacity: u8,
+    pub arm9_offset: u32,
+    pub arm9_size: u32,
+    pub arm7_offset: u32,
+    pub arm7_size: u32,
+    pub fnt_offset: u32,
+    pub fnt_size: u32,
+    pub fat_offset: u32,
+    pub fat_size: u32,
+    pub arm9_overlay_offset: u32,
+    pub arm9_overlay_size: u32,
+    pub arm7_overlay_offset: u32,
+    pub arm7_overlay_size: u32,
+    pub banner_offset: u32,
+    /// Amount of bytes of the ROM that are used
+    pub rom_size: u32,
+    /// The whole header, kept so it can be written back unchanged
+    pub raw: Vec<u8>,
+}
+
+impl RomHeader {
+    /// Parses the first [HEADER_SIZE] bytes of a ROM
+    pub fn from_bytes(raw: &[u8]) -> Result<Self> {
+        if raw.len() < HEADER_SIZE {
+            Err(Error::MalformedData {
+                file: "ROM header".to_string(),
+            })?
+        }
+        let string = |c: &[u8]| {
+            String::from_utf8_lossy(c)
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        let word = |offset: usize| u32::read_from(&mut &raw[offset..], ByteOrder::LittleEndian);
+
+        Ok(Self {
+            title: string(&raw[..0x0C]),
+            game_code: string(&raw[0x0C..0x10]),
+            maker_code: string(&raw[0x10..0x12]),
+            capacity: raw[0x14],
+            arm9_offset: word(0x20)?,
+            arm9_size: word(0x2C)?,
+            arm7_offset: word(0x30)?,
+            arm7_size: word(0x3C)?,
+            fnt_offset: word(0x40)?,
+            fnt_size: word(0x44)?,
+            fat_offset: word(0x48)?,
+            fat_size: word(0x4C)?,
+            arm9_overlay_offset: word(0x50)?,
+            arm9_overlay_size: word(0x54)?,
+            arm7_overlay_offset: word(0x58)?,
+            arm7_overlay_size: word(0x5C)?,
+            banner_offset: word(0x68)?,
+            rom_size: word(0x80)?,
+            raw: raw[..HEADER_SIZE].to_vec(),
+        })
+    }
+
+    /// Sets the used ROM size, growing the cartridge capacity to fit it, and updates the header CRC
+    ///
+    /// The header is left alone if the size doesn't change, so ROMs with a wrong CRC stay as they
+    /// were
+    fn set_rom_size(&mut self, rom_size: u32) {
+        if rom_size == self.rom_size && (0x20000u64 << self.capacity) >= rom_size as u64 {
+            return;
+        }
+        self.rom_size = rom_size;
+        while (0x20000u64 << self.capacity) < rom_size as u64 {
+            self.capacity += 1;
+        }
+        self.raw[0x80..0x84].copy_from_slice(&rom_size.to_le_bytes());
+        self.raw[0x14] = self.capacity;
+
+        let crc = crc16(&self.raw[..0x15E]);
+        self.raw[0x15E..0x160].copy_from_slice(&crc.to_le_bytes());
+    }
+}
+
+/// CRC16 as used by the cartridge header (CRC-16/MODBUS)
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// A .nds ROM image, whose files are read on demand from the underlying reader
+pub struct NDSRom<R: Read + Seek> {
+    reader: R,
+    pub header: RomHeader,
+    /// Root of the ROM's file system
+    pub names: Directory,
+    /// Start and end offset of every file in the ROM, by ID
+    pub fat: Vec<(u32, u32)>,
+    /// Files that will be replaced when writing the ROM, by ID
+    replaced: HashMap<u16, Vec<u8>>,
+}
+
+impl NDSRom<File> {
+    /// Opens the ROM at the given path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> NDSRom<R> {
+    /// Reads the header, FNT and FAT of a ROM - file contents are only read when requested
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut raw = vec![0u8; HEADER_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut raw)?;
+        let header = RomHeader::from_bytes(&raw)?;
+
+        let fnt = Self::read_region(&mut reader, header.fnt_offset, header.fnt_size)?;
+        let names = Directory::from_fnt(&fnt, "ROM file name table", ByteOrder::LittleEndian)?;
+
+        let fat_data = Self::read_region(&mut reader, header.fat_offset, header.fat_size)?;
+        let mut data = fat_data.as_slice();
+        let mut fat = vec![];
+        for _ in 0..fat_data.len() / 8 {
+            let start = u32::read_from(&mut data, ByteOrder::LittleEndian)?;
+            let end = u32::read_from(&mut data, ByteOrder::LittleEndian)?;
+            if start > end {
+                Err(Error::MalformedData {
+                    file: "ROM file allocation table".to_string(),
+                })?
+            }
+            fat.push((start, end));
+        }
+
+        Ok(Self {
+            reader,
+            header,
+            names,
+            fat,
+            replaced: HashMap::new(),
+        })
+    }
+
+    fn read_region(reader: &mut R, offset: u32, size: u32) -> Result<Vec<u8>> {
+        let mut out = vec![];
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        reader.take(size as u64).read_to_end(&mut out)?;
+        if out.len() != size as usize {
+            Err(Error::MalformedData {
+                file: "ROM".to_string(),
+            })?
+        }
+        Ok(out)
+    }
+
+    /// Gets the ID of the file at the given path
+    pub fn find(&self, path: &str) -> Result<u16> {
+        self.names.find(path).ok_or_else(|| Error::FileNotFound {
+            file: self.header.title.clone(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Lists every named file in the ROM as (path, ID)
+    pub fn files(&self) -> Vec<(String, u16)> {
+        self.names.files()
+    }
+
+    /// Gets a reader over the contents of the file with the given ID
+    pub fn file_reader(&mut self, id: u16) -> Result<Take<&mut R>> {
+        let Some(&(start, end)) = self.fat.get(id as usize) else {
+            Err(Error::FileNotFound {
+                file: self.header.title.clone(),
+                path: format!("#{}", id),
+            })?
+        };
+        self.reader.seek(SeekFrom::Start(start as u64))?;
+        Ok((&mut self.reader).take((end - start) as u64))
+    }
+
+    /// Gets a reader over the contents of the file at the given path
+    pub fn open_file(&mut self, path: &str) -> Result<Take<&mut R>> {
+        let id = self.find(path)?;
+        self.file_reader(id)
+    }
+
+    /// Indexes the sections of the file at the given path without reading their contents, so many
+    /// files can be scanned quickly - see [NDSFileIndex]
+    ///
+    /// Like [Self::file_reader], this reads the file as stored in the ROM, so compressed files
+    /// can't be indexed
+    pub fn index_file(&mut self, path: &str) -> Result<NDSFileIndex> {
+        let id = self.find(path)?;
+        let size = self.file_reader(id)?.limit();
+        NDSFileIndex::from_reader_sized(path, &mut self.reader, size)
+    }
+
+    /// Reads one section of a file indexed with [Self::index_file]
+    pub fn read_section(&mut self, index: &NDSFileIndex, entry: &SectionEntry) -> Result<Section> {
+        index.read_section(&mut self.reader, entry)
+    }
+
+    /// Reads the whole contents of the file with the given ID, including any pending replacement
+    pub fn read_file(&mut self, id: u16) -> Result<Vec<u8>> {
+        if let Some(c) = self.replaced.get(&id) {
+            return Ok(c.clone());
+        }
+        let mut out = vec![];
+        self.file_reader(id)?.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    /// Replaces the contents of the file with the given ID the next time the ROM is written
+    pub fn replace_file(&mut self, id: u16, data: Vec<u8>) -> Result<()> {
+        if id as usize >= self.fat.len() {
+            Err(Error::FileNotFound {
+                file: self.header.title.clone(),
+                path: format!("#{}", id),
+            })?
+        }
+        self.replaced.insert(id, data);
+        Ok(())
+    }
+
+    /// Replaces the file at the given path with a Nintendo file, compressing it the same way
+    /// the original file was
+    pub fn replace<T: NDSFileType>(
+        &mut self,
+        path: &str,
+        file: &T,
+        order: ByteOrder,
+    ) -> Result<()> {
+        let id = self.find(path)?;
+        let mut header = [0u8; 4];
+        let read = self.file_reader(id)?.read(&mut header)?;
+        let compression = Compression::detect(&header[..read]);
+
+        let mut data = io::Cursor::new(vec![]);
+        file.to_file(&mut data, path.to_string(), order)?;
+        self.replace_file(id, compress::compress(data.get_ref(), compression)?)
+    }
+
+    /// Writes the ROM with all replaced files to `f`
+    ///
+    /// Files stored after the last system region (header, ARM binaries, FNT, FAT, overlay tables
+    /// and banner) are laid out again, so the FAT, the used ROM size, the cartridge capacity and
+    /// the header CRC get updated. Files stored among the system regions, like the overlays in
+    /// ndstool layouts, stay where they are, and everything before the first moved file is
+    /// copied as-is. Whatever comes after the last file, like the RSA signature or the padding up
+    /// to the cartridge capacity, is copied after the moved files, so an unchanged ROM is written
+    /// back byte-for-byte.
+    pub fn write_to<W: Write>(&mut self, f: &mut W) -> Result<()> {
+        let title = self.header.title.clone();
+        let unsupported =
+            |reason: &str| Error::Generic(format!("Can't rebuild ROM {}: {}", title, reason));
+
+        let system_end = self
+            .system_regions()?
+            .into_iter()
+            .filter(|c| c.1 != 0)
+            .map(|(offset, size)| offset as u64 + size as u64)
+            .max()
+            .unwrap_or(HEADER_SIZE as u64);
+
+        // Unused FAT entries are (0, 0) and don't take any space
+        let (fixed, mut order): (Vec<usize>, Vec<usize>) = (0..self.fat.len())
+            .filter(|c| self.fat[*c].1 > self.fat[*c].0)
+            .partition(|c| (self.fat[*c].0 as u64) < system_end);
+        order.sort_by_key(|c| self.fat[*c].0);
+        let data_start = match order.first() {
+            Some(c) => self.fat[*c].0,
+            None => fixed
+                .iter()
+                .map(|c| self.fat[*c].1 as u64)
+                .chain([system_end, self.header.rom_size as u64])
+                .max()
+                .unwrap_or(system_end) as u32,
+        };
+
+        if (self.header.fat_size as usize) < self.fat.len() * 8 {
+            Err(unsupported("the FAT is smaller than the amount of files"))?
+        }
+
+        let mut fat = self.fat.clone();
+        for &id in &fixed {
+            let (start, end) = self.fat[id];
+            if end > data_start {
+                Err(unsupported(&format!(
+                    "file #{} is stored among the system data but ends after it",
+                    id
+                )))?
+            }
+            if let Some(c) = self.replaced.get(&(id as u16)) {
+                if c.len() as u32 > end - start {
+                    Err(unsupported(&format!(
+                        "file #{} is stored among the system data and its replacement doesn't fit",
+                        id
+                    )))?
+                }
+                fat[id] = (start, start + c.len() as u32);
+            }
+        }
+
+        // End of the file data in the original ROM, anything after it is kept as-is
+        let data_end = order
+            .iter()
+            .map(|c| self.fat[*c].1)
+            .chain([data_start])
+            .max()
+            .unwrap_or(data_start);
+
+        let mut pos = data_start;
+        for &id in &order {
+            let size = match self.replaced.get(&(id as u16)) {
+                Some(c) => c.len() as u32,
+                None => self.fat[id].1 - self.fat[id].0,
+            };
+            pos = pos.next_multiple_of(FILE_ALIGNMENT);
+            fat[id] = (pos, pos + size);
+            pos = pos
+                .checked_add(size)
+                .ok_or_else(|| unsupported("it's over 4GB"))?;
+        }
+
+        // If the used ROM size goes past the last file, it keeps covering whatever is there
+        let rom_size = match self.header.rom_size.checked_sub(data_end) {
+            Some(c) => pos
+                .checked_add(c)
+                .ok_or_else(|| unsupported("it's over 4GB"))?,
+            None => pos,
+        };
+        let mut header = self.header.clone();
+        header.set_rom_size(rom_size);
+
+        let mut system = Self::read_region(&mut self.reader, 0, data_start)?;
+        system[..HEADER_SIZE].copy_from_slice(&header.raw);
+        for &id in &fixed {
+            if let Some(c) = self.replaced.get(&(id as u16)) {
+                let (start, end) = (self.fat[id].0 as usize, self.fat[id].1 as usize);
+                system[start..start + c.len()].copy_from_slice(c);
+                system[start + c.len()..end].fill(0xFF);
+            }
+        }
+        let mut fat_data = vec![];
+        for (start, end) in &fat {
+            start.write_to(&mut fat_data, ByteOrder::LittleEndian)?;
+            end.write_to(&mut fat_data, ByteOrder::LittleEndian)?;
+        }
+        let fat_offset = header.fat_offset as usize;
+        system[fat_offset..fat_offset + fat_data.len()].copy_from_slice(&fat_data);
+        f.write_all(&system)?;
+
+        let mut pos = data_start;
+        for &id in &order {
+            f.write_all(&vec![0xFF; (fat[id].0 - pos) as usize])?;
+            match self.replaced.get(&(id as u16)) {
+                Some(c) => f.write_all(c)?,
+                None => {
+                    let (start, end) = self.fat[id];
+                    self.reader.seek(SeekFrom::Start(start as u64))?;
+                    let copied = io::copy(&mut (&mut self.reader).take((end - start) as u64), f)?;
+                    if copied != (end - start) as u64 {
+                        Err(Error::MalformedData {
+                            file: "ROM".to_string(),
+                        })?
+                    }
+                }
+            }
+            pos = fat[id].1;
+        }
+
+        self.reader.seek(SeekFrom::Start(data_end as u64))?;
+        io::copy(&mut self.reader, f)?;
+
+        Ok(())
+    }
+
+    /// Offset and size of the parts of the ROM that aren't in the file system
+    fn system_regions(&mut self) -> Result<Vec<(u32, u32)>> {
+        let h = &self.header;
+        let mut regions = vec![
+            (0, HEADER_SIZE as u32),
+            (h.arm9_offset, h.arm9_size),
+            (h.arm7_offset, h.arm7_size),
+            (h.fnt_offset, h.fnt_size),
+            (h.fat_offset, h.fat_size),
+            (h.arm9_overlay_offset, h.arm9_overlay_size),
+            (h.arm7_overlay_offset, h.arm7_overlay_size),
+        ];
+
+        // The banner's size depends on its version
+        if h.banner_offset != 0 {
+            let offset = h.banner_offset;
+            self.reader.seek(SeekFrom::Start(offset as u64))?;
+            let size = match u16::read_from(&mut self.reader, ByteOrder::LittleEndian)? {
+                2 => 0x940,
+                3 => 0xA40,
+                0x103 => 0x23C0,
+                _ => 0x840,
+            };
+            regions.push((offset, size));
+        }
+        Ok(regions)
+    }
+
+    /// Gives back the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
@@ -1,8 +1,12 @@
-use crate::error::{Error, Result};
+use crate::{
+    compress,
+    error::{Error, Result},
+    nds_rom::NDSRom,
+};
 use bytestream::{ByteOrder, StreamReader, StreamWriter};
 use std::{
     fmt::{self, Debug, Formatter},
-    io::{Read, Seek, SeekFrom, Write},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
 };
 
 #[derive(Clone)]
@@ -10,6 +14,8 @@ pub struct NDSFile {
     pub fname: String,
     pub magic: String,
     pub byteorder: ByteOrder,
+    /// Format version from the header, usually 0x0100
+    pub version: u16,
     pub sections: Vec<Section>,
 }
 
@@ -19,57 +25,260 @@ pub struct Section {
     pub contents: Vec<u8>,
 }
 
-impl NDSFile {
-    pub fn from_file<F: Read>(fname: &str, f: &mut F) -> Result<Self> {
-        let mut magic = [0u8; 4];
-        f.read_exact(&mut magic)?;
+#[derive(Debug, Clone)]
+/// Section that a format doesn't define, kept as-is when parsing with [ParseMode::Lenient]
+pub struct ExtraSection {
+    /// Position of the section in the file it was read from
+    pub index: usize,
+    pub section: Section,
+}
 
-        let mut bom = [0u8; 2];
-        f.read_exact(&mut bom)?;
-        let o = match bom {
-            [0xFF, 0xFE] => ByteOrder::LittleEndian,
-            [0xFE, 0xFF] => ByteOrder::BigEndian,
-            _ => Err(Error::InvalidBOM {
-                file: fname.to_string(),
-            })?,
-        };
+impl ExtraSection {
+    /// Puts extra sections back among the known ones, at the position they were read from
+    pub(crate) fn insert_into(extra: &[Self], sections: &mut Vec<Section>) {
+        let mut extra = extra.to_vec();
+        extra.sort_by_key(|c| c.index);
+        for c in extra {
+            sections.insert(c.index.min(sections.len()), c.section);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How parsing deals with sections that a format doesn't define
+pub enum ParseMode {
+    /// Unknown sections are an error
+    #[default]
+    Strict,
+    /// Unknown sections are kept as-is, and written back along with the rest of the file
+    Lenient,
+}
 
-        u16::read_from(f, o)?; // 0x0001
-        u32::read_from(f, o)?; // Full filesize, we can discard it here
-        u16::read_from(f, o)?; // Header size, always 0x10
+impl ParseMode {
+    /// Handles the section at `index` of the file, which the format doesn't define
+    pub(crate) fn unknown_section(
+        self,
+        file: &NDSFile,
+        index: usize,
+        extra: &mut Vec<ExtraSection>,
+    ) -> Result<()> {
+        let section = &file.sections[index];
+        match self {
+            Self::Strict => Err(Error::UnknownSection {
+                file: file.fname.clone(),
+                s_name: section.magic.clone(),
+            }),
+            Self::Lenient => {
+                extra.push(ExtraSection {
+                    index,
+                    section: section.clone(),
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the fields of a section one after another, so errors can say which field of the file
+/// was wrong and where it is instead of just failing
+pub(crate) struct SectionReader<'a> {
+    file: &'a str,
+    section: &'a Section,
+    o: ByteOrder,
+    pos: usize,
+    /// Offset of the last field read
+    last: usize,
+}
 
-        let section_count = u16::read_from(f, o)?;
-        let mut sections = vec![];
+impl<'a> SectionReader<'a> {
+    pub fn new(file: &'a NDSFile, section: &'a Section) -> Self {
+        Self {
+            file: &file.fname,
+            section,
+            o: file.byteorder,
+            pos: 0,
+            last: 0,
+        }
+    }
+
+    /// Reads a field at the current offset
+    pub fn read<T: StreamReader>(&mut self, field: &str) -> Result<T> {
+        let mut data = self.section.contents.get(self.pos..).unwrap_or_default();
+        let len = data.len();
+        let value = T::read_from(&mut data, self.o).map_err(|_| self.truncated(field))?;
+        self.last = self.pos;
+        self.pos += len - data.len();
+        Ok(value)
+    }
+
+    /// Reads `len` bytes at the current offset
+    pub fn bytes(&mut self, len: usize, field: &str) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or_else(|| self.truncated(field))?;
+        let data =
+            (self.section.contents.get(self.pos..end)).ok_or_else(|| self.truncated(field))?;
+        self.last = self.pos;
+        self.pos = end;
+        Ok(data)
+    }
+
+    /// Reads a 32-bit offset, relative to `base`, making sure it points inside the section
+    pub fn read_offset(&mut self, field: &str, base: usize) -> Result<usize> {
+        let target = base + self.read::<u32>(field)? as usize;
+        if target > self.section.contents.len() {
+            Err(Error::OffsetOutOfBounds {
+                file: self.file.to_string(),
+                section: self.section.magic.clone(),
+                field: field.to_string(),
+                offset: self.last,
+                target,
+            })?
+        }
+        Ok(target)
+    }
+
+    /// Moves to an offset of the section, reading past its end fails
+    pub fn seek(&mut self, offset: usize) {
+        self.pos = offset;
+    }
+
+    /// Current offset in the section
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Amount of bytes left after the current offset
+    pub fn remaining(&self) -> usize {
+        self.section.contents.len().saturating_sub(self.pos)
+    }
+
+    /// Error for the last field read having a value the format doesn't define
+    pub fn unknown_value(&self, field: &str, value: impl Into<u64>) -> Error {
+        Error::UnknownFieldValue {
+            file: self.file.to_string(),
+            section: self.section.magic.clone(),
+            field: field.to_string(),
+            offset: self.last,
+            value: value.into(),
+        }
+    }
+
+    fn truncated(&self, field: &str) -> Error {
+        Error::TruncatedSection {
+            file: self.file.to_string(),
+            section: self.section.magic.clone(),
+            field: field.to_string(),
+            offset: self.pos,
+        }
+    }
+}
+
+impl NDSFile {
+    /// Version written to files that weren't read from one
+    pub const DEFAULT_VERSION: u16 = 0x0100;
+
+    pub fn from_file<F: Read>(fname: &str, f: &mut F) -> Result<Self> {
+        let (magic, byteorder, version, section_count) = Self::read_header(fname, f)?;
+        let mut sections = Vec::with_capacity(section_count as usize);
+        let mut offset = 0x10;
         for _ in 0..section_count {
-            let mut s_magic = [0u8; 4];
-            f.read_exact(&mut s_magic)?;
-            let size = u32::read_from(f, o)? - 0x08;
+            let (s_magic, size) = Self::read_section_header(fname, f, byteorder, offset)?;
+            // Read through take() so a bogus size can't allocate more than the file has
             let mut s_contents = vec![];
-            for _ in 0..size {
-                s_contents.push(u8::read_from(f, o)?)
+            f.take(size as u64 - 0x08).read_to_end(&mut s_contents)?;
+            if s_contents.len() != size as usize - 0x08 {
+                Err(truncated_error(
+                    fname,
+                    &format!("{} contents", s_magic),
+                    offset + 8,
+                ))?
             }
+            offset += size as usize;
             sections.push(Section {
-                magic: String::from_utf8(s_magic.into()).unwrap(),
+                magic: s_magic,
                 contents: s_contents,
             });
         }
 
         Ok(Self {
             fname: fname.to_string(),
-            magic: String::from_utf8(magic.into()).unwrap(),
+            magic,
             sections,
-            byteorder: o,
+            byteorder,
+            version,
         })
     }
 
+    /// Reads the header up to the first section, returning its magic, byte order, version and
+    /// section count
+    fn read_header<F: Read>(fname: &str, f: &mut F) -> Result<(String, ByteOrder, u16, u16)> {
+        let mut magic = [0u8; 4];
+        f.read_exact(&mut magic)
+            .map_err(truncated(fname, "magic", 0))?;
+        let magic_str = Self::magic_to_string(fname, "magic", 0, magic)?;
+
+        let mut bom = [0u8; 2];
+        f.read_exact(&mut bom)
+            .map_err(truncated(fname, "byte order mark", 0x04))?;
+        if Self::reversed_bom(&magic) {
+            bom.reverse();
+        }
+        let o = match bom {
+            [0xFF, 0xFE] => ByteOrder::LittleEndian,
+            [0xFE, 0xFF] => ByteOrder::BigEndian,
+            _ => Err(Error::InvalidBOM {
+                file: fname.to_string(),
+            })?,
+        };
+
+        let version = u16::read_from(f, o).map_err(truncated(fname, "version", 0x06))?;
+        u32::read_from(f, o).map_err(truncated(fname, "file size", 0x08))?; // We can discard it here
+        u16::read_from(f, o).map_err(truncated(fname, "header size", 0x0C))?; // Always 0x10
+        let section_count =
+            u16::read_from(f, o).map_err(truncated(fname, "section count", 0x0E))?;
+
+        Ok((magic_str, o, version, section_count))
+    }
+
+    /// Reads the magic and size of the section at `offset`, the size includes the 8 bytes read
+    fn read_section_header<F: Read>(
+        fname: &str,
+        f: &mut F,
+        o: ByteOrder,
+        offset: usize,
+    ) -> Result<(String, u32)> {
+        let mut magic = [0u8; 4];
+        f.read_exact(&mut magic)
+            .map_err(truncated(fname, "section magic", offset))?;
+        let magic = Self::magic_to_string(fname, "section magic", offset, magic)?;
+        let size_field = format!("{} size", magic);
+        let size = u32::read_from(f, o).map_err(truncated(fname, &size_field, offset + 4))?;
+        if size < 0x08 {
+            Err(Error::UnknownFieldValue {
+                file: fname.to_string(),
+                section: "header".to_string(),
+                field: size_field,
+                offset: offset + 4,
+                value: size as u64,
+            })?
+        }
+        Ok((magic, size))
+    }
+
     pub fn to_file<F: Write + Seek>(&self, f: &mut F) -> Result<()> {
         f.write_all(self.magic.as_bytes())?;
-        f.write_all(match self.byteorder {
-            ByteOrder::BigEndian => &[0xFE, 0xFF],
-            ByteOrder::LittleEndian => &[0xFF, 0xFE],
-        })?;
+        let mut bom = match self.byteorder {
+            ByteOrder::BigEndian => [0xFE, 0xFF],
+            ByteOrder::LittleEndian => [0xFF, 0xFE],
+        };
+        if Self::reversed_bom(self.magic.as_bytes()) {
+            bom.reverse();
+        }
+        f.write_all(&bom)?;
 
-        1u16.write_to(f, self.byteorder)?;
+        self.version.write_to(f, self.byteorder)?;
         0u32.write_to(f, self.byteorder)?; // This will be written later with the entire filesize
         0x10u16.write_to(f, self.byteorder)?;
         (self.sections.len() as u16).write_to(f, self.byteorder)?; // Section count
@@ -86,6 +295,25 @@ impl NDSFile {
 
         Ok(())
     }
+
+    /// Magics are always ASCII, anything else means the file isn't a Nintendo file
+    fn magic_to_string(fname: &str, field: &str, offset: usize, magic: [u8; 4]) -> Result<String> {
+        match magic.is_ascii() {
+            true => Ok(String::from_utf8_lossy(&magic).to_string()),
+            false => Err(Error::UnknownFieldValue {
+                file: fname.to_string(),
+                section: "header".to_string(),
+                field: field.to_string(),
+                offset,
+                value: u32::from_be_bytes(magic) as u64,
+            }),
+        }
+    }
+
+    /// NARC archives write their BOM as 0xFFFE instead of the usual 0xFEFF
+    fn reversed_bom(magic: &[u8]) -> bool {
+        magic == b"NARC"
+    }
 }
 
 impl Debug for NDSFile {
@@ -99,22 +327,255 @@ impl Debug for NDSFile {
                     ByteOrder::LittleEndian => &"little",
                 },
             )
+            .field("version", &self.version)
             .field("sections", &self.sections)
             .finish()
     }
 }
 
+#[derive(Debug, Clone)]
+/// Where a section is in a file, found without reading its contents
+pub struct SectionEntry {
+    pub magic: String,
+    /// Offset of the section's contents from the start of the file, past its 8-byte header
+    pub offset: usize,
+    /// Size of the section's contents, without its header
+    pub len: usize,
+}
+
+#[derive(Clone)]
+/// Header of a Nintendo file and the location of its sections, read without loading any section
+/// contents, so many files can be scanned quickly
+///
+/// Contents are read only when asked for, either by seeking in the reader the index was made
+/// from or by borrowing them from the byte slice it was made from, like a memory-mapped file.
+/// Compressed files have to be decompressed before they can be indexed
+pub struct NDSFileIndex {
+    pub fname: String,
+    pub magic: String,
+    pub byteorder: ByteOrder,
+    pub version: u16,
+    pub sections: Vec<SectionEntry>,
+    /// Position of the file in the reader or slice it was indexed from
+    pub base: u64,
+}
+
+impl NDSFileIndex {
+    /// Indexes the file starting at the reader's current position and ending with the reader
+    pub fn from_reader<R: Read + Seek>(fname: &str, f: &mut R) -> Result<Self> {
+        let base = f.stream_position()?;
+        let end = f.seek(SeekFrom::End(0))?;
+        f.seek(SeekFrom::Start(base))?;
+        Self::from_reader_sized(fname, f, end.saturating_sub(base))
+    }
+
+    /// Indexes the file starting at the reader's current position, which is `size` bytes long -
+    /// for files stored inside bigger files like ROMs
+    pub fn from_reader_sized<R: Read + Seek>(fname: &str, f: &mut R, size: u64) -> Result<Self> {
+        let base = f.stream_position()?;
+        // Headers are read through take() so they can't run into whatever follows the file
+        let (magic, byteorder, version, section_count) =
+            NDSFile::read_header(fname, &mut (&mut *f).take(size))?;
+        let size = size as usize;
+
+        let mut sections = Vec::with_capacity(section_count as usize);
+        let mut offset = 0x10;
+        for _ in 0..section_count {
+            let mut f_section = (&mut *f).take(size.saturating_sub(offset) as u64);
+            let (s_magic, s_size) =
+                NDSFile::read_section_header(fname, &mut f_section, byteorder, offset)?;
+            let len = s_size as usize - 0x08;
+            if offset + 8 + len > size {
+                Err(truncated_error(
+                    fname,
+                    &format!("{} contents", s_magic),
+                    offset + 8,
+                ))?
+            }
+            f.seek(SeekFrom::Current(len as i64))?;
+            sections.push(SectionEntry {
+                magic: s_magic,
+                offset: offset + 8,
+                len,
+            });
+            offset += s_size as usize;
+        }
+
+        Ok(Self {
+            fname: fname.to_string(),
+            magic,
+            byteorder,
+            version,
+            sections,
+            base,
+        })
+    }
+
+    /// Indexes the file in the given bytes, which can then be borrowed with [Self::section_bytes]
+    pub fn from_bytes(fname: &str, data: &[u8]) -> Result<Self> {
+        Self::from_reader(fname, &mut Cursor::new(data))
+    }
+
+    /// Gets the first section with the given magic
+    pub fn find(&self, magic: &str) -> Option<&SectionEntry> {
+        self.sections.iter().find(|c| c.magic == magic)
+    }
+
+    /// Borrows the contents of a section from the data the file was indexed from, without copying
+    ///
+    /// For files indexed with [Self::from_reader], `data` is everything the reader holds
+    pub fn section_bytes<'a>(&self, data: &'a [u8], entry: &SectionEntry) -> Result<&'a [u8]> {
+        let start = self.base as usize + entry.offset;
+        data.get(start..start + entry.len).ok_or_else(|| {
+            truncated_error(
+                &self.fname,
+                &format!("{} contents", entry.magic),
+                entry.offset,
+            )
+        })
+    }
+
+    /// Reads the contents of a section from the reader the file was indexed from
+    pub fn read_section<R: Read + Seek>(&self, f: &mut R, entry: &SectionEntry) -> Result<Section> {
+        f.seek(SeekFrom::Start(self.base + entry.offset as u64))?;
+        let mut contents = vec![0u8; entry.len];
+        f.read_exact(&mut contents).map_err(truncated(
+            &self.fname,
+            &format!("{} contents", entry.magic),
+            entry.offset,
+        ))?;
+        Ok(Section {
+            magic: entry.magic.clone(),
+            contents,
+        })
+    }
+
+    /// Reads every section from the reader the file was indexed from
+    pub fn load<R: Read + Seek>(&self, f: &mut R) -> Result<NDSFile> {
+        Ok(NDSFile {
+            fname: self.fname.clone(),
+            magic: self.magic.clone(),
+            byteorder: self.byteorder,
+            version: self.version,
+            sections: self
+                .sections
+                .iter()
+                .map(|c| self.read_section(f, c))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl Debug for NDSFileIndex {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("NDSFileIndex")
+            .field("magic", &self.magic)
+            .field(
+                "byteorder",
+                match self.byteorder {
+                    ByteOrder::BigEndian => &"big",
+                    ByteOrder::LittleEndian => &"little",
+                },
+            )
+            .field("version", &self.version)
+            .field("sections", &self.sections)
+            .field("base", &self.base)
+            .finish()
+    }
+}
+
+/// Error for a header field that the file ends before
+fn truncated_error(fname: &str, field: &str, offset: usize) -> Error {
+    Error::TruncatedSection {
+        file: fname.to_string(),
+        section: "header".to_string(),
+        field: field.to_string(),
+        offset,
+    }
+}
+
+/// Turns running out of data while reading a header field into [truncated_error]
+fn truncated<'a>(
+    fname: &'a str,
+    field: &'a str,
+    offset: usize,
+) -> impl FnOnce(io::Error) -> Error + 'a {
+    move |e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated_error(fname, field, offset),
+        _ => Error::IOError(e),
+    }
+}
+
 pub trait NDSFileType
 where
     Self: Sized,
 {
-    fn from_ndsfile(file: &NDSFile) -> Result<Self>;
+    /// Parses the file, handling sections the format doesn't define as `mode` says
+    fn from_ndsfile_with(file: &NDSFile, mode: ParseMode) -> Result<Self>;
     fn to_ndsfile(&self, fname: String, order: ByteOrder) -> Result<NDSFile>;
 
+    /// Parses the file, failing if it has sections the format doesn't define
+    fn from_ndsfile(file: &NDSFile) -> Result<Self> {
+        Self::from_ndsfile_with(file, ParseMode::Strict)
+    }
+    /// Reads the file from a reader, decompressing it first if it's LZ10/LZ11 compressed
     fn from_file<F: Read>(fname: &str, f: &mut F) -> Result<Self> {
-        Self::from_ndsfile(&NDSFile::from_file(fname, f)?)
+        Self::from_file_with(fname, f, ParseMode::Strict)
+    }
+    /// Like [Self::from_file], handling sections the format doesn't define as `mode` says
+    fn from_file_with<F: Read>(fname: &str, f: &mut F, mode: ParseMode) -> Result<Self> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        data = compress::decompress_if_needed(data)?;
+        Self::from_ndsfile_with(&NDSFile::from_file(fname, &mut data.as_slice())?, mode)
+    }
+    /// Reads the file at the given path inside a ROM's file system
+    fn from_rom<R: Read + Seek>(rom: &mut NDSRom<R>, path: &str) -> Result<Self> {
+        Self::from_file(path, &mut rom.open_file(path)?)
     }
     fn to_file<F: Write + Seek>(&self, f: &mut F, fname: String, order: ByteOrder) -> Result<()> {
         self.to_ndsfile(fname, order)?.to_file(f)
     }
+
+    /// Reads the file and writes it back, returning where the result first differs from the
+    /// original, or nothing if they're the same
+    ///
+    /// Compressed files are compared after decompressing them
+    fn verify_round_trip(
+        fname: &str,
+        data: &[u8],
+        mode: ParseMode,
+    ) -> Result<Option<RoundTripMismatch>> {
+        let data = compress::decompress_if_needed(data.to_vec())?;
+        let file = NDSFile::from_file(fname, &mut data.as_slice())?;
+        let mut written = Cursor::new(vec![]);
+        Self::from_ndsfile_with(&file, mode)?.to_file(&mut written, file.fname, file.byteorder)?;
+        Ok(RoundTripMismatch::find(&data, &written.into_inner()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// First difference between a file and what it was written back as
+pub struct RoundTripMismatch {
+    pub offset: usize,
+    /// Byte of the original file, if it isn't shorter than the written one
+    pub original: Option<u8>,
+    /// Byte of the written file, if it isn't shorter than the original one
+    pub written: Option<u8>,
+}
+
+impl RoundTripMismatch {
+    /// Compares two files byte by byte
+    pub fn find(original: &[u8], written: &[u8]) -> Option<Self> {
+        let offset = match original.iter().zip(written).position(|(a, b)| a != b) {
+            Some(c) => c,
+            None if original.len() == written.len() => return None,
+            None => original.len().min(written.len()),
+        };
+        Some(Self {
+            offset,
+            original: original.get(offset).copied(),
+            written: written.get(offset).copied(),
+        })
+    }
 }
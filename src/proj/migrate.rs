@@ -0,0 +1,132 @@
+use crate::{
+    error::{Error, Result},
+    img::NCLR,
+    ndsfile::NDSFile,
+};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, path::Path};
+
+/// Latest version of the project format, written to new and migrated projects
+pub const PROJECT_VERSION: u32 = 4;
+
+/// Upgrades a project from the version at the same index to the next one
+type Migration = fn(&mut Value) -> Option<()>;
+
+const MIGRATIONS: [Migration; PROJECT_VERSION as usize] = [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// Upgrades the contents of a `nuclear_meta.json` to [PROJECT_VERSION]
+///
+/// Returns whether anything had to be migrated. Projects made before versioning are version 0.
+pub fn migrate(meta: &mut Value, meta_path: &Path) -> Result<bool> {
+    let malformed =
+        |reason: &str| Error::FileFormatWrong(meta_path.to_path_buf(), reason.to_string());
+
+    let version = match meta.get("version") {
+        Some(c) => c
+            .as_u64()
+            .ok_or_else(|| malformed("project version isn't a number"))?,
+        None => 0,
+    };
+    if version > PROJECT_VERSION as u64 {
+        Err(Error::UnsupportedVersion {
+            file: meta_path.display().to_string(),
+            version: version.min(u32::MAX as u64) as u32,
+            max: PROJECT_VERSION,
+        })?
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(meta)
+            .ok_or_else(|| malformed("project couldn't be upgraded to the current version"))?;
+    }
+    meta.as_object_mut()
+        .ok_or_else(|| malformed("project metadata isn't an object"))?
+        .insert("version".to_string(), PROJECT_VERSION.into());
+    Ok(version != PROJECT_VERSION as u64)
+}
+
+/// Palette sets, tilesets and tilemaps of a project
+const COLLECTIONS: [&str; 3] = ["palette_sets", "tilesets", "tilemaps"];
+
+/// Version 1 adds the version field itself, and always has every collection - older projects
+/// could leave out the ones they didn't use
+fn v0_to_v1(meta: &mut Value) -> Option<()> {
+    let meta = meta.as_object_mut()?;
+    for collection in COLLECTIONS {
+        meta.entry(collection)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    Some(())
+}
+
+/// Gets the entries of a collection, a missing collection being empty
+fn entries<'a>(meta: &'a mut Value, collection: &str) -> Option<Vec<&'a mut Map<String, Value>>> {
+    match meta.get_mut(collection) {
+        Some(c) => c
+            .as_object_mut()?
+            .values_mut()
+            .map(|c| c.as_object_mut())
+            .collect(),
+        None => Some(vec![]),
+    }
+}
+
+/// Version 2 stores the screen mode and color depth of each tilemap, the latter taken from the
+/// tileset it uses
+fn v1_to_v2(meta: &mut Value) -> Option<()> {
+    let tileset_depths: HashMap<String, bool> = match meta.get("tilesets") {
+        Some(c) => c
+            .as_object()?
+            .iter()
+            .filter_map(|(name, c)| Some((name.clone(), c.get("is_8_bit")?.as_bool()?)))
+            .collect(),
+        None => HashMap::new(),
+    };
+    for tilemap in entries(meta, "tilemaps")? {
+        let is_8_bit = tilemap
+            .get("associated_tileset")
+            .and_then(|c| c.as_str())
+            .and_then(|c| tileset_depths.get(c))
+            .copied()
+            .unwrap_or(false);
+        tilemap.entry("screen_mode").or_insert(0.into());
+        tilemap.entry("is_8_bit").or_insert(is_8_bit.into());
+    }
+    Some(())
+}
+
+/// Version 3 stores whether each palette set uses extended palettes
+fn v2_to_v3(meta: &mut Value) -> Option<()> {
+    for palette_set in entries(meta, "palette_sets")? {
+        palette_set.entry("is_extended").or_insert(false.into());
+    }
+    Some(())
+}
+
+/// Version 4 stores the header version and unknown fields of the original files, so they can be
+/// exported exactly as they were imported
+fn v3_to_v4(meta: &mut Value) -> Option<()> {
+    let version = Value::from(NDSFile::DEFAULT_VERSION);
+    for palette_set in entries(meta, "palette_sets")? {
+        palette_set.entry("version").or_insert(version.clone());
+        palette_set
+            .entry("pcmp_unknown")
+            .or_insert(NCLR::PCMP_UNKNOWN.to_vec().into());
+        palette_set
+            .entry("unreferenced_colors")
+            .or_insert(Value::Array(vec![]));
+        palette_set
+            .entry("pltt_trailing")
+            .or_insert(Value::Array(vec![]));
+    }
+    for tileset in entries(meta, "tilesets")? {
+        tileset.entry("cpos_unknown").or_insert(0.into());
+        tileset.entry("mapping_type").or_insert(0.into());
+        tileset.entry("tile_mode_flags").or_insert(0.into());
+        tileset.entry("version").or_insert(version.clone());
+    }
+    for tilemap in entries(meta, "tilemaps")? {
+        tilemap.entry("version").or_insert(version.clone());
+    }
+    Some(())
+}
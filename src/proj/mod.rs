@@ -0,0 +1,685 @@
+use crate::{
+    compress::{self, Compression},
+    error::{Error, Result},
+    extend::{FileType, FormatType},
+    img::{
+        ncgr::{NCGRTiles, Tile},
+        nscr::{ScreenMode, TileRef},
+        ColorBGR555, NCGR, NCLR, NSCR,
+    },
+    narc::NARC,
+    nds_rom::NDSRom,
+    ndsfile::{NDSFile, NDSFileType},
+};
+use bytestream::{ByteOrder, StreamReader, StreamWriter};
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+pub mod migrate;
+pub mod portable;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NCLRWrapper {
+    pub folder: PathBuf,
+    pub palettes: BTreeMap<u16, PathBuf>,
+    pub is_8_bit: bool,
+    /// Whether the palettes are extended palettes, see [NCLR::is_extended]
+    #[serde(default)]
+    pub is_extended: bool,
+    /// Format version of the original file, see [NCLR::version]
+    #[serde(default = "default_version")]
+    pub version: u16,
+    /// See [NCLR::pcmp_unknown]
+    #[serde(default = "default_pcmp_unknown")]
+    pub pcmp_unknown: [u8; 6],
+    /// Colors after the palettes listed in the original file, as little endian BGR555 data -
+    /// see [NCLR::unreferenced_colors]
+    #[serde(default)]
+    pub unreferenced_colors: Vec<u8>,
+    /// See [NCLR::pltt_padding]
+    #[serde(default)]
+    pub pltt_padding: Vec<u8>,
+    /// See [NCLR::pltt_trailing]
+    #[serde(default)]
+    pub pltt_trailing: Vec<u8>,
+    #[serde(skip, default)]
+    pub bin: BTreeMap<u16, Vec<u8>>, // to be loaded at project load
+}
+
+fn default_version() -> u16 {
+    NDSFile::DEFAULT_VERSION
+}
+
+fn default_pcmp_unknown() -> [u8; 6] {
+    NCLR::PCMP_UNKNOWN
+}
+
+impl NCLRWrapper {
+    pub fn get_inner(&self) -> Result<NCLR> {
+        let mut palettes = BTreeMap::new();
+        let mut color_amt = 0;
+        for (id, pal) in &self.bin {
+            let mut pal: &[u8] = pal;
+            let mut colors = vec![];
+            while !pal.is_empty() {
+                colors.push(ColorBGR555::read_from(&mut pal, ByteOrder::LittleEndian)?);
+            }
+
+            if color_amt == 0 {
+                color_amt = colors.len();
+            } else if colors.len() != color_amt {
+                Err(Error::FileFormatWrong(
+                    self.palettes.get(id).unwrap().to_path_buf(),
+                    format!(
+                        "All palletes must have same number of colors (found {} and {})",
+                        colors.len(),
+                        color_amt
+                    ),
+                ))?;
+            }
+
+            palettes.insert(*id, colors);
+        }
+        let mut unreferenced: &[u8] = &self.unreferenced_colors;
+        let mut unreferenced_colors = vec![];
+        while !unreferenced.is_empty() {
+            unreferenced_colors.push(ColorBGR555::read_from(
+                &mut unreferenced,
+                ByteOrder::LittleEndian,
+            )?);
+        }
+        Ok(NCLR {
+            palettes,
+            is_8_bit: self.is_8_bit,
+            color_amt: color_amt as u32,
+            is_extended: self.is_extended,
+            version: self.version,
+            pcmp_unknown: self.pcmp_unknown,
+            unreferenced_colors,
+            pltt_padding: self.pltt_padding.clone(),
+            pltt_trailing: self.pltt_trailing.clone(),
+            extra_sections: vec![],
+        })
+    }
+
+    /// Creates a wrapper from a NCLR file, to be stored in `pal/{name}`
+    pub fn from_inner(nclr: &NCLR, name: &str) -> Result<Self> {
+        let mut palettes = BTreeMap::new();
+        let mut bin = BTreeMap::new();
+        for (id, palette) in &nclr.palettes {
+            let mut data = vec![];
+            for color in palette {
+                color.write_to(&mut data, ByteOrder::LittleEndian)?;
+            }
+            palettes.insert(*id, PathBuf::from(format!("pal{:X}.bin", id)));
+            bin.insert(*id, data);
+        }
+        let mut unreferenced_colors = vec![];
+        for color in &nclr.unreferenced_colors {
+            color.write_to(&mut unreferenced_colors, ByteOrder::LittleEndian)?;
+        }
+
+        Ok(Self {
+            folder: name.into(),
+            palettes,
+            is_8_bit: nclr.is_8_bit,
+            is_extended: nclr.is_extended,
+            version: nclr.version,
+            pcmp_unknown: nclr.pcmp_unknown,
+            unreferenced_colors,
+            pltt_padding: nclr.pltt_padding.clone(),
+            pltt_trailing: nclr.pltt_trailing.clone(),
+            bin,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NCGRWrapper {
+    pub tiles: PathBuf,
+    pub has_cpos: bool,
+    pub is_8_bit: bool,
+    pub ncbr_ff: bool,
+    pub lineal_mode: bool,
+    /// Width and height in tiles of the original file
+    #[serde(default)]
+    pub size: Option<[u16; 2]>,
+    /// See [NCGR::cpos_unknown]
+    #[serde(default)]
+    pub cpos_unknown: u32,
+    /// See [NCGR::mapping_type]
+    #[serde(default)]
+    pub mapping_type: u32,
+    /// See [NCGR::tile_mode_flags]
+    #[serde(default)]
+    pub tile_mode_flags: u32,
+    /// Format version of the original file, see [NCGR::version]
+    #[serde(default = "default_version")]
+    pub version: u16,
+    pub associated_palette: Option<String>,
+    #[serde(skip, default)]
+    pub bin: Vec<u8>, // to be loaded at project load
+}
+
+impl NCGRWrapper {
+    pub fn get_inner(&self) -> Result<NCGR> {
+        let tiles = if self.lineal_mode {
+            NCGRTiles::Lineal(self.bin.clone())
+        } else {
+            let mut tiles: Vec<Tile> = vec![];
+            let mut f: &[u8] = &self.bin;
+            for _ in 0..self.bin.len() / 64 {
+                let mut tslice = [0u8; 64];
+                f.read_exact(&mut tslice)?;
+                tiles.push(tslice.into());
+            }
+            NCGRTiles::Horizontal(tiles)
+        };
+
+        Ok(NCGR {
+            tiles,
+            has_cpos: self.has_cpos,
+            is_8_bit: self.is_8_bit,
+            ncbr_ff: self.ncbr_ff,
+            size: self.size,
+            cpos_unknown: self.cpos_unknown,
+            mapping_type: self.mapping_type,
+            tile_mode_flags: self.tile_mode_flags,
+            version: self.version,
+            extra_sections: vec![],
+        })
+    }
+
+    /// Creates a wrapper from a NCGR file, to be stored in `img/tile_{name}.bin`
+    pub fn from_inner(ncgr: &NCGR, name: &str) -> Result<Self> {
+        let (bin, lineal_mode) = match &ncgr.tiles {
+            NCGRTiles::Horizontal(c) => (c.concat(), false),
+            NCGRTiles::Lineal(c) => (c.clone(), true),
+        };
+
+        Ok(Self {
+            tiles: PathBuf::from(format!("tile_{}.bin", name)),
+            has_cpos: ncgr.has_cpos,
+            is_8_bit: ncgr.is_8_bit,
+            ncbr_ff: ncgr.ncbr_ff,
+            lineal_mode,
+            size: ncgr.size,
+            cpos_unknown: ncgr.cpos_unknown,
+            mapping_type: ncgr.mapping_type,
+            tile_mode_flags: ncgr.tile_mode_flags,
+            version: ncgr.version,
+            associated_palette: None,
+            bin,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NSCRWrapper {
+    pub map: PathBuf,
+    pub width: u16,
+    pub height: u16,
+    /// Screen mode of the tilemap, as stored in NSCR files - see [ScreenMode]
+    #[serde(default)]
+    pub screen_mode: u16,
+    #[serde(default)]
+    pub is_8_bit: bool,
+    /// Format version of the original file, see [NSCR::version]
+    #[serde(default = "default_version")]
+    pub version: u16,
+    pub associated_tileset: Option<String>,
+    #[serde(skip, default)]
+    pub bin: Vec<u8>, // to be loaded at project load
+}
+
+impl NSCRWrapper {
+    pub fn get_inner(&self) -> Result<NSCR> {
+        let mut tiles = vec![];
+        let mut bin: &[u8] = &self.bin;
+        while !bin.is_empty() {
+            tiles.push(TileRef {
+                tile: u16::read_from(&mut bin, ByteOrder::LittleEndian)?,
+                flip_x: bool::read_from(&mut bin, ByteOrder::LittleEndian)?,
+                flip_y: bool::read_from(&mut bin, ByteOrder::LittleEndian)?,
+                palette: u8::read_from(&mut bin, ByteOrder::LittleEndian)?,
+            })
+        }
+
+        let mode = ScreenMode::new(self.screen_mode).ok_or_else(|| Error::MalformedData {
+            file: self.map.display().to_string(),
+        })?;
+
+        Ok(NSCR {
+            tiles,
+            width: self.width,
+            height: self.height,
+            mode,
+            is_8_bit: self.is_8_bit,
+            version: self.version,
+            extra_sections: vec![],
+        })
+    }
+
+    /// Creates a wrapper from a NSCR file, to be stored in `map/map_{name}.bin`
+    pub fn from_inner(nscr: &NSCR, name: &str) -> Result<Self> {
+        let mut bin = vec![];
+        for tile in &nscr.tiles {
+            tile.tile.write_to(&mut bin, ByteOrder::LittleEndian)?;
+            tile.flip_x.write_to(&mut bin, ByteOrder::LittleEndian)?;
+            tile.flip_y.write_to(&mut bin, ByteOrder::LittleEndian)?;
+            tile.palette.write_to(&mut bin, ByteOrder::LittleEndian)?;
+        }
+
+        Ok(Self {
+            map: PathBuf::from(format!("map_{}.bin", name)),
+            width: nscr.width,
+            height: nscr.height,
+            screen_mode: nscr.mode.to_u16(),
+            is_8_bit: nscr.is_8_bit,
+            version: nscr.version,
+            associated_tileset: None,
+            bin,
+        })
+    }
+}
+
+/// Files added to a project from a ROM or archive
+#[derive(Debug, Default)]
+pub struct ImportedFiles {
+    /// Names of the files added to the project
+    pub inserted: Vec<String>,
+    /// Names of the files that were recognized but couldn't be loaded, with the reason why
+    pub failed: Vec<(String, Error)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NuclearProject {
+    /// Version of the project format, see [migrate]
+    #[serde(default)]
+    pub version: u32,
+    pub name: String,
+    pub author: String,
+    pub description: String,
+    pub palette_sets: HashMap<String, NCLRWrapper>,
+    pub tilesets: HashMap<String, NCGRWrapper>,
+    pub tilemaps: HashMap<String, NSCRWrapper>,
+    //TODO: NCER, NANR
+    #[serde(skip, default)]
+    path: PathBuf,
+}
+
+impl NuclearProject {
+    /// Creates a new nuclear project on the specified path
+    pub fn new(name: &str, author: &str, description: &str, path: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&path)?;
+
+        let out = Self {
+            version: migrate::PROJECT_VERSION,
+            name: name.to_string(),
+            author: author.to_string(),
+            description: description.to_string(),
+            palette_sets: HashMap::new(),
+            tilesets: HashMap::new(),
+            tilemaps: HashMap::new(),
+            path,
+        };
+
+        out.write_meta()?;
+        Ok(out)
+    }
+
+    /// Writes the metadata of the project, effectively saving the project
+    ///
+    /// Might be extended to more functionality in the future
+    pub fn save(&self) -> Result<()> {
+        self.write_meta()
+    }
+
+    fn write_meta(&self) -> Result<()> {
+        let mut file = File::create(Self::proj_file_path(&self.path))?;
+        write!(file, "{}", serde_json::to_string_pretty(&self)?)?;
+        Ok(())
+    }
+
+    fn write_file(&self, path: &PathBuf, contents: &[u8]) -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(contents)?;
+        Ok(())
+    }
+
+    fn read_file(own_path: &Path, path: &PathBuf) -> Result<Vec<u8>> {
+        let mut path_ = own_path.to_path_buf();
+        path_.extend(path);
+        let mut file = File::open(path_)?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn proj_file_path(proj_path: &Path) -> PathBuf {
+        let mut path = proj_path.to_path_buf();
+        path.extend(&PathBuf::from("nuclear_meta.json"));
+        path
+    }
+
+    pub fn load_from_file(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let meta_path = Self::proj_file_path(&path);
+        let mut meta = File::open(&meta_path)?;
+
+        let mut json = String::new();
+        meta.read_to_string(&mut json)?;
+
+        let mut json: serde_json::Value = serde_json::from_str(&json)?;
+        let migrated = migrate::migrate(&mut json, &meta_path)?;
+        let mut project: NuclearProject = serde_json::from_value(json)
+            .map_err(|e| Error::FileFormatWrong(meta_path.clone(), e.to_string()))?;
+        project.path = path;
+
+        let path: PathBuf = "pal".into();
+        for pal in &mut project.palette_sets {
+            let pal = pal.1;
+            let mut path = path.clone();
+            path.extend(&pal.folder);
+            for palette in &pal.palettes {
+                let mut path = path.clone();
+                path.extend(palette.1);
+                pal.bin
+                    .insert(*palette.0, Self::read_file(&project.path, &path)?);
+            }
+        }
+
+        let path: PathBuf = "img".into();
+        for tiles in &mut project.tilesets {
+            let tiles = tiles.1;
+            let mut path = path.clone();
+            path.extend(&tiles.tiles);
+            tiles.bin = Self::read_file(&project.path, &path)?;
+        }
+
+        let path: PathBuf = "map".into();
+        for map in &mut project.tilemaps {
+            let map = map.1;
+            let mut path = path.clone();
+            path.extend(&map.map);
+            map.bin = Self::read_file(&project.path, &path)?;
+        }
+
+        //TODO: NCER, NANR
+
+        // Save the upgraded metadata so the migration only happens once
+        if migrated {
+            project.write_meta()?;
+        }
+
+        Ok(project)
+    }
+
+    /// Adds a NCLR file to the project. If it already exists, it replaces the previous version.
+    /// Will reset the palette files to their original positions!!
+    pub fn insert_nclr(&mut self, name: &str, nclr: &NCLR) -> Result<()> {
+        let wrapper = NCLRWrapper::from_inner(nclr, name)?;
+
+        let mut path = self.path.clone();
+        path.extend(&PathBuf::from("pal"));
+        path.extend(&wrapper.folder);
+        fs::create_dir_all(&path)?;
+        for (id, fname) in &wrapper.palettes {
+            self.write_file(&path.join(fname), &wrapper.bin[id])?;
+        }
+
+        self.palette_sets.insert(name.to_string(), wrapper);
+        self.write_meta()?;
+        Ok(())
+    }
+
+    /// Gets the specified NCLR file from the project
+    pub fn get_nclr(&self, name: &str) -> Result<Option<NCLR>> {
+        let wrapper = match self.palette_sets.get(name) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        Ok(Some(wrapper.get_inner()?))
+    }
+
+    /// Adds a NCGR file to the project. If it already exists, it replaces the previous version.
+    /// Will reset the tile file to its original position!!
+    pub fn insert_ncgr(&mut self, name: &str, ncgr: &NCGR) -> Result<()> {
+        let wrapper = NCGRWrapper::from_inner(ncgr, name)?;
+
+        let mut path = self.path.clone();
+        path.extend(&PathBuf::from("img"));
+        fs::create_dir_all(&path)?;
+        self.write_file(&path.join(&wrapper.tiles), &wrapper.bin)?;
+
+        self.tilesets.insert(name.to_string(), wrapper);
+        self.write_meta()?;
+        Ok(())
+    }
+
+    /// Gets the specified NCGR file from the project
+    pub fn get_ncgr(&self, name: &str) -> Result<Option<NCGR>> {
+        let wrapper = match self.tilesets.get(name) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        Ok(Some(wrapper.get_inner()?))
+    }
+
+    /// Adds a NSCR file to the project. If it already exists, it replaces the previous version.
+    /// Will reset the tilemap file to its original position!!
+    pub fn insert_nscr(&mut self, name: &str, nscr: &NSCR) -> Result<()> {
+        let wrapper = NSCRWrapper::from_inner(nscr, name)?;
+
+        let mut path = self.path.clone();
+        path.extend(&PathBuf::from("map"));
+        fs::create_dir_all(&path)?;
+        self.write_file(&path.join(&wrapper.map), &wrapper.bin)?;
+
+        self.tilemaps.insert(name.to_string(), wrapper);
+        self.write_meta()?;
+        Ok(())
+    }
+
+    /// Gets the specified NSCR file from the project
+    pub fn get_nscr(&self, name: &str) -> Result<Option<NSCR>> {
+        let wrapper = match self.tilemaps.get(name) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        Ok(Some(wrapper.get_inner()?))
+    }
+
+    /// Writes the specified palette set to `path` as a NCLR file
+    pub fn export_nclr(
+        &self,
+        name: &str,
+        path: impl AsRef<Path>,
+        order: ByteOrder,
+        compression: Compression,
+    ) -> Result<()> {
+        let nclr = self.get_nclr(name)?.ok_or_else(|| self.not_found(name))?;
+        Self::export_file(&nclr, path.as_ref(), order, compression)
+    }
+
+    /// Writes the specified tileset to `path` as a NCGR file
+    pub fn export_ncgr(
+        &self,
+        name: &str,
+        path: impl AsRef<Path>,
+        order: ByteOrder,
+        compression: Compression,
+    ) -> Result<()> {
+        let ncgr = self.get_ncgr(name)?.ok_or_else(|| self.not_found(name))?;
+        Self::export_file(&ncgr, path.as_ref(), order, compression)
+    }
+
+    /// Writes the specified tilemap to `path` as a NSCR file
+    pub fn export_nscr(
+        &self,
+        name: &str,
+        path: impl AsRef<Path>,
+        order: ByteOrder,
+        compression: Compression,
+    ) -> Result<()> {
+        let nscr = self.get_nscr(name)?.ok_or_else(|| self.not_found(name))?;
+        Self::export_file(&nscr, path.as_ref(), order, compression)
+    }
+
+    /// Writes every palette set, tileset and tilemap in the project to `dir`, uncompressed
+    ///
+    /// Files are named after their name in the project. Returns the paths of the files written.
+    pub fn export_all(&self, dir: impl AsRef<Path>, order: ByteOrder) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut written = vec![];
+        for name in self.palette_sets.keys() {
+            let path = dir.join(format!("{}.NCLR", name));
+            self.export_nclr(name, &path, order, Compression::None)?;
+            written.push(path);
+        }
+        for (name, tileset) in &self.tilesets {
+            let ext = if tileset.ncbr_ff { "NCBR" } else { "NCGR" };
+            let path = dir.join(format!("{}.{}", name, ext));
+            self.export_ncgr(name, &path, order, Compression::None)?;
+            written.push(path);
+        }
+        for name in self.tilemaps.keys() {
+            let path = dir.join(format!("{}.NSCR", name));
+            self.export_nscr(name, &path, order, Compression::None)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    fn export_file<T: NDSFileType>(
+        file: &T,
+        path: &Path,
+        order: ByteOrder,
+        compression: Compression,
+    ) -> Result<()> {
+        let fname = match path.file_name() {
+            Some(c) => c.to_string_lossy().to_string(),
+            None => String::new(),
+        };
+        let mut data = Cursor::new(vec![]);
+        file.to_file(&mut data, fname, order)?;
+        fs::write(path, compress::compress(data.get_ref(), compression)?)?;
+        Ok(())
+    }
+
+    fn not_found(&self, name: &str) -> Error {
+        Error::FileNotFound {
+            file: format!("project {}", self.name),
+            path: name.to_string(),
+        }
+    }
+
+    /// Adds every palette, tileset and tilemap in a NARC archive to the project
+    ///
+    /// Files are named after their path in the archive, or `{prefix}_{id}` if they don't have one.
+    /// Files that look supported but fail to load are skipped and listed in [ImportedFiles::failed].
+    pub fn insert_narc(&mut self, narc: &NARC, prefix: &str) -> ImportedFiles {
+        let mut out = ImportedFiles::default();
+        for (id, (file, path)) in narc.files.iter().zip(narc.paths()).enumerate() {
+            let name = match path {
+                Some(c) => Self::name_from_path(&c),
+                None => format!("{}_{}", prefix, id),
+            };
+            match self.insert_detected(file, &name) {
+                Ok(true) => out.inserted.push(name),
+                Ok(false) => {}
+                Err(e) => out.failed.push((name, e)),
+            }
+        }
+        out
+    }
+
+    /// Add a file from a ROM's file system to the project - NARC archives get all their supported
+    /// files added, named after the archive
+    ///
+    /// Returns the names of the files inserted, and the archive files that couldn't be
+    pub fn insert_rom_file<R: Read + Seek>(
+        &mut self,
+        rom: &mut NDSRom<R>,
+        path: &str,
+    ) -> Result<ImportedFiles> {
+        let id = rom.find(path)?;
+        let data = compress::decompress_if_needed(rom.read_file(id)?)?;
+        let name = Self::name_from_path(path);
+
+        if data.starts_with(b"NARC") {
+            let narc = NARC::from_file(path, &mut data.as_slice())?;
+            return Ok(self.insert_narc(&narc, &name));
+        }
+        if !self.insert_detected(&data, &name)? {
+            Err(Error::Generic(format!(
+                "File {} isn't a palette, tileset or tilemap",
+                path
+            )))?
+        }
+        Ok(ImportedFiles {
+            inserted: vec![name],
+            failed: vec![],
+        })
+    }
+
+    /// Project names can't contain folders, so those get flattened and the extension removed
+    fn name_from_path(path: &str) -> String {
+        let path = Path::new(path.trim_start_matches('/'));
+        let mut parts: Vec<String> = match path.parent() {
+            Some(c) => c.iter().map(|c| c.to_string_lossy().to_string()).collect(),
+            None => vec![],
+        };
+        if let Some(c) = path.file_stem() {
+            parts.push(c.to_string_lossy().to_string());
+        }
+        parts.join("_")
+    }
+
+    /// Adds the given data if it's a (maybe compressed) supported Nintendo file,
+    /// returning whether it was inserted
+    fn insert_detected(&mut self, data: &[u8], name: &str) -> Result<bool> {
+        let data = match compress::decompress_if_needed(data.to_vec()) {
+            Ok(c) => c,
+            Err(_) => return Ok(false),
+        };
+        let ftype = match data.get(..4).and_then(FileType::from_magic) {
+            Some(c @ (FileType::Palette | FileType::Tileset | FileType::Tilemap)) => c,
+            _ => return Ok(false),
+        };
+        self.insert_file(&mut data.as_slice(), ftype, FormatType::Nintendo, name)?;
+        Ok(true)
+    }
+
+    /// Add a specific file to the project, with the given filetype
+    pub fn insert_file<F: Read>(
+        &mut self,
+        file: &mut F,
+        ftype: FileType,
+        format: FormatType,
+        name: &str,
+    ) -> Result<()> {
+        match (format, ftype) {
+            (FormatType::Nintendo, FileType::Palette) => {
+                self.insert_nclr(name, &NCLR::from_file(name, file)?)
+            }
+            (FormatType::Nintendo, FileType::Tileset) => {
+                self.insert_ncgr(name, &NCGR::from_file(name, file)?)
+            }
+            (FormatType::Nintendo, FileType::Tilemap) => {
+                self.insert_nscr(name, &NSCR::from_file(name, file)?)
+            }
+            (FormatType::Nintendo, _) => todo!("NCER and NANR importing"),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::NuclearProject;
+use crate::error::{Error, Result};
+use bytestream::{ByteOrder, StreamReader, StreamWriter};
+use std::{
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+/// Magic of portable project files - see binary_formats.md for the full layout
+pub const PORTABLE_MAGIC: &[u8; 4] = b"NUCP";
+/// Latest version of the portable format
+pub const PORTABLE_VERSION: u16 = 1;
+
+const META_FILE: &str = "nuclear_meta.json";
+
+impl NuclearProject {
+    /// Packs the project into a single portable file, which can be opened with [Self::import_portable]
+    pub fn export_portable<W: Write>(&self, f: &mut W) -> Result<()> {
+        let o = ByteOrder::LittleEndian;
+        let mut entries = vec![(
+            META_FILE.to_string(),
+            serde_json::to_string_pretty(&self)?.into_bytes(),
+        )];
+        for wrapper in self.palette_sets.values() {
+            for (id, fname) in &wrapper.palettes {
+                let path = Path::new("pal").join(&wrapper.folder).join(fname);
+                entries.push((archive_path(&path), wrapper.bin[id].clone()));
+            }
+        }
+        for wrapper in self.tilesets.values() {
+            let path = Path::new("img").join(&wrapper.tiles);
+            entries.push((archive_path(&path), wrapper.bin.clone()));
+        }
+        for wrapper in self.tilemaps.values() {
+            let path = Path::new("map").join(&wrapper.map);
+            entries.push((archive_path(&path), wrapper.bin.clone()));
+        }
+
+        let mut out = vec![];
+        out.write_all(PORTABLE_MAGIC)?;
+        PORTABLE_VERSION.write_to(&mut out, o)?;
+        0u16.write_to(&mut out, o)?;
+        (entries.len() as u32).write_to(&mut out, o)?;
+        for (path, contents) in &entries {
+            (path.len() as u16).write_to(&mut out, o)?;
+            out.write_all(path.as_bytes())?;
+            (contents.len() as u32).write_to(&mut out, o)?;
+            crc32fast::hash(contents).write_to(&mut out, o)?;
+            out.write_all(contents)?;
+        }
+        crc32fast::hash(&out).write_to(&mut out, o)?;
+
+        f.write_all(&out)?;
+        Ok(())
+    }
+
+    /// Unpacks a portable project into the folder at `path` and opens it
+    ///
+    /// The folder can't already contain a project. Every file is checked before anything is
+    /// written, so a corrupted archive won't leave a half-extracted project behind.
+    pub fn import_portable<R: Read>(f: &mut R, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let fname = "portable project";
+        let o = ByteOrder::LittleEndian;
+        let malformed = || Error::MalformedData {
+            file: fname.to_string(),
+        };
+
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        if data.len() < 0x10 {
+            Err(malformed())?
+        }
+        let (body, checksum) = data.split_at(data.len() - 4);
+        let expected = u32::read_from(&mut &checksum[..], o)?;
+        let got = crc32fast::hash(body);
+        if expected != got {
+            Err(Error::ChecksumMismatch {
+                file: fname.to_string(),
+                expected,
+                got,
+            })?
+        }
+
+        let mut body = body;
+        let mut magic = [0u8; 4];
+        body.read_exact(&mut magic)?;
+        if &magic != PORTABLE_MAGIC {
+            Err(Error::WrongFileKind {
+                file: fname.to_string(),
+                ftype: None,
+                expected: String::from_utf8_lossy(PORTABLE_MAGIC).to_string(),
+                got: String::from_utf8_lossy(&magic).to_string(),
+            })?
+        }
+        let version = u16::read_from(&mut body, o)?;
+        if version > PORTABLE_VERSION {
+            Err(Error::UnsupportedVersion {
+                file: fname.to_string(),
+                version: version as u32,
+                max: PORTABLE_VERSION as u32,
+            })?
+        }
+        u16::read_from(&mut body, o)?; // Reserved
+
+        let count = u32::read_from(&mut body, o)?;
+        let mut entries = vec![];
+        for _ in 0..count {
+            let len = u16::read_from(&mut body, o)? as usize;
+            let entry_path = String::from_utf8(body.get(..len).ok_or_else(malformed)?.to_vec())
+                .map_err(|_| malformed())?;
+            body = &body[len..];
+            let len = u32::read_from(&mut body, o)? as usize;
+            let expected = u32::read_from(&mut body, o)?;
+            let contents = body.get(..len).ok_or_else(malformed)?;
+            body = &body[len..];
+
+            let got = crc32fast::hash(contents);
+            if expected != got {
+                Err(Error::ChecksumMismatch {
+                    file: entry_path.clone(),
+                    expected,
+                    got,
+                })?
+            }
+            // Don't allow entries to be written outside of the project folder
+            let relative = PathBuf::from(&entry_path);
+            if entry_path.is_empty()
+                || !relative
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)))
+            {
+                Err(malformed())?
+            }
+            entries.push((relative, contents));
+        }
+        if !body.is_empty() || !entries.iter().any(|(c, _)| c == Path::new(META_FILE)) {
+            Err(malformed())?
+        }
+
+        if path.join(META_FILE).exists() {
+            Err(Error::Generic(format!(
+                "There's already a project in {}",
+                path.display()
+            )))?
+        }
+        for (relative, contents) in entries {
+            let file_path = path.join(relative);
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(file_path, contents)?;
+        }
+
+        Self::load_from_file(path)
+    }
+}
+
+/// Paths inside the archive always use `/`, no matter the platform
+fn archive_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,96 @@
+use nuclear::{
+    error::Error,
+    img::{export, nanr::RenderedFrame, ncer::CellImage},
+};
+
+const RED: [u8; 4] = [0xF8, 0, 0, 0xFF];
+const BLUE: [u8; 4] = [0, 0, 0xF8, 0xFF];
+const CLEAR: [u8; 4] = [0; 4];
+
+fn frame(width: usize, height: usize, pixels: &[[u8; 4]], duration: u16) -> RenderedFrame {
+    RenderedFrame {
+        image: CellImage {
+            width,
+            height,
+            x: 0,
+            y: 0,
+            pixels: pixels.concat(),
+        },
+        duration,
+    }
+}
+
+fn two_frames() -> Vec<RenderedFrame> {
+    vec![
+        frame(2, 1, &[RED, CLEAR], 1),
+        frame(2, 1, &[CLEAR, BLUE], 30),
+    ]
+}
+
+/// Every frame of a GIF as (RGBA pixels, delay in centiseconds)
+fn decode_gif(data: &[u8]) -> Vec<(Vec<u8>, u16)> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(data).unwrap();
+    let mut frames = vec![];
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        frames.push((frame.buffer.to_vec(), frame.delay));
+    }
+    frames
+}
+
+#[test]
+fn apng_frames_decode_back() {
+    let mut data = vec![];
+    export::export_apng(&mut data, &two_frames(), true).unwrap();
+
+    let mut reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+    let control = reader.info().animation_control.unwrap();
+    assert_eq!((control.num_frames, control.num_plays), (2, 0));
+
+    for expected in two_frames() {
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        let fctl = reader.info().frame_control.unwrap();
+        assert_eq!((fctl.delay_num, fctl.delay_den), (expected.duration, 60));
+        assert_eq!(buf, expected.image.pixels);
+    }
+}
+
+#[test]
+fn gif_frames_decode_back() {
+    let mut data = vec![];
+    export::export_gif(&mut data, &two_frames(), false).unwrap();
+
+    let frames = decode_gif(&data);
+    assert_eq!(frames.len(), 2);
+    for ((pixels, _), expected) in frames.iter().zip(two_frames()) {
+        assert_eq!(pixels, &expected.image.pixels);
+    }
+}
+
+#[test]
+fn gif_delays_dont_drift() {
+    // 60 frames of 1/60s each add up to exactly one second
+    let frames: Vec<_> = (0..60).map(|_| frame(1, 1, &[RED], 1)).collect();
+    let mut data = vec![];
+    export::export_gif(&mut data, &frames, true).unwrap();
+
+    let delays: Vec<_> = decode_gif(&data).iter().map(|(_, d)| *d).collect();
+    assert_eq!(delays.iter().map(|d| *d as u32).sum::<u32>(), 100);
+    assert!(delays.iter().all(|d| (1..=2).contains(d)));
+}
+
+#[test]
+fn gifs_bigger_than_the_format_allows_are_rejected() {
+    let pixels = vec![CLEAR; 0x10000];
+    let frames = [frame(0x10000, 1, &pixels, 1)];
+    assert!(matches!(
+        export::export_gif(&mut vec![], &frames, true),
+        Err(Error::InvalidImageSize {
+            width: 0x10000,
+            height: 1,
+            ..
+        })
+    ));
+}
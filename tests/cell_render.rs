@@ -0,0 +1,177 @@
+use nuclear::{
+    error::Error,
+    img::{
+        ncer::{CellImage, CellInternals, CharMapping, NCERCell},
+        ncgr::NCGRTiles,
+        ColorBGR555, Rounding, NCER, NCGR, NCLR,
+    },
+    ndsfile::NDSFile,
+};
+use std::collections::BTreeMap;
+
+const RED: [u8; 3] = [255, 0, 0];
+const GREEN: [u8; 3] = [0, 255, 0];
+const BLUE: [u8; 3] = [0, 0, 255];
+
+/// 4-bit palettes whose color N is `colors[N - 1]`
+fn nclr(palettes: &[&[[u8; 3]]]) -> NCLR {
+    let palettes = palettes
+        .iter()
+        .enumerate()
+        .map(|(id, colors)| {
+            let mut palette = vec![ColorBGR555::default()];
+            palette.extend(
+                colors
+                    .iter()
+                    .map(|c| ColorBGR555::from_rgb888(*c, Rounding::Truncate)),
+            );
+            palette.resize(16, ColorBGR555::default());
+            (id as u16, palette)
+        })
+        .collect::<BTreeMap<_, _>>();
+    NCLR {
+        palettes,
+        is_8_bit: false,
+        color_amt: 16,
+        is_extended: false,
+        version: NDSFile::DEFAULT_VERSION,
+        pcmp_unknown: NCLR::PCMP_UNKNOWN,
+        unreferenced_colors: vec![],
+        pltt_padding: vec![],
+        pltt_trailing: vec![],
+        extra_sections: vec![],
+    }
+}
+
+/// 4-bit tileset where every tile is filled with a single color index
+fn ncgr(tiles: &[u8]) -> NCGR {
+    NCGR {
+        tiles: NCGRTiles::Horizontal(tiles.iter().map(|c| vec![*c; 64]).collect()),
+        is_8_bit: false,
+        has_cpos: false,
+        ncbr_ff: false,
+        size: None,
+        cpos_unknown: 0,
+        mapping_type: 0,
+        tile_mode_flags: 0,
+        version: NDSFile::DEFAULT_VERSION,
+        extra_sections: vec![],
+    }
+}
+
+/// NCER with a single cell made of the given objects, as OAM attributes
+fn ncer(mapping: CharMapping, objects: &[[u16; 3]]) -> NCER {
+    NCER {
+        cells: vec![NCERCell {
+            objects: objects
+                .iter()
+                .map(|c| CellInternals::from_oam(*c).unwrap())
+                .collect(),
+            attributes: 0,
+            bounds: None,
+        }],
+        bank_attributes: 0,
+        mapping,
+        vram_transfer: None,
+        extended: None,
+        labels: None,
+        uext: None,
+        version: NDSFile::DEFAULT_VERSION,
+        extra_sections: vec![],
+    }
+}
+
+fn pixel(img: &CellImage, x: usize, y: usize) -> [u8; 4] {
+    let pos = (y * img.width + x) * 4;
+    img.pixels[pos..pos + 4].try_into().unwrap()
+}
+
+fn opaque([r, g, b]: [u8; 3]) -> [u8; 4] {
+    [r, g, b, 255]
+}
+
+#[test]
+fn objects_are_placed_and_flipped() {
+    let tiles = ncgr(&[1, 2]);
+    let colors = nclr(&[&[RED, GREEN]]);
+
+    // 16x8 object at (-8, -4) using tiles 0 and 1
+    let plain = ncer(CharMapping::Map1D32K, &[[0x40FC, 0x01F8, 0]]);
+    let img = plain.render_cell(0, &tiles, &colors).unwrap();
+    assert_eq!((img.width, img.height, img.x, img.y), (16, 8, -8, -4));
+    assert_eq!(pixel(&img, 0, 0), opaque(RED));
+    assert_eq!(pixel(&img, 15, 7), opaque(GREEN));
+
+    let flipped = ncer(CharMapping::Map1D32K, &[[0x40FC, 0x01F8 | 1 << 12, 0]]);
+    let img = flipped.render_cell(0, &tiles, &colors).unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(GREEN));
+    assert_eq!(pixel(&img, 15, 7), opaque(RED));
+}
+
+#[test]
+fn priority_decides_which_object_is_on_top() {
+    let tiles = ncgr(&[1, 2, 0]);
+    let colors = nclr(&[&[RED, GREEN]]);
+
+    // The second object has a lower priority value, so it's drawn over the first one
+    let img = ncer(CharMapping::Map1D32K, &[[0, 0, 1 << 10], [0, 0, 1]])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(GREEN));
+
+    // With the same priority, the first object wins
+    let img = ncer(CharMapping::Map1D32K, &[[0, 0, 0], [0, 0, 1]])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(RED));
+
+    // Color 0 is transparent, so what's under it shows through
+    let img = ncer(CharMapping::Map1D32K, &[[0, 0, 2], [0, 0, 1 << 10]])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(RED));
+}
+
+#[test]
+fn objects_use_their_own_palette() {
+    let tiles = ncgr(&[1]);
+    let colors = nclr(&[&[RED], &[BLUE]]);
+    let img = ncer(CharMapping::Map1D32K, &[[0, 0, 0], [0, 8, 1 << 12]])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(RED));
+    assert_eq!(pixel(&img, 8, 0), opaque(BLUE));
+
+    let img = ncer(CharMapping::Map1D32K, &[[0, 0, 2 << 12]]).render_cell(0, &tiles, &colors);
+    assert!(matches!(img, Err(Error::MissingReference { index: 2, .. })));
+}
+
+#[test]
+fn mapping_decides_where_the_next_row_of_tiles_is() {
+    // Tile 2 is the second row of a 16x16 object in 1D mapping, and tile 32 in 2D mapping
+    let mut indices = vec![0; 34];
+    indices[..2].fill(1);
+    indices[2..4].fill(2);
+    indices[32..34].fill(3);
+    let tiles = ncgr(&indices);
+    let colors = nclr(&[&[RED, GREEN, BLUE]]);
+    let object = [0, 1 << 14, 0];
+
+    let img = ncer(CharMapping::Map1D32K, &[object])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(RED));
+    assert_eq!(pixel(&img, 0, 8), opaque(GREEN));
+
+    let img = ncer(CharMapping::Map2D, &[object])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(RED));
+    assert_eq!(pixel(&img, 0, 8), opaque(BLUE));
+
+    // In 1D mapping with a 64K boundary, tile numbers count two tiles each
+    let img = ncer(CharMapping::Map1D64K, &[[0, 0, 1]])
+        .render_cell(0, &tiles, &colors)
+        .unwrap();
+    assert_eq!(pixel(&img, 0, 0), opaque(GREEN));
+}
@@ -0,0 +1,82 @@
+use bytestream::{ByteOrder, StreamReader, StreamWriter};
+use nuclear::img::{ColorBGR555, Rounding, NSCR};
+
+fn gray(value: u8) -> ColorBGR555 {
+    ColorBGR555 {
+        r: value,
+        g: value,
+        b: value,
+        x: false,
+    }
+}
+
+#[test]
+fn channels_are_expanded_by_replicating_bits() {
+    assert_eq!(gray(0x1F).to_rgb888(), [0xFF; 3]);
+    assert_eq!(gray(0).to_rgb888(), [0; 3]);
+    assert_eq!(gray(0x10).to_rgb888(), [0x84; 3]);
+    assert_eq!(gray(0x01).to_rgb888(), [0x08; 3]);
+}
+
+#[test]
+fn every_color_survives_both_roundings() {
+    for value in 0..0x20 {
+        let rgb = gray(value).to_rgb888();
+        assert_eq!(
+            ColorBGR555::from_rgb888(rgb, Rounding::Nearest),
+            gray(value)
+        );
+        assert_eq!(
+            ColorBGR555::from_rgb888(rgb, Rounding::Truncate),
+            gray(value)
+        );
+    }
+}
+
+#[test]
+fn nearest_rounding_picks_the_closest_channel() {
+    // 0x07 is closer to 0x08 (channel value 1) than to 0x00, which truncating picks
+    assert_eq!(ColorBGR555::from_rgb888([7; 3], Rounding::Nearest), gray(1));
+    assert_eq!(
+        ColorBGR555::from_rgb888([7; 3], Rounding::Truncate),
+        gray(0)
+    );
+    // Values that multiply by 8 come back the same with truncation
+    assert_eq!(
+        ColorBGR555::from_rgb888([0xF8; 3], Rounding::Truncate),
+        gray(0x1F)
+    );
+
+    for channel in 0..=0xFF {
+        let error = |rounding| {
+            let color = ColorBGR555::from_rgb888([channel; 3], rounding);
+            channel.abs_diff(color.to_rgb888()[0])
+        };
+        assert!(error(Rounding::Nearest) <= error(Rounding::Truncate));
+        assert!(error(Rounding::Nearest) <= 4, "channel {:#04X}", channel);
+    }
+}
+
+#[test]
+fn the_unused_bit_is_kept() {
+    let mut data = vec![];
+    let color = ColorBGR555 {
+        r: 0x1F,
+        g: 0,
+        b: 0x10,
+        x: true,
+    };
+    color.write_to(&mut data, ByteOrder::LittleEndian).unwrap();
+    assert_eq!(data, [0x1F, 0xC0]);
+    assert_eq!(
+        ColorBGR555::read_from(&mut data.as_slice(), ByteOrder::LittleEndian).unwrap(),
+        color
+    );
+}
+
+#[test]
+fn rendered_tilemaps_use_the_full_color_range() {
+    let white = vec![gray(0x1F); 64];
+    let (nscr, ncgr, nclr) = NSCR::gritify(&white, [8, 8], false).unwrap();
+    assert_eq!(nscr.render(&nclr, &ncgr).unwrap(), vec![0xFF; 64 * 3]);
+}
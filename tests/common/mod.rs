@@ -0,0 +1,134 @@
+//! Small files laid out like the ones found in games, built byte by byte
+#![allow(dead_code)]
+
+/// Builds a little endian Nintendo file from its magic, version and sections
+pub fn nds_file(magic: &[u8; 4], version: u16, sections: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut out = magic.to_vec();
+    out.extend(match magic {
+        b"NARC" => [0xFE, 0xFF],
+        _ => [0xFF, 0xFE],
+    });
+    out.extend(version.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend(0x10u16.to_le_bytes());
+    out.extend((sections.len() as u16).to_le_bytes());
+    for (magic, contents) in sections {
+        out.extend(*magic);
+        out.extend((contents.len() as u32 + 8).to_le_bytes());
+        out.extend(contents);
+    }
+    let size = out.len() as u32;
+    out[8..12].copy_from_slice(&size.to_le_bytes());
+    out
+}
+
+pub fn u16s(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+pub fn u32s(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+/// 4-bit NCLR with two 16-color palettes
+pub fn nclr() -> Vec<u8> {
+    let mut ttlp = u32s(&[3, 0, 0x40, 0x10]);
+    ttlp.extend(u16s(&(0..0x20).map(|c| c * 0x421).collect::<Vec<_>>()));
+    let mut pmcp = u16s(&[2]);
+    pmcp.extend([0xEF, 0xBE, 0x08, 0x00, 0x00, 0x00]);
+    pmcp.extend(u16s(&[0, 1]));
+    nds_file(b"RLCN", 0x0100, &[(b"TTLP", ttlp), (b"PMCP", pmcp)])
+}
+
+/// NCLR with a newer version and odd PCMP bytes, listing only the first of its three palettes,
+/// with some padding after the colors
+pub fn nclr_nonstandard() -> Vec<u8> {
+    let mut ttlp = u32s(&[3, 0, 0x60, 0x10]);
+    ttlp.extend(u16s(&(0..0x30).collect::<Vec<_>>()));
+    ttlp.extend([0xAA, 0xBB, 0xCC, 0xDD]);
+    let mut pmcp = u16s(&[1]);
+    pmcp.extend([0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC]);
+    pmcp.extend(u16s(&[0]));
+    nds_file(b"RLCN", 0x0101, &[(b"TTLP", ttlp), (b"PMCP", pmcp)])
+}
+
+/// 4-bit NCGR of 2x1 tiles with a CPOS section
+pub fn ncgr() -> Vec<u8> {
+    let mut rahc = u16s(&[1, 2]);
+    rahc.extend(u32s(&[3, 0, 0, 0x40, 0x18]));
+    rahc.extend((0..0x40).map(|c| c as u8));
+    let mut sopc = u32s(&[0]);
+    sopc.extend(u16s(&[0x20, 2]));
+    nds_file(b"RGCN", 0x0101, &[(b"RAHC", rahc), (b"SOPC", sopc)])
+}
+
+/// 4-bit lineal NCGR with a mapping type, tile mode flags and an unknown CPOS word
+pub fn ncgr_nonstandard() -> Vec<u8> {
+    let mut rahc = u16s(&[1, 1]);
+    rahc.extend(u32s(&[3, 0x10, 0x101, 0x20, 0x18]));
+    rahc.extend((0..0x20).map(|c| c as u8 * 3));
+    let mut sopc = u32s(&[0x1234]);
+    sopc.extend(u16s(&[0x20, 1]));
+    nds_file(b"RGCN", 0x0102, &[(b"RAHC", rahc), (b"SOPC", sopc)])
+}
+
+/// 16x8 text mode NSCR using both tiles of [ncgr], the second one flipped with palette 1
+pub fn nscr() -> Vec<u8> {
+    let mut nrcs = u16s(&[16, 8, 0, 0]);
+    nrcs.extend(u32s(&[4]));
+    nrcs.extend(u16s(&[0x0000, 0x1401]));
+    nds_file(b"RCSN", 0x0100, &[(b"NRCS", nrcs)])
+}
+
+/// [nscr] with a newer version
+pub fn nscr_nonstandard() -> Vec<u8> {
+    let mut data = nscr();
+    data[6..8].copy_from_slice(&0x0102u16.to_le_bytes());
+    data
+}
+
+/// NCER with two cells with bounds, the second one having two objects
+pub fn ncer() -> Vec<u8> {
+    let mut cebk = u16s(&[2, 1]);
+    cebk.extend(u32s(&[0x18, 0, 0, 0, 0]));
+    cebk.extend(u16s(&[1, 0x0C, 0, 0]));
+    cebk.extend(u16s(&[8, 8, 0xFFF8, 0xFFF8]));
+    cebk.extend(u16s(&[2, 0x0C, 6, 0]));
+    cebk.extend(u16s(&[16, 8, 0xFFF0, 0xFFF8]));
+    cebk.extend(u16s(&[0x00F8, 0x01F8, 0x0000]));
+    cebk.extend(u16s(&[0x40F8, 0x01F0, 0x1001]));
+    cebk.extend(u16s(&[0x00F8, 0x1000, 0x2401]));
+    cebk.extend([0; 2]);
+    let uext = u32s(&[0]);
+    nds_file(b"RECN", 0x0100, &[(b"KBEC", cebk), (b"TXEU", uext)])
+}
+
+/// NANR with a looping sequence of two frames showing cells 0 and 1
+pub fn nanr() -> Vec<u8> {
+    let mut abnk = u16s(&[1, 2]);
+    abnk.extend(u32s(&[0x18, 0x28, 0x38, 0, 0]));
+    abnk.extend(u16s(&[2, 0, 0, 1]));
+    abnk.extend(u32s(&[2, 0]));
+    abnk.extend(u32s(&[0]));
+    abnk.extend(u16s(&[4, 0xBEEF]));
+    abnk.extend(u32s(&[2]));
+    abnk.extend(u16s(&[4, 0xBEEF]));
+    abnk.extend(u16s(&[0, 1]));
+    let uext = u32s(&[0]);
+    nds_file(b"RNAN", 0x0100, &[(b"KNBA", abnk), (b"TXEU", uext)])
+}
+
+/// NARC with two unnamed files, the first one padded to 4 bytes
+pub fn narc() -> Vec<u8> {
+    let mut btaf = u16s(&[2, 0]);
+    btaf.extend(u32s(&[0, 5, 8, 0xB]));
+    let mut btnf = u32s(&[8]);
+    btnf.extend(u16s(&[0, 1]));
+    btnf.extend([0, 0xFF, 0xFF, 0xFF]);
+    let gmif = vec![1, 2, 3, 4, 5, 0xFF, 0xFF, 0xFF, 6, 7, 8, 0xFF];
+    nds_file(
+        b"NARC",
+        0x0100,
+        &[(b"BTAF", btaf), (b"BTNF", btnf), (b"GMIF", gmif)],
+    )
+}
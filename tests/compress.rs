@@ -0,0 +1,88 @@
+use nuclear::compress::{self, Compression};
+
+/// Bytes that don't repeat in any useful way, from a small LCG
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
+
+fn assert_round_trip(data: &[u8]) {
+    for format in [Compression::LZ10, Compression::LZ11] {
+        let compressed = compress::compress(data, format).unwrap();
+        assert_eq!(Compression::detect(&compressed), format);
+        assert!(compressed.len().is_multiple_of(4));
+        assert_eq!(
+            compress::decompress(&compressed).unwrap(),
+            data,
+            "{:?} round trip of {} bytes failed",
+            format,
+            data.len()
+        );
+    }
+}
+
+#[test]
+fn round_trips() {
+    assert_round_trip(&[]);
+    assert_round_trip(&[7]);
+    assert_round_trip(b"abcabcabcabcabcabcabc");
+    assert_round_trip(&noise(0x3000, 1));
+    assert_round_trip(&[0x55; 0x20000]);
+
+    // Runs long enough for every LZ11 token length, separated by noise
+    let mut runs = vec![];
+    for (i, len) in [3, 16, 17, 0x110, 0x111, 0x10110].into_iter().enumerate() {
+        runs.extend(noise(0x20, i as u32));
+        runs.extend(vec![i as u8; len]);
+    }
+    assert_round_trip(&runs);
+}
+
+#[test]
+fn matches_at_the_window_edge_round_trip() {
+    // Repeats exactly at the furthest distance matches can reach, and just past it
+    for gap in [0xFFF, 0x1000, 0x1001] {
+        let block = noise(gap, gap as u32);
+        let mut data = block.clone();
+        data.extend(&block[..0x40]);
+        assert_round_trip(&data);
+    }
+}
+
+#[test]
+fn lz11_decodes_every_token_length() {
+    // Literal 'A', then a 2-byte token (3 bytes), a 3-byte token (0x20 bytes) and a 4-byte
+    // token (0x200 bytes), all copying the previous byte
+    let size = 1 + 3 + 0x20 + 0x200;
+    let mut data = vec![0x11, size as u8, (size >> 8) as u8, 0];
+    data.extend([0b0111_0000, b'A']);
+    data.extend([0x20, 0x00]);
+    data.extend([0x00, 0xF0, 0x00]);
+    data.extend([0x10, 0x0E, 0xF0, 0x00]);
+    assert_eq!(compress::decompress_lz11(&data).unwrap(), vec![b'A'; size]);
+}
+
+#[test]
+fn lz11_encodes_long_runs_with_long_tokens() {
+    // LZ10 matches stop at 18 bytes, while a single LZ11 token can copy up to 0x10110 bytes
+    let run = vec![0x55; 0x10111];
+    let lz10 = compress::compress(&run, Compression::LZ10).unwrap();
+    let lz11 = compress::compress(&run, Compression::LZ11).unwrap();
+    assert!(lz10.len() > 0x1000);
+    assert!(lz11.len() <= 0x10, "{} bytes", lz11.len());
+    assert_eq!(compress::decompress(&lz11).unwrap(), run);
+}
+
+#[test]
+fn bad_data_is_an_error() {
+    // Huge sizes in the header don't reserve memory before the data runs out
+    assert!(compress::decompress(&[0x10, 0xFF, 0xFF, 0xFF, 0x00]).is_err());
+    assert!(compress::decompress(&[0x11, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]).is_err());
+    // Back-reference before the start
+    assert!(compress::decompress(&[0x10, 4, 0, 0, 0x80, 0x10, 0x05]).is_err());
+}
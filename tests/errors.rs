@@ -0,0 +1,146 @@
+mod common;
+
+use common::{nds_file, u16s, u32s};
+use nuclear::{
+    error::{Error, Result},
+    img::{NANR, NCER, NCGR, NCLR, NSCR},
+    ndsfile::NDSFileType,
+};
+
+fn nclr_with(ttlp: Vec<u8>) -> Result<NCLR> {
+    let mut pmcp = u16s(&[0]);
+    pmcp.extend([0; 6]);
+    let data = nds_file(b"RLCN", 0x0100, &[(b"TTLP", ttlp), (b"PMCP", pmcp)]);
+    NCLR::from_file("a.NCLR", &mut data.as_slice())
+}
+
+/// Checks that `result` failed on `field` at `offset` of `section`
+fn assert_field_error<T>(result: Result<T>, section: &str, field: &str, offset: usize) {
+    let (got_section, got_field, got_offset) = match result {
+        Err(Error::UnknownFieldValue {
+            section,
+            field,
+            offset,
+            ..
+        })
+        | Err(Error::TruncatedSection {
+            section,
+            field,
+            offset,
+            ..
+        })
+        | Err(Error::OffsetOutOfBounds {
+            section,
+            field,
+            offset,
+            ..
+        }) => (section, field, offset),
+        Err(e) => panic!("expected an error about a field, got {}", e),
+        Ok(_) => panic!("expected an error about {}", field),
+    };
+    assert_eq!(
+        (got_section.as_str(), got_field.as_str(), got_offset),
+        (section, field, offset)
+    );
+}
+
+#[test]
+fn unknown_values_name_their_field_and_offset() {
+    match nclr_with(u32s(&[5, 0, 0, 0x10])) {
+        Err(Error::UnknownFieldValue { file, value, .. }) => {
+            assert_eq!((file.as_str(), value), ("a.NCLR", 5))
+        }
+        c => panic!("expected an unknown value, got {:?}", c.map(|_| ())),
+    }
+    assert_field_error(nclr_with(u32s(&[5, 0, 0, 0x10])), "TTLP", "color format", 0);
+
+    let mut rahc = u16s(&[1, 1]);
+    rahc.extend(u32s(&[3, 0, 7, 0x20, 0x18]));
+    let data = nds_file(b"RGCN", 0x0100, &[(b"RAHC", rahc)]);
+    assert_field_error(
+        NCGR::from_file("a.NCGR", &mut data.as_slice()),
+        "RAHC",
+        "tile mode",
+        0x0C,
+    );
+
+    let mut nrcs = u16s(&[8, 8, 0, 9]);
+    nrcs.extend(u32s(&[2]));
+    nrcs.extend(u16s(&[0]));
+    let data = nds_file(b"RCSN", 0x0100, &[(b"NRCS", nrcs)]);
+    assert_field_error(
+        NSCR::from_file("a.NSCR", &mut data.as_slice()),
+        "NRCS",
+        "screen format",
+        6,
+    );
+}
+
+#[test]
+fn short_sections_name_the_missing_field() {
+    assert_field_error(
+        nclr_with(vec![3, 0, 0, 0, 0, 0]),
+        "TTLP",
+        "extended palette flag",
+        4,
+    );
+    // The colors are cut in the middle
+    let mut ttlp = u32s(&[3, 0, 0x20, 0x10]);
+    ttlp.extend(u16s(&[0; 5]));
+    assert_field_error(nclr_with(ttlp), "TTLP", "color", 0x1A);
+}
+
+#[test]
+fn offsets_past_the_section_are_reported() {
+    match nclr_with(u32s(&[3, 0, 0, 0x100])) {
+        Err(Error::OffsetOutOfBounds {
+            field,
+            offset,
+            target,
+            ..
+        }) => assert_eq!(
+            (field.as_str(), offset, target),
+            ("palette data offset", 0x0C, 0x100)
+        ),
+        c => panic!("expected an offset error, got {:?}", c.map(|_| ())),
+    }
+}
+
+#[test]
+fn broken_headers_are_reported_with_file_offsets() {
+    let data = nds_file(b"RLCN", 0x0100, &[]);
+    assert_field_error(
+        NCLR::from_file("a.NCLR", &mut &data[..7]),
+        "header",
+        "version",
+        6,
+    );
+
+    let mut data = nds_file(b"RLCN", 0x0100, &[(b"TTLP", vec![])]);
+    data[0x14..0x18].copy_from_slice(&4u32.to_le_bytes());
+    assert_field_error(
+        NCLR::from_file("a.NCLR", &mut data.as_slice()),
+        "header",
+        "TTLP size",
+        0x14,
+    );
+}
+
+#[test]
+fn cut_files_are_errors_instead_of_panics() {
+    fn check<T: NDSFileType>(name: &str, data: &[u8]) {
+        for len in 0..data.len() {
+            assert!(
+                T::from_file(name, &mut &data[..len]).is_err(),
+                "{} cut to {} bytes was read",
+                name,
+                len
+            );
+        }
+    }
+    check::<NCLR>("a.NCLR", &common::nclr());
+    check::<NCGR>("a.NCGR", &common::ncgr());
+    check::<NSCR>("a.NSCR", &common::nscr());
+    check::<NCER>("a.NCER", &common::ncer());
+    check::<NANR>("a.NANR", &common::nanr());
+}
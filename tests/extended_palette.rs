@@ -0,0 +1,89 @@
+mod common;
+
+use bytestream::ByteOrder;
+use common::{nds_file, u16s, u32s};
+use nuclear::{
+    error::Error,
+    img::{ColorBGR555, NCLR, NSCR},
+    ndsfile::NDSFileType,
+    proj::NuclearProject,
+};
+use std::{env, fs, io::Cursor, process};
+
+/// 8-bit extended NCLR with a 256-color palette for each ID, whose colors are all `id + 1`
+fn extended_nclr(ids: &[u16]) -> Vec<u8> {
+    let mut ttlp = u32s(&[4, 1, ids.len() as u32 * 0x200, 0x10]);
+    for id in ids {
+        ttlp.extend(u16s(&[id + 1; 0x100]));
+    }
+    let mut pmcp = u16s(&[ids.len() as u16]);
+    pmcp.extend([0xEF, 0xBE, 0x08, 0x00, 0x00, 0x00]);
+    pmcp.extend(u16s(ids));
+    nds_file(b"RLCN", 0x0100, &[(b"TTLP", ttlp), (b"PMCP", pmcp)])
+}
+
+#[test]
+fn extended_palettes_are_read_by_id() {
+    let data = extended_nclr(&[0, 1, 5]);
+    let nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+
+    assert!(nclr.is_extended && nclr.is_8_bit);
+    assert_eq!(nclr.color_amt, 0x100);
+    assert_eq!(nclr.palettes.keys().copied().collect::<Vec<_>>(), [0, 1, 5]);
+    assert!(nclr.palettes[&5].iter().all(|c| c.r == 6));
+
+    let mut out = Cursor::new(vec![]);
+    nclr.to_file(&mut out, "a.NCLR".to_string(), ByteOrder::LittleEndian)
+        .unwrap();
+    assert_eq!(out.into_inner(), data);
+}
+
+#[test]
+fn tiles_pick_their_palette_only_with_extended_palettes() {
+    let (mut nscr, ncgr, _) =
+        NSCR::gritify_indexed(&[7; 64], &[ColorBGR555::default(); 8], [8, 8], true).unwrap();
+    nscr.tiles[0].palette = 1;
+    let data = extended_nclr(&[0, 1]);
+    let mut nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+
+    let color = |c: u16| ColorBGR555 {
+        r: c as u8,
+        ..Default::default()
+    };
+    assert_eq!(
+        nscr.render(&nclr, &ncgr).unwrap()[..3],
+        color(2).to_rgb888()
+    );
+
+    // Without extended palettes, 8-bit tiles always use the first one
+    nclr.is_extended = false;
+    assert_eq!(
+        nscr.render(&nclr, &ncgr).unwrap()[..3],
+        color(1).to_rgb888()
+    );
+
+    nclr.is_extended = true;
+    nscr.tiles[0].palette = 2;
+    assert!(matches!(
+        nscr.render(&nclr, &ncgr),
+        Err(Error::MissingReference { index: 2, .. })
+    ));
+}
+
+#[test]
+fn projects_keep_every_extended_palette() {
+    let dir = env::temp_dir().join(format!("nuclear-extpltt-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let data = extended_nclr(&[0, 3]);
+    let nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+
+    let mut project = NuclearProject::new("test", "", "", dir.clone()).unwrap();
+    project.insert_nclr("ext", &nclr).unwrap();
+    project.save().unwrap();
+
+    let project = NuclearProject::load_from_file(&dir).unwrap();
+    let stored = project.get_nclr("ext").unwrap().unwrap();
+    assert!(stored.is_extended);
+    assert_eq!(stored.palettes, nclr.palettes);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{nds_file, u32s};
+use nuclear::{
+    error::Error,
+    ndsfile::{NDSFile, NDSFileIndex},
+};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+/// Reader that counts how many bytes were read through it
+struct Counting<R> {
+    inner: R,
+    read: usize,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.read += len;
+        Ok(len)
+    }
+}
+
+impl<R: Seek> Seek for Counting<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// File with a big section between two small ones
+fn big_file() -> Vec<u8> {
+    nds_file(
+        b"TEST",
+        0x0102,
+        &[
+            (b"ONE_", u32s(&[1])),
+            (b"BIG_", vec![0xAB; 0x10000]),
+            (b"TWO_", u32s(&[2, 2])),
+        ],
+    )
+}
+
+#[test]
+fn indexing_only_reads_headers() {
+    let mut f = Counting {
+        inner: Cursor::new(big_file()),
+        read: 0,
+    };
+    let index = NDSFileIndex::from_reader("a.bin", &mut f).unwrap();
+    assert!(f.read <= 0x10 + 3 * 8, "read {} bytes", f.read);
+
+    assert_eq!((index.magic.as_str(), index.version), ("TEST", 0x0102));
+    let sections: Vec<_> = index
+        .sections
+        .iter()
+        .map(|c| (c.magic.as_str(), c.offset, c.len))
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            ("ONE_", 0x18, 4),
+            ("BIG_", 0x24, 0x10000),
+            ("TWO_", 0x1002C, 8)
+        ]
+    );
+
+    let two = index.find("TWO_").unwrap();
+    assert_eq!(
+        index.read_section(&mut f, two).unwrap().contents,
+        u32s(&[2, 2])
+    );
+}
+
+#[test]
+fn sections_are_borrowed_from_the_bytes_they_were_indexed_from() {
+    let file = big_file();
+    let mut data = vec![0xEE; 0x100];
+    data.extend(&file);
+    data.extend([0xEE; 0x100]);
+
+    let mut f = Cursor::new(data.as_slice());
+    f.seek(SeekFrom::Start(0x100)).unwrap();
+    let index = NDSFileIndex::from_reader_sized("a.bin", &mut f, file.len() as u64).unwrap();
+    assert_eq!(index.base, 0x100);
+
+    let big = index.find("BIG_").unwrap();
+    let contents = index.section_bytes(&data, big).unwrap();
+    assert_eq!(contents.len(), 0x10000);
+    assert!(std::ptr::eq(contents.as_ptr(), &data[0x100 + big.offset]));
+}
+
+#[test]
+fn loading_an_index_gives_the_same_file() {
+    let data = big_file();
+    let index = NDSFileIndex::from_bytes("a.bin", &data).unwrap();
+    let loaded = index.load(&mut Cursor::new(&data)).unwrap();
+    let parsed = NDSFile::from_file("a.bin", &mut data.as_slice()).unwrap();
+
+    assert_eq!(
+        (loaded.magic, loaded.version),
+        (parsed.magic, parsed.version)
+    );
+    assert_eq!(loaded.sections.len(), parsed.sections.len());
+    for (a, b) in loaded.sections.iter().zip(&parsed.sections) {
+        assert_eq!((&a.magic, &a.contents), (&b.magic, &b.contents));
+    }
+}
+
+#[test]
+fn sections_cant_reach_past_the_file() {
+    let file = big_file();
+    // Whatever follows the file in a ROM isn't part of its last section
+    let mut data = file[..file.len() - 4].to_vec();
+    data.extend([0; 0x100]);
+    let mut f = Cursor::new(&data);
+    let size = file.len() as u64 - 4;
+    match NDSFileIndex::from_reader_sized("a.bin", &mut f, size) {
+        Err(Error::TruncatedSection { field, offset, .. }) => {
+            assert_eq!((field.as_str(), offset), ("TWO_ contents", 0x1002C))
+        }
+        c => panic!("expected a truncated section, got {:?}", c.map(|_| ())),
+    }
+}
@@ -0,0 +1,83 @@
+use bytestream::ByteOrder;
+use nuclear::{
+    error::Error,
+    fnt::{DirEntry, Directory},
+};
+
+fn dir(id: u16, name: &str, entries: Vec<DirEntry>) -> DirEntry {
+    DirEntry::Dir(Directory {
+        id,
+        name: name.to_string(),
+        first_file: 0,
+        entries,
+    })
+}
+
+#[test]
+fn tables_round_trip() {
+    let mut root = Directory::root(0);
+    root.entries = vec![
+        dir(
+            0xF001,
+            "a",
+            vec![dir(0xF002, "b", vec![DirEntry::File("c".into())])],
+        ),
+        DirEntry::File("d".into()),
+    ];
+
+    let fnt = root.to_fnt(ByteOrder::LittleEndian).unwrap();
+    let parsed = Directory::from_fnt(&fnt, "fnt", ByteOrder::LittleEndian).unwrap();
+    assert_eq!(parsed.to_fnt(ByteOrder::LittleEndian).unwrap(), fnt);
+    assert_eq!(parsed.find("a/b/c"), Some(0));
+}
+
+#[test]
+fn directory_ids_must_be_contiguous() {
+    let mut root = Directory::root(0);
+    root.entries = vec![dir(0xF002, "a", vec![])];
+    assert!(root.to_fnt(ByteOrder::LittleEndian).is_err());
+
+    root.entries = vec![dir(0xF001, "a", vec![]), dir(0xF001, "b", vec![])];
+    assert!(root.to_fnt(ByteOrder::LittleEndian).is_err());
+}
+
+/// FNT with just a root directory holding `names`, numbered from `first_file`
+fn root_fnt(first_file: u16, names: &[&str]) -> Vec<u8> {
+    let mut out = 8u32.to_le_bytes().to_vec();
+    out.extend(first_file.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    for name in names {
+        out.push(name.len() as u8);
+        out.extend(name.as_bytes());
+    }
+    out.push(0);
+    out
+}
+
+#[test]
+fn file_ids_past_the_last_one_are_rejected() {
+    let fnt = root_fnt(0xFFFF, &["a"]);
+    let root = Directory::from_fnt(&fnt, "fnt", ByteOrder::LittleEndian).unwrap();
+    assert_eq!(root.files(), [("a".to_string(), 0xFFFF)]);
+
+    let fnt = root_fnt(0xFFFF, &["a", "b"]);
+    assert!(matches!(
+        Directory::from_fnt(&fnt, "fnt", ByteOrder::LittleEndian),
+        Err(Error::MalformedData { .. })
+    ));
+}
+
+#[test]
+fn broken_tables_are_rejected() {
+    // Subdirectory pointing at a directory that isn't in the table
+    let mut fnt = root_fnt(0, &[]);
+    fnt.pop();
+    fnt.extend([0x81, b'a', 0x05, 0xF0, 0]);
+    assert!(Directory::from_fnt(&fnt, "fnt", ByteOrder::LittleEndian).is_err());
+
+    // Name running past the end of the table
+    let mut fnt = root_fnt(0, &[]);
+    fnt.pop();
+    fnt.extend([0x10, b'a']);
+    assert!(Directory::from_fnt(&fnt, "fnt", ByteOrder::LittleEndian).is_err());
+}
@@ -0,0 +1,126 @@
+use nuclear::{
+    error::Error,
+    img::{ncgr::NCGRTiles, ColorBGR555, Rounding, NSCR},
+};
+
+fn color(rgb: [u8; 3]) -> ColorBGR555 {
+    ColorBGR555::from_rgb888(rgb, Rounding::Truncate)
+}
+
+/// Indexed image made of 8x8 tiles laid out in a row
+fn row_of_tiles(tiles: &[Vec<u8>]) -> Vec<u8> {
+    let mut img = vec![];
+    for y in 0..8 {
+        for tile in tiles {
+            img.extend(&tile[y * 8..y * 8 + 8]);
+        }
+    }
+    img
+}
+
+fn flip(tile: &[u8], flip_x: bool, flip_y: bool) -> Vec<u8> {
+    let mut out = vec![];
+    for y in 0..8 {
+        for x in 0..8 {
+            let x = if flip_x { 7 - x } else { x };
+            let y = if flip_y { 7 - y } else { y };
+            out.push(tile[y * 8 + x]);
+        }
+    }
+    out
+}
+
+#[test]
+fn flipped_tiles_are_stored_once() {
+    // Nothing in this tile is symmetric, so every flip looks different
+    let tile: Vec<u8> = (0..64).map(|c| (c % 7) as u8).collect();
+    let tiles = [
+        tile.clone(),
+        flip(&tile, true, false),
+        flip(&tile, false, true),
+        flip(&tile, true, true),
+        tile.clone(),
+    ];
+    let palette: Vec<_> = (0..7).map(|c| color([c * 32, 0, 0])).collect();
+
+    let (nscr, ncgr, _) =
+        NSCR::gritify_indexed(&row_of_tiles(&tiles), &palette, [40, 8], false).unwrap();
+    let NCGRTiles::Horizontal(stored) = &ncgr.tiles else {
+        panic!("tiles should be horizontal");
+    };
+    assert_eq!(stored, &[tile]);
+    let flips: Vec<_> = nscr
+        .tiles
+        .iter()
+        .map(|c| (c.tile, c.flip_x, c.flip_y))
+        .collect();
+    assert_eq!(
+        flips,
+        [
+            (0, false, false),
+            (0, true, false),
+            (0, false, true),
+            (0, true, true),
+            (0, false, false)
+        ]
+    );
+}
+
+#[test]
+fn converted_images_render_the_same() {
+    let colors = [[0, 0, 0], [248, 0, 0], [0, 248, 0], [0, 0, 248]].map(color);
+    let img: Vec<_> = (0..16 * 16)
+        .map(|i| colors[(i % 16 / 3 + i / 16 / 5) % 4])
+        .collect();
+
+    let (nscr, ncgr, nclr) = NSCR::gritify(&img, [16, 16], false).unwrap();
+    let rendered = nscr.render(&nclr, &ncgr).unwrap();
+    let expected: Vec<u8> = img.iter().flat_map(|c| c.to_rgb888()).collect();
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn the_top_left_color_becomes_color_0() {
+    let (red, blue) = (color([248, 0, 0]), color([0, 0, 248]));
+    let mut img = vec![blue; 64];
+    img[0] = red;
+
+    let (_, _, nclr) = NSCR::gritify(&img, [8, 8], false).unwrap();
+    assert_eq!(nclr.palettes[&0][..2], [red, blue]);
+    assert_eq!(nclr.palettes[&0].len(), 16);
+}
+
+#[test]
+fn images_that_dont_fit_are_rejected() {
+    let many: Vec<_> = (0..64).map(|c| color([c / 2 * 8, 0, 0])).collect();
+    assert!(matches!(
+        NSCR::gritify(&many, [8, 8], false),
+        Err(Error::TooManyColors { max: 16, got: 32 })
+    ));
+
+    assert!(matches!(
+        NSCR::gritify_indexed(&[0; 12 * 8], &[], [12, 8], false),
+        Err(Error::InvalidImageSize {
+            width: 12,
+            height: 8,
+            ..
+        })
+    ));
+
+    // Every tile has its two marker pixels on the left, so no tile is a flip of another
+    let tiles: Vec<_> = (0..0x401)
+        .map(|i| {
+            let mut tile = vec![0; 64];
+            tile[0] = (i & 0xFF) as u8;
+            tile[1] = (i >> 8) as u8 + 1;
+            tile
+        })
+        .collect();
+    assert!(matches!(
+        NSCR::gritify_indexed(&row_of_tiles(&tiles), &[], [0x401 * 8, 8], true),
+        Err(Error::TooManyTiles {
+            count: 0x401,
+            limit: 0x400
+        })
+    ));
+}
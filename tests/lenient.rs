@@ -0,0 +1,90 @@
+use bytestream::ByteOrder;
+use nuclear::{
+    error::Error,
+    img::{NCLR, NSCR},
+    ndsfile::{NDSFileType, ParseMode},
+};
+use std::io::Cursor;
+
+/// Little endian Nintendo file with the given sections
+fn file(magic: &[u8; 4], sections: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut out = magic.to_vec();
+    out.extend([0xFF, 0xFE, 0x00, 0x01, 0, 0, 0, 0, 0x10, 0x00]);
+    out.extend((sections.len() as u16).to_le_bytes());
+    for (magic, contents) in sections {
+        out.extend(*magic);
+        out.extend((contents.len() as u32 + 8).to_le_bytes());
+        out.extend(contents);
+    }
+    let size = out.len() as u32;
+    out[8..12].copy_from_slice(&size.to_le_bytes());
+    out
+}
+
+/// Sections of a 4-bit NCLR with one palette of white colors
+fn nclr_sections() -> Vec<(&'static [u8; 4], Vec<u8>)> {
+    let mut ttlp = vec![3, 0, 0, 0, 0, 0, 0, 0, 0x20, 0, 0, 0, 0x10, 0, 0, 0];
+    ttlp.extend([0xFF, 0x7F].repeat(16));
+    let pmcp = vec![1, 0, 0xEF, 0xBE, 0x08, 0, 0, 0, 0, 0];
+    vec![(b"TTLP", ttlp), (b"PMCP", pmcp)]
+}
+
+/// Sections of a 16x8 text mode NSCR
+fn nscr_sections() -> Vec<(&'static [u8; 4], Vec<u8>)> {
+    let nrcs = vec![16, 0, 8, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0x01, 0x14];
+    vec![(b"NRCS", nrcs)]
+}
+
+/// Sections with a made up one inserted before the section at `index`
+fn with_unknown(
+    mut sections: Vec<(&'static [u8; 4], Vec<u8>)>,
+    index: usize,
+) -> Vec<(&'static [u8; 4], Vec<u8>)> {
+    sections.insert(index, (b"TSET", vec![1, 2, 3, 4]));
+    sections
+}
+
+fn written<T: NDSFileType>(file: &T) -> Vec<u8> {
+    let mut out = Cursor::new(vec![]);
+    file.to_file(&mut out, "a".to_string(), ByteOrder::LittleEndian)
+        .unwrap();
+    out.into_inner()
+}
+
+#[test]
+fn strict_mode_rejects_unknown_sections() {
+    let data = file(b"RLCN", &with_unknown(nclr_sections(), 1));
+    match NCLR::from_file_with("a.NCLR", &mut data.as_slice(), ParseMode::Strict) {
+        Err(Error::UnknownSection { s_name, .. }) => assert_eq!(s_name, "TSET"),
+        c => panic!("expected an unknown section error, got {:?}", c),
+    }
+}
+
+#[test]
+fn lenient_mode_writes_unknown_sections_back_in_place() {
+    for index in 0..=2 {
+        let data = file(b"RLCN", &with_unknown(nclr_sections(), index));
+        let nclr =
+            NCLR::from_file_with("a.NCLR", &mut data.as_slice(), ParseMode::Lenient).unwrap();
+        assert_eq!(nclr.extra_sections.len(), 1);
+        assert_eq!(nclr.extra_sections[0].index, index);
+        assert_eq!(nclr.extra_sections[0].section.contents, [1, 2, 3, 4]);
+        assert_eq!(written(&nclr), data, "section at {} was moved", index);
+    }
+    for index in 0..=1 {
+        let data = file(b"RCSN", &with_unknown(nscr_sections(), index));
+        let nscr =
+            NSCR::from_file_with("a.NSCR", &mut data.as_slice(), ParseMode::Lenient).unwrap();
+        assert_eq!(written(&nscr), data, "section at {} was moved", index);
+    }
+}
+
+#[test]
+fn lenient_mode_reads_known_sections_normally() {
+    let data = file(b"RLCN", &with_unknown(nclr_sections(), 1));
+    let lenient = NCLR::from_file_with("a.NCLR", &mut data.as_slice(), ParseMode::Lenient).unwrap();
+    let plain = file(b"RLCN", &nclr_sections());
+    let strict = NCLR::from_file("a.NCLR", &mut plain.as_slice()).unwrap();
+    assert_eq!(lenient.palettes, strict.palettes);
+    assert!(strict.extra_sections.is_empty());
+}
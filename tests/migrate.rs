@@ -0,0 +1,102 @@
+mod common;
+
+use common::*;
+use nuclear::{
+    img::NCLR,
+    ndsfile::NDSFileType,
+    proj::{
+        migrate::{migrate, PROJECT_VERSION},
+        NuclearProject,
+    },
+};
+use serde_json::{json, Value};
+use std::{env, fs, path::Path, process};
+
+fn migrated(mut meta: Value) -> Value {
+    migrate(&mut meta, Path::new("nuclear_meta.json")).unwrap();
+    assert_eq!(meta["version"], PROJECT_VERSION);
+    meta
+}
+
+#[test]
+fn tilemaps_take_color_depth_from_their_tileset() {
+    let tileset = |is_8_bit: bool| {
+        json!({
+            "tiles": "tile_a.bin",
+            "has_cpos": false,
+            "is_8_bit": is_8_bit,
+            "ncbr_ff": false,
+            "lineal_mode": false,
+            "size": null,
+            "associated_palette": null,
+        })
+    };
+    let tilemap = |tileset: Option<&str>| {
+        json!({
+            "map": "map.bin",
+            "width": 256,
+            "height": 192,
+            "associated_tileset": tileset,
+        })
+    };
+    let meta = migrated(json!({
+        "version": 1,
+        "name": "test",
+        "author": "",
+        "description": "",
+        "palette_sets": {},
+        "tilesets": { "big": tileset(true), "small": tileset(false) },
+        "tilemaps": {
+            "a": tilemap(Some("big")),
+            "b": tilemap(Some("small")),
+            "c": tilemap(None),
+            "d": tilemap(Some("gone")),
+        },
+    }));
+
+    let depth = |name: &str| meta["tilemaps"][name]["is_8_bit"].clone();
+    assert_eq!(depth("a"), true);
+    assert_eq!(depth("b"), false);
+    assert_eq!(depth("c"), false);
+    assert_eq!(depth("d"), false);
+}
+
+#[test]
+fn old_projects_can_leave_out_collections() {
+    let dir = env::temp_dir().join(format!("nuclear-v0-palettes-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let data = nclr();
+    let nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+    let mut project = NuclearProject::new("test", "", "", dir.clone()).unwrap();
+    project.insert_nclr("a", &nclr).unwrap();
+    project.save().unwrap();
+
+    // Strip everything a version 0 project with only palettes wouldn't have
+    let meta_path = dir.join("nuclear_meta.json");
+    let mut meta: Value = serde_json::from_slice(&fs::read(&meta_path).unwrap()).unwrap();
+    let meta_obj = meta.as_object_mut().unwrap();
+    for key in ["version", "tilesets", "tilemaps"] {
+        meta_obj.remove(key);
+    }
+    let palette = meta["palette_sets"]["a"].as_object_mut().unwrap();
+    for key in [
+        "is_extended",
+        "version",
+        "pcmp_unknown",
+        "unreferenced_colors",
+        "pltt_trailing",
+    ] {
+        palette.remove(key);
+    }
+    fs::write(&meta_path, serde_json::to_vec(&meta).unwrap()).unwrap();
+
+    let project = NuclearProject::load_from_file(&dir).unwrap();
+    assert_eq!(project.version, PROJECT_VERSION);
+    assert!(project.tilesets.is_empty() && project.tilemaps.is_empty());
+    assert_eq!(
+        project.get_nclr("a").unwrap().unwrap().palettes,
+        nclr.palettes
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,102 @@
+use nuclear::img::{ColorBGR555, Rounding, NSCR};
+
+/// A row of 8x8 tiles, each filled with 15 shades made by `color` from values 1 to 15
+fn shaded_tiles(tiles: &[fn(u8) -> [u8; 3]]) -> (Vec<u8>, [usize; 2]) {
+    let mut rgba = vec![];
+    for y in 0..8 {
+        for color in tiles {
+            for x in 0..8 {
+                let shade = ((y * 8 + x) % 15 + 1) as u8;
+                // Colors that BGR555 can store exactly, so the render can be compared
+                let [r, g, b] =
+                    ColorBGR555::from_rgb888(color(shade), Rounding::Truncate).to_rgb888();
+                rgba.extend([r, g, b, 0xFF]);
+            }
+        }
+    }
+    (rgba, [tiles.len() * 8, 8])
+}
+
+fn red(shade: u8) -> [u8; 3] {
+    [shade * 16, 0, 0]
+}
+
+fn blue(shade: u8) -> [u8; 3] {
+    [0, 0, shade * 16]
+}
+
+fn rgb(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks(4).flat_map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+#[test]
+fn tiles_with_similar_colors_share_a_palette() {
+    let (rgba, size) = shaded_tiles(&[red, blue, red, blue]);
+    let (nscr, ncgr, nclr) = NSCR::gritify_rgba_multi(&rgba, size, 2).unwrap();
+
+    assert_eq!(nclr.palettes.len(), 2);
+    let palettes: Vec<_> = nscr.tiles.iter().map(|c| c.palette).collect();
+    assert_eq!(palettes[0], palettes[2]);
+    assert_eq!(palettes[1], palettes[3]);
+    assert_ne!(palettes[0], palettes[1]);
+    // Every tile has 15 colors, so nothing had to be reduced
+    assert_eq!(nscr.render(&nclr, &ncgr).unwrap(), rgb(&rgba));
+}
+
+#[test]
+fn color_0_of_every_palette_is_transparent() {
+    let (mut rgba, size) = shaded_tiles(&[red, blue]);
+    // Top-left pixel of both tiles
+    rgba[3] = 0;
+    rgba[8 * 4 + 3] = 0;
+    let (nscr, ncgr, nclr) = NSCR::gritify_rgba_multi(&rgba, size, 2).unwrap();
+
+    let rendered = nscr.render(&nclr, &ncgr).unwrap();
+    for (tile, x) in nscr.tiles.iter().zip([0, 8]) {
+        let color = nclr.palettes[&(tile.palette as u16)][0].to_rgb888();
+        assert_eq!(rendered[x * 3..x * 3 + 3], color);
+    }
+}
+
+#[test]
+fn a_single_palette_is_shared_by_every_tile() {
+    let (rgba, size) = shaded_tiles(&[red, blue]);
+    let (nscr, ncgr, nclr) = NSCR::gritify_rgba_multi(&rgba, size, 1).unwrap();
+
+    assert_eq!(nclr.palettes.len(), 1);
+    assert!(nscr.tiles.iter().all(|c| c.palette == 0));
+    // 30 colors don't fit in 15, so they got reduced
+    assert_ne!(nscr.render(&nclr, &ncgr).unwrap(), rgb(&rgba));
+}
+
+#[test]
+fn no_more_than_16_palettes_are_made() {
+    let hues: Vec<fn(u8) -> [u8; 3]> = vec![
+        |c| [c * 16, 0, 0],
+        |c| [0, c * 16, 0],
+        |c| [0, 0, c * 16],
+        |c| [c * 16, c * 16, 0],
+        |c| [c * 16, 0, c * 16],
+        |c| [0, c * 16, c * 16],
+        |c| [c * 16, c * 16, c * 16],
+        |c| [c * 16, 0x80, 0],
+        |c| [c * 16, 0, 0x80],
+        |c| [0x80, c * 16, 0],
+        |c| [0, c * 16, 0x80],
+        |c| [0x80, 0, c * 16],
+        |c| [0, 0x80, c * 16],
+        |c| [c * 16, 0x80, 0x80],
+        |c| [0x80, c * 16, 0x80],
+        |c| [0x80, 0x80, c * 16],
+        |c| [c * 16, 0xF8, 0],
+        |c| [0xF8, c * 16, 0],
+    ];
+    let (rgba, size) = shaded_tiles(&hues);
+    let (nscr, _, nclr) = NSCR::gritify_rgba_multi(&rgba, size, 20).unwrap();
+
+    assert!(nclr.palettes.len() <= 16);
+    assert!(nscr
+        .tiles
+        .iter()
+        .all(|c| nclr.palettes.contains_key(&(c.palette as u16))));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use bytestream::ByteOrder;
+use common::*;
+use nuclear::{
+    error::Error,
+    img::{nanr::FrameData, ncer::CellImage, NANR},
+    ndsfile::{NDSFileType, ParseMode},
+};
+use std::io::Cursor;
+
+/// NANR with a single looping sequence of the given frame type, without frames
+fn empty_sequence(frame_type: u16) -> Vec<u8> {
+    let mut abnk = u16s(&[1, 0]);
+    abnk.extend(u32s(&[0x18, 0x28, 0x28, 0, 0]));
+    abnk.extend(u16s(&[0, 0, frame_type, 1]));
+    abnk.extend(u32s(&[2, 0]));
+    let uext = u32s(&[0]);
+    nds_file(b"RNAN", 0x0100, &[(b"KNBA", abnk), (b"TXEU", uext)])
+}
+
+#[test]
+fn empty_sequences_keep_their_frame_type() {
+    for frame_type in 0..3 {
+        let data = empty_sequence(frame_type);
+        let nanr = NANR::from_file("a.NANR", &mut data.as_slice()).unwrap();
+        assert_eq!(nanr.sequences[0].frame_type, frame_type);
+        assert_eq!(
+            NANR::verify_round_trip("a.NANR", &data, ParseMode::Strict).unwrap(),
+            None
+        );
+    }
+}
+
+#[test]
+fn frames_must_match_the_sequence_frame_type() {
+    let data = nanr();
+    let mut nanr = NANR::from_file("a.NANR", &mut data.as_slice()).unwrap();
+    nanr.sequences[0].frames[1].data = FrameData::Translation {
+        cell: 1,
+        padding: 0,
+        x: 0,
+        y: 0,
+    };
+    assert!(nanr.sequences[0].check_frame_types().is_err());
+    let mut out = Cursor::new(vec![]);
+    assert!(nanr
+        .to_file(&mut out, "a.NANR".to_string(), ByteOrder::LittleEndian)
+        .is_err());
+}
+
+#[test]
+fn transforms_are_limited_to_the_object_space() {
+    let cell = CellImage {
+        width: 8,
+        height: 8,
+        x: -4,
+        y: -4,
+        pixels: vec![0xFF; 8 * 8 * 4],
+    };
+    let transform = |scale_x: i32, scale_y: i32| FrameData::Transform {
+        cell: 0,
+        rotation: 0,
+        scale_x,
+        scale_y,
+        x: 0,
+        y: 0,
+    };
+
+    let doubled = transform(0x2000, 0x2000).transform(&cell).unwrap();
+    assert_eq!([doubled.width, doubled.height], [16, 16]);
+    assert_eq!([doubled.x, doubled.y], [-8, -8]);
+
+    assert!(matches!(
+        transform(0x1000 * 65, 0x1000).transform(&cell),
+        Err(Error::InvalidImageSize { .. })
+    ));
+    assert!(matches!(
+        transform(i32::MAX, i32::MIN).transform(&cell),
+        Err(Error::InvalidImageSize { .. })
+    ));
+}
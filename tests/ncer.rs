@@ -0,0 +1,99 @@
+mod common;
+
+use bytestream::ByteOrder;
+use common::*;
+use nuclear::{
+    error::Error,
+    img::{
+        ncer::{CellBounds, CellShape, CharMapping},
+        NCER,
+    },
+    ndsfile::NDSFileType,
+};
+use std::io::Cursor;
+
+/// NCER with a single cell of two objects, storing bounding boxes if `bank_attributes` says so
+fn one_cell(bank_attributes: u16, attr0: u16) -> Vec<u8> {
+    let has_bounds = bank_attributes & 1 != 0;
+    let mut cebk = u16s(&[1, bank_attributes]);
+    cebk.extend(u32s(&[0x18, 1, 0, 0, 0]));
+    cebk.extend(u16s(&[2, 0x0C, 0, 0]));
+    if has_bounds {
+        cebk.extend(u16s(&[24, 8, 0xFFF8, 0xFFF0]));
+    }
+    // 16x8 object at (-8, -16), flipped, tile 5 of palette 3 with priority 2
+    cebk.extend(u16s(&[attr0, 0x01F8 | 1 << 12, 0x3805]));
+    // 8x8 8-bit object at (8, 0) using mosaic
+    cebk.extend(u16s(&[0x3000, 0x0008, 0x0010]));
+    nds_file(b"RECN", 0x0100, &[(b"KBEC", cebk)])
+}
+
+fn written(ncer: &NCER) -> Vec<u8> {
+    let mut out = Cursor::new(vec![]);
+    ncer.to_file(&mut out, "a.NCER".to_string(), ByteOrder::LittleEndian)
+        .unwrap();
+    out.into_inner()
+}
+
+#[test]
+fn cells_are_read_with_bounds_and_objects() {
+    let data = one_cell(1, 0x40F0);
+    let ncer = NCER::from_file("a.NCER", &mut data.as_slice()).unwrap();
+    assert!(ncer.has_bounds());
+    assert_eq!(ncer.mapping, CharMapping::Map1D64K);
+    assert_eq!(ncer.cells.len(), 1);
+
+    let cell = &ncer.cells[0];
+    assert_eq!(cell.attributes, 0x0C);
+    assert_eq!(
+        cell.bounds,
+        Some(CellBounds {
+            max_x: 24,
+            max_y: 8,
+            min_x: -8,
+            min_y: -16,
+        })
+    );
+
+    let [first, second] = &cell.objects[..] else {
+        panic!("expected two objects, got {}", cell.objects.len());
+    };
+    assert_eq!((first.x_coord, first.y_coord), (-8, -16));
+    assert_eq!(first.shape, CellShape::Cell16x8);
+    assert!(first.flip_x && !first.flip_y && !first.is_8_bit);
+    assert_eq!((first.tile, first.priority, first.palette), (5, 2, 3));
+
+    assert_eq!((second.x_coord, second.y_coord), (8, 0));
+    assert_eq!(second.shape, CellShape::Cell8x8);
+    assert!(second.is_8_bit && second.mosaic);
+    assert_eq!(second.tile, 0x10);
+}
+
+#[test]
+fn cells_without_bounds_use_short_entries() {
+    let data = one_cell(0, 0x40F0);
+    let ncer = NCER::from_file("a.NCER", &mut data.as_slice()).unwrap();
+    assert!(!ncer.has_bounds());
+    assert_eq!(ncer.cells[0].bounds, None);
+    assert_eq!(ncer.cells[0].objects[0].tile, 5);
+    assert_eq!(written(&ncer), data);
+}
+
+#[test]
+fn bank_attributes_are_kept_as_they_were() {
+    for bank_attributes in [0, 1, 2, 0x101] {
+        let data = one_cell(bank_attributes, 0x40F0);
+        let ncer = NCER::from_file("a.NCER", &mut data.as_slice()).unwrap();
+        assert_eq!(ncer.bank_attributes, bank_attributes);
+        assert_eq!(written(&ncer), data);
+    }
+}
+
+#[test]
+fn prohibited_shapes_are_rejected() {
+    let data = one_cell(1, 0xC0F0);
+    assert!(matches!(
+        NCER::from_file("a.NCER", &mut data.as_slice()),
+        Err(Error::UnknownFieldValue { .. })
+    ));
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use bytestream::ByteOrder;
+use common::u32s;
+use nuclear::{
+    error::Error,
+    img::{ncgr::NCGRTiles, ColorBGR555, IndexedImage, Rounding, NCGR},
+    ndsfile::{NDSFile, NDSFileType, Section},
+};
+
+#[test]
+fn cpos_follows_the_tiles() {
+    let mut rahc = vec![1, 0, 2, 0];
+    rahc.extend(u32s(&[3, 0, 0, 0x40, 0x18]));
+    rahc.extend(vec![0x11; 0x40]);
+    let mut sopc = u32s(&[0x12345678]);
+    sopc.extend([0x20, 0, 2, 0]);
+    let file = NDSFile {
+        fname: "a.NCGR".to_string(),
+        magic: "RGCN".to_string(),
+        byteorder: ByteOrder::LittleEndian,
+        version: NDSFile::DEFAULT_VERSION,
+        sections: vec![
+            Section {
+                magic: "RAHC".to_string(),
+                contents: rahc,
+            },
+            Section {
+                magic: "SOPC".to_string(),
+                contents: sopc,
+            },
+        ],
+    };
+
+    let mut ncgr = NCGR::from_ndsfile(&file).unwrap();
+    assert!(ncgr.has_cpos);
+    assert_eq!(ncgr.cpos_unknown, 0x12345678);
+    let NCGRTiles::Horizontal(tiles) = &mut ncgr.tiles else {
+        panic!("tiles should be horizontal");
+    };
+    tiles.push(vec![0; 64]);
+
+    let written = ncgr
+        .to_ndsfile("a.NCGR".to_string(), ByteOrder::LittleEndian)
+        .unwrap();
+    let cpos = &written.sections[1];
+    assert_eq!(cpos.magic, "SOPC");
+    assert_eq!(cpos.contents, [0x78, 0x56, 0x34, 0x12, 0x20, 0, 3, 0]);
+}
+
+#[test]
+fn indexed_images_are_validated() {
+    let palette: Vec<ColorBGR555> = (0..16)
+        .map(|c| ColorBGR555::from_rgb888([c * 16, c * 16, c * 16], Rounding::Truncate))
+        .collect();
+    let image = |pixels: Vec<u8>, colors: usize| IndexedImage {
+        width: 8,
+        height: 8,
+        pixels,
+        palette: palette[..colors].iter().map(|c| c.to_rgb888()).collect(),
+    };
+
+    let ok = image(vec![1; 64], 16);
+    assert!(NCGR::from_indexed_image(&ok, false, false, Some(&palette)).is_ok());
+
+    // Color 3 isn't in the image's palette, so it can't be compared with the NCLR
+    let short_plte = image(vec![3; 64], 2);
+    assert!(matches!(
+        NCGR::from_indexed_image(&short_plte, false, false, Some(&palette)),
+        Err(Error::ColorIndexTooHigh { index: 3, .. })
+    ));
+
+    let short_pixels = image(vec![1; 60], 16);
+    assert!(matches!(
+        NCGR::from_indexed_image(&short_pixels, false, false, None),
+        Err(Error::InvalidImageSize { .. })
+    ));
+}
+
+#[test]
+fn imported_images_must_be_as_wide_as_the_tileset() {
+    let blank = |width: usize, height: usize| IndexedImage {
+        width,
+        height,
+        pixels: vec![1; width * height],
+        palette: vec![[0; 3]; 16],
+    };
+    let mut ncgr = NCGR::from_indexed_image(&blank(32, 8), false, false, None).unwrap();
+
+    assert!(matches!(
+        ncgr.import_indexed_image(&blank(16, 16), None),
+        Err(Error::InvalidImageSize {
+            width: 16,
+            height: 16,
+            ..
+        })
+    ));
+    assert_eq!(ncgr.dimensions(), [4, 1]);
+
+    ncgr.import_indexed_image(&blank(32, 16), None).unwrap();
+    assert_eq!(ncgr.dimensions(), [4, 2]);
+    assert_eq!(ncgr.tiles.len(false), 8);
+}
@@ -0,0 +1,118 @@
+use bytestream::ByteOrder;
+use nuclear::{
+    fnt::{DirEntry, Directory},
+    nds_rom::NDSRom,
+};
+use std::io::Cursor;
+
+const OVERLAY: (u32, u32) = (0x300, 0x340);
+const ARM7: (u32, u32) = (0x600, 0x100);
+const FAT_OFFSET: u32 = 0x900;
+const FILE: (u32, u32) = (0xA00, 0xA10);
+
+/// ROM laid out like ndstool does: ARM9, overlay file, overlay table, ARM7, FNT, FAT, files
+fn synthetic_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; FILE.1 as usize];
+    rom[..4].copy_from_slice(b"TEST");
+    let mut word = |offset: usize, value: u32| {
+        rom[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    word(0x20, 0x200); // ARM9
+    word(0x2C, 0x100);
+    word(0x30, ARM7.0);
+    word(0x3C, ARM7.1);
+    word(0x48, FAT_OFFSET);
+    word(0x4C, 16);
+    word(0x50, 0x400); // ARM9 overlay table
+    word(0x54, 0x20);
+    word(0x80, FILE.1);
+
+    let mut root = Directory::root(1);
+    root.entries.push(DirEntry::File("a.bin".to_string()));
+    let fnt = root.to_fnt(ByteOrder::LittleEndian).unwrap();
+    rom[0x40..0x44].copy_from_slice(&0x800u32.to_le_bytes());
+    rom[0x44..0x48].copy_from_slice(&(fnt.len() as u32).to_le_bytes());
+    rom[0x800..0x800 + fnt.len()].copy_from_slice(&fnt);
+
+    let fat = [OVERLAY.0, OVERLAY.1, FILE.0, FILE.1];
+    for (i, c) in fat.iter().enumerate() {
+        let offset = FAT_OFFSET as usize + i * 4;
+        rom[offset..offset + 4].copy_from_slice(&c.to_le_bytes());
+    }
+
+    rom[OVERLAY.0 as usize..OVERLAY.1 as usize].fill(0x0E);
+    rom[ARM7.0 as usize..(ARM7.0 + ARM7.1) as usize].fill(0x07);
+    rom[FILE.0 as usize..FILE.1 as usize].fill(0xF1);
+    rom
+}
+
+#[test]
+fn overlays_stay_in_place_when_rebuilding() {
+    let mut rom = NDSRom::new(Cursor::new(synthetic_rom())).unwrap();
+    let id = rom.find("a.bin").unwrap();
+    rom.replace_file(id, vec![0xAB; 0x300]).unwrap();
+    let mut out = vec![];
+    rom.write_to(&mut out).unwrap();
+
+    let mut rebuilt = NDSRom::new(Cursor::new(out.clone())).unwrap();
+    assert_eq!(rebuilt.fat[0], OVERLAY);
+    assert_eq!(rebuilt.read_file(0).unwrap(), vec![0x0E; 0x40]);
+    assert_eq!(rebuilt.read_file(id).unwrap(), vec![0xAB; 0x300]);
+    assert!(rebuilt.fat[id as usize].0 >= FILE.0);
+    assert_eq!(
+        &out[ARM7.0 as usize..(ARM7.0 + ARM7.1) as usize],
+        &[0x07; ARM7.1 as usize][..]
+    );
+    assert_eq!(rebuilt.header.rom_size, rebuilt.fat[id as usize].1);
+}
+
+#[test]
+fn replaced_overlays_are_written_in_place() {
+    let mut rom = NDSRom::new(Cursor::new(synthetic_rom())).unwrap();
+    rom.replace_file(0, vec![0x55; 0x20]).unwrap();
+    let mut out = vec![];
+    rom.write_to(&mut out).unwrap();
+
+    let mut rebuilt = NDSRom::new(Cursor::new(out)).unwrap();
+    assert_eq!(rebuilt.fat[0], (OVERLAY.0, OVERLAY.0 + 0x20));
+    assert_eq!(rebuilt.read_file(0).unwrap(), vec![0x55; 0x20]);
+    assert_eq!(rebuilt.read_file(1).unwrap(), vec![0xF1; 0x10]);
+
+    let mut rom = NDSRom::new(Cursor::new(synthetic_rom())).unwrap();
+    rom.replace_file(0, vec![0x55; 0x80]).unwrap();
+    assert!(rom.write_to(&mut vec![]).is_err());
+}
+
+/// [synthetic_rom] with a bigger capacity than needed, followed by a signature and padding
+fn signed_rom() -> Vec<u8> {
+    let mut rom = synthetic_rom();
+    rom[0x14] = 2;
+    rom.extend([0x5A; 0x88]);
+    rom.resize(0x20000 << 2, 0xFF);
+    rom
+}
+
+#[test]
+fn unchanged_roms_are_written_back_byte_for_byte() {
+    let original = signed_rom();
+    let mut rom = NDSRom::new(Cursor::new(original.clone())).unwrap();
+    let mut out = vec![];
+    rom.write_to(&mut out).unwrap();
+    assert!(out == original);
+}
+
+#[test]
+fn data_after_the_files_moves_with_them() {
+    let mut rom = NDSRom::new(Cursor::new(signed_rom())).unwrap();
+    let id = rom.find("a.bin").unwrap();
+    rom.replace_file(id, vec![0xAB; 0x300]).unwrap();
+    let mut out = vec![];
+    rom.write_to(&mut out).unwrap();
+
+    let rebuilt = NDSRom::new(Cursor::new(out.clone())).unwrap();
+    let end = rebuilt.fat[id as usize].1 as usize;
+    assert_eq!(&out[end..end + 0x88], &[0x5A; 0x88][..]);
+    assert_eq!(rebuilt.header.rom_size, end as u32);
+    assert_eq!(rebuilt.header.capacity, 2);
+    assert_eq!(out.len(), signed_rom().len() + end - FILE.1 as usize);
+}
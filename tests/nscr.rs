@@ -0,0 +1,16 @@
+mod common;
+
+use common::nscr;
+use nuclear::{error::Error, img::NSCR, ndsfile::NDSFileType};
+
+#[test]
+fn other_files_are_reported_with_the_right_magic() {
+    let mut data = nscr();
+    data[..4].copy_from_slice(b"RLCN");
+    match NSCR::from_file("a.NSCR", &mut data.as_slice()) {
+        Err(Error::WrongFileKind { expected, got, .. }) => {
+            assert_eq!((expected.as_str(), got.as_str()), ("RCSN", "RLCN"));
+        }
+        c => panic!("expected a WrongFileKind error, got {:?}", c.map(|_| ())),
+    }
+}
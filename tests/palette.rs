@@ -0,0 +1,36 @@
+mod common;
+
+use common::*;
+use nuclear::{
+    img::{PaletteFormat, NCLR},
+    ndsfile::NDSFileType,
+};
+
+const FORMATS: [PaletteFormat; 5] = [
+    PaletteFormat::JascPal,
+    PaletteFormat::Gpl,
+    PaletteFormat::Act,
+    PaletteFormat::RiffPal,
+    PaletteFormat::Aco,
+];
+
+#[test]
+fn exported_palettes_are_detected_and_imported_back() {
+    let data = nclr();
+    let nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+    for format in FORMATS {
+        let exported = nclr.export_palette(format).unwrap();
+        assert_eq!(PaletteFormat::detect(&exported), Some(format));
+
+        let imported = NCLR::import_palette("a", &exported, format, false).unwrap();
+        for (id, palette) in &nclr.palettes {
+            assert_eq!(
+                imported.palettes.get(id),
+                Some(palette),
+                "palette {} changed when going through {:?}",
+                id,
+                format
+            );
+        }
+    }
+}
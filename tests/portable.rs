@@ -0,0 +1,160 @@
+use nuclear::{
+    error::Error,
+    img::NSCR,
+    proj::{
+        portable::{PORTABLE_MAGIC, PORTABLE_VERSION},
+        NuclearProject,
+    },
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Empty directory for a test, removed first if a previous run left it behind
+fn temp_dir(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("nuclear-portable-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+/// Portable file of a project with one background, made in `dir`
+fn exported(dir: &Path) -> Vec<u8> {
+    let rgba: Vec<u8> = (0..16 * 8)
+        .flat_map(|c| [(c % 16 * 16) as u8, 0x80, 0, 0xFF])
+        .collect();
+    let (nscr, ncgr, nclr) = NSCR::gritify_rgba(&rgba, [16, 8], false).unwrap();
+
+    let mut project = NuclearProject::new("bg test", "me", "", dir.join("proj")).unwrap();
+    project.insert_nclr("bg", &nclr).unwrap();
+    project.insert_ncgr("bg", &ncgr).unwrap();
+    project.insert_nscr("bg", &nscr).unwrap();
+    let mut out = vec![];
+    project.export_portable(&mut out).unwrap();
+    out
+}
+
+/// Portable file with the given entries, with correct checksums
+fn archive(version: u16, entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = PORTABLE_MAGIC.to_vec();
+    out.extend(version.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend((entries.len() as u32).to_le_bytes());
+    for (path, contents) in entries {
+        out.extend((path.len() as u16).to_le_bytes());
+        out.extend(path.as_bytes());
+        out.extend((contents.len() as u32).to_le_bytes());
+        out.extend(crc32fast::hash(contents).to_le_bytes());
+        out.extend(*contents);
+    }
+    out.extend(crc32fast::hash(&out).to_le_bytes());
+    out
+}
+
+#[test]
+fn projects_are_the_same_after_packing() {
+    let dir = temp_dir("round-trip");
+    let data = exported(&dir);
+
+    let original = NuclearProject::load_from_file(dir.join("proj")).unwrap();
+    let imported = NuclearProject::import_portable(&mut data.as_slice(), dir.join("copy")).unwrap();
+    assert_eq!(imported.name, "bg test");
+    assert_eq!(imported.author, "me");
+    assert_eq!(
+        imported.get_nclr("bg").unwrap().unwrap().palettes,
+        original.get_nclr("bg").unwrap().unwrap().palettes
+    );
+    assert_eq!(
+        imported
+            .get_ncgr("bg")
+            .unwrap()
+            .unwrap()
+            .tiles
+            .to_raw(false),
+        original
+            .get_ncgr("bg")
+            .unwrap()
+            .unwrap()
+            .tiles
+            .to_raw(false)
+    );
+    let refs = |project: &NuclearProject| -> Vec<_> {
+        let nscr = project.get_nscr("bg").unwrap().unwrap();
+        nscr.tiles
+            .iter()
+            .map(|c| (c.tile, c.flip_x, c.flip_y, c.palette))
+            .collect()
+    };
+    assert_eq!(refs(&imported), refs(&original));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn corrupt_archives_are_rejected_without_writing_anything() {
+    let dir = temp_dir("corrupt");
+    let mut data = exported(&dir);
+    let middle = data.len() / 2;
+    data[middle] ^= 0xFF;
+
+    assert!(matches!(
+        NuclearProject::import_portable(&mut data.as_slice(), dir.join("copy")),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    assert!(!dir.join("copy").exists());
+
+    // Cut files are caught by the checksum too, as it's always the last 4 bytes
+    let data = exported(&dir);
+    assert!(matches!(
+        NuclearProject::import_portable(&mut &data[..data.len() - 1], dir.join("copy")),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn entries_outside_the_project_are_rejected() {
+    let dir = temp_dir("escape");
+    for path in ["../evil.bin", "/evil.bin", ""] {
+        let data = archive(
+            PORTABLE_VERSION,
+            &[("nuclear_meta.json", b"{}"), (path, b"evil")],
+        );
+        assert!(matches!(
+            NuclearProject::import_portable(&mut data.as_slice(), dir.join("copy")),
+            Err(Error::MalformedData { .. })
+        ));
+    }
+    assert!(!dir.exists());
+}
+
+#[test]
+fn newer_and_foreign_archives_are_rejected() {
+    let dir = temp_dir("version");
+    let data = archive(PORTABLE_VERSION + 1, &[]);
+    assert!(matches!(
+        NuclearProject::import_portable(&mut data.as_slice(), &dir),
+        Err(Error::UnsupportedVersion { version, max, .. })
+            if version == PORTABLE_VERSION as u32 + 1 && max == PORTABLE_VERSION as u32
+    ));
+
+    let mut data = b"PK\x03\x04".to_vec();
+    data.extend(&archive(PORTABLE_VERSION, &[])[4..]);
+    let len = data.len();
+    let checksum = crc32fast::hash(&data[..len - 4]);
+    data[len - 4..].copy_from_slice(&checksum.to_le_bytes());
+    assert!(matches!(
+        NuclearProject::import_portable(&mut data.as_slice(), &dir),
+        Err(Error::WrongFileKind { .. })
+    ));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn existing_projects_are_not_overwritten() {
+    let dir = temp_dir("existing");
+    let data = exported(&dir);
+    assert!(NuclearProject::import_portable(&mut data.as_slice(), dir.join("proj")).is_err());
+    assert!(NuclearProject::load_from_file(dir.join("proj")).is_ok());
+    fs::remove_dir_all(dir).unwrap();
+}
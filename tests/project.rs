@@ -0,0 +1,146 @@
+mod common;
+
+use bytestream::ByteOrder;
+use common::*;
+use nuclear::{
+    fnt::{DirEntry, Directory},
+    img::{NCGR, NCLR, NSCR},
+    narc::NARC,
+    ndsfile::NDSFileType,
+    proj::NuclearProject,
+};
+use std::{env, fs, path::PathBuf, process};
+
+/// Empty directory for a test, removed first if a previous run left it behind
+fn temp_dir(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("nuclear-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+#[test]
+fn projects_keep_header_versions_and_unknown_fields() {
+    let dir = temp_dir("preserved-fields");
+    let (nclr, ncgr, nscr) = (nclr_nonstandard(), ncgr_nonstandard(), nscr_nonstandard());
+
+    let mut project = NuclearProject::new("test", "", "", dir.join("proj")).unwrap();
+    project
+        .insert_nclr(
+            "a",
+            &NCLR::from_file("a.NCLR", &mut nclr.as_slice()).unwrap(),
+        )
+        .unwrap();
+    project
+        .insert_ncgr(
+            "a",
+            &NCGR::from_file("a.NCGR", &mut ncgr.as_slice()).unwrap(),
+        )
+        .unwrap();
+    project
+        .insert_nscr(
+            "a",
+            &NSCR::from_file("a.NSCR", &mut nscr.as_slice()).unwrap(),
+        )
+        .unwrap();
+    project.save().unwrap();
+
+    let project = NuclearProject::load_from_file(dir.join("proj")).unwrap();
+    project
+        .export_all(dir.join("out"), ByteOrder::LittleEndian)
+        .unwrap();
+    assert_eq!(fs::read(dir.join("out/a.NCLR")).unwrap(), nclr);
+    assert_eq!(fs::read(dir.join("out/a.NCGR")).unwrap(), ncgr);
+    assert_eq!(fs::read(dir.join("out/a.NSCR")).unwrap(), nscr);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unmodified_exports_are_identical() {
+    let dir = temp_dir("export");
+    let (nclr, ncgr, nscr) = (common::nclr(), common::ncgr(), common::nscr());
+
+    let mut project = NuclearProject::new("test", "", "", dir.join("proj")).unwrap();
+    project
+        .insert_nclr(
+            "bg",
+            &NCLR::from_file("bg.NCLR", &mut nclr.as_slice()).unwrap(),
+        )
+        .unwrap();
+    project
+        .insert_ncgr(
+            "bg",
+            &NCGR::from_file("bg.NCGR", &mut ncgr.as_slice()).unwrap(),
+        )
+        .unwrap();
+    project
+        .insert_nscr(
+            "bg",
+            &NSCR::from_file("bg.NSCR", &mut nscr.as_slice()).unwrap(),
+        )
+        .unwrap();
+
+    let written = project
+        .export_all(dir.join("out"), ByteOrder::LittleEndian)
+        .unwrap();
+    assert_eq!(written.len(), 3);
+    assert_eq!(fs::read(dir.join("out/bg.NCLR")).unwrap(), nclr);
+    assert_eq!(fs::read(dir.join("out/bg.NCGR")).unwrap(), ncgr);
+    assert_eq!(fs::read(dir.join("out/bg.NSCR")).unwrap(), nscr);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn archive_files_are_named_after_their_path() {
+    let dir = temp_dir("narc-names");
+    let mut names = Directory::root(2);
+    names.entries = vec![
+        DirEntry::Dir(Directory {
+            id: 0xF001,
+            name: "a.dir".to_string(),
+            first_file: 0,
+            entries: vec![
+                DirEntry::File("bg".to_string()),
+                DirEntry::File("fg.NCGR".to_string()),
+            ],
+        }),
+        DirEntry::File("pal.v2.NCLR".to_string()),
+    ];
+    let narc = NARC {
+        files: vec![nscr(), ncgr(), nclr()],
+        names: Some(names),
+        version: 0x0100,
+        extra_sections: vec![],
+    };
+
+    let mut project = NuclearProject::new("test", "", "", dir.join("proj")).unwrap();
+    let mut inserted = project.insert_narc(&narc, "arc").inserted;
+    inserted.sort();
+    assert_eq!(inserted, ["a.dir_bg", "a.dir_fg", "pal.v2"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn broken_archive_files_are_skipped() {
+    let dir = temp_dir("narc-broken");
+    let mut truncated = nclr();
+    truncated.truncate(0x20);
+    let narc = NARC {
+        files: vec![nclr(), truncated, b"not a Nintendo file".to_vec(), ncgr()],
+        names: None,
+        version: 0x0100,
+        extra_sections: vec![],
+    };
+
+    let mut project = NuclearProject::new("test", "", "", dir.join("proj")).unwrap();
+    let mut imported = project.insert_narc(&narc, "arc");
+    imported.inserted.sort();
+    assert_eq!(imported.inserted, ["arc_0", "arc_3"]);
+    assert_eq!(imported.failed.len(), 1);
+    assert_eq!(imported.failed[0].0, "arc_1");
+    assert!(project.palette_sets.contains_key("arc_0"));
+    assert!(!project.palette_sets.contains_key("arc_1"));
+    assert!(project.tilesets.contains_key("arc_3"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,48 @@
+use nuclear::img::{
+    quantize::{QuantizeOptions, QuantizedImage},
+    ColorBGR555,
+};
+
+#[test]
+fn fixed_colors_filling_the_palette_keep_indices_in_range() {
+    let fixed: Vec<ColorBGR555> = (1..16)
+        .map(|c| ColorBGR555::from_rgb888([c * 16, 0, 0], Default::default()))
+        .collect();
+    let rgba: Vec<u8> = (0..64u32)
+        .flat_map(|c| [(c * 4) as u8, 0x80, (255 - c * 4) as u8, 0xFF])
+        .collect();
+    let options = QuantizeOptions {
+        fixed_colors: fixed.clone(),
+        ..Default::default()
+    };
+
+    let image = QuantizedImage::from_rgba(&rgba, 8, 8, &options).unwrap();
+    let palette = &image.palette.palettes[&0];
+    assert_eq!(palette.len(), 16);
+    assert_eq!(&palette[1..], &fixed[..]);
+    assert!(image.pixels.iter().all(|c| (1..16).contains(c)));
+}
+
+#[test]
+fn palettes_never_go_over_their_size() {
+    let rgba: Vec<u8> = (0..32 * 32u32)
+        .flat_map(|c| [(c * 7) as u8, (c * 13) as u8, (c * 29) as u8, 0xFF])
+        .collect();
+    for (is_8_bit, max_palettes, fixed) in [(false, 1, 3), (false, 4, 0), (true, 1, 10)] {
+        let options = QuantizeOptions {
+            is_8_bit,
+            max_palettes,
+            dither: true,
+            fixed_colors: (0..fixed)
+                .map(|c| ColorBGR555::from_rgb888([c * 8, c * 8, 0], Default::default()))
+                .collect(),
+            ..Default::default()
+        };
+        let image = QuantizedImage::from_rgba(&rgba, 32, 32, &options).unwrap();
+        let color_amt = if is_8_bit { 256 } else { 16 };
+        assert!(image.palette.palettes.len() <= max_palettes);
+        for palette in image.palette.palettes.values() {
+            assert!(palette.len() <= color_amt);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use common::*;
+use nuclear::{
+    img::{nanr::FrameData, NANR, NCER, NCGR, NCLR, NSCR},
+    narc::NARC,
+    ndsfile::{NDSFileType, ParseMode, RoundTripMismatch},
+};
+
+fn assert_round_trip<T: NDSFileType>(fname: &str, data: &[u8]) {
+    assert_eq!(
+        T::verify_round_trip(fname, data, ParseMode::Strict).unwrap(),
+        None,
+        "{} wasn't written back as it was read",
+        fname
+    );
+}
+
+#[test]
+fn every_format_round_trips() {
+    assert_round_trip::<NCLR>("a.NCLR", &nclr());
+    assert_round_trip::<NCGR>("a.NCGR", &ncgr());
+    assert_round_trip::<NSCR>("a.NSCR", &nscr());
+    assert_round_trip::<NCER>("a.NCER", &ncer());
+    assert_round_trip::<NANR>("a.NANR", &nanr());
+    assert_round_trip::<NARC>("a.narc", &narc());
+    assert_round_trip::<NCGR>("a.NCGR", &ncgr_nonstandard());
+    assert_round_trip::<NSCR>("a.NSCR", &nscr_nonstandard());
+}
+
+#[test]
+fn nclr_keeps_unreferenced_palettes_and_trailing_data() {
+    let data = nclr_nonstandard();
+    assert_round_trip::<NCLR>("a.NCLR", &data);
+    let nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+    assert_eq!(nclr.palettes.len(), 1);
+    assert_eq!(nclr.unreferenced_colors.len(), 0x20);
+    assert_eq!(nclr.pltt_trailing, [0xAA, 0xBB, 0xCC, 0xDD]);
+}
+
+#[test]
+fn mismatches_are_reported() {
+    // A 4-bit palette of 8 colors is padded to 16 when written back
+    let mut ttlp = u32s(&[3, 0, 0x10, 0x10]);
+    ttlp.extend(u16s(&[0x7FFF; 8]));
+    let mut pmcp = u16s(&[1]);
+    pmcp.extend([0xEF, 0xBE, 0x08, 0x00, 0x00, 0x00]);
+    pmcp.extend(u16s(&[0]));
+    let data = nds_file(b"RLCN", 0x0100, &[(b"TTLP", ttlp), (b"PMCP", pmcp)]);
+
+    let mismatch = NCLR::verify_round_trip("a.NCLR", &data, ParseMode::Strict)
+        .unwrap()
+        .unwrap();
+    assert_eq!(mismatch.offset, 0x08);
+}
+
+#[test]
+fn mismatch_finds_first_difference() {
+    assert_eq!(RoundTripMismatch::find(&[1, 2, 3], &[1, 2, 3]), None);
+    assert_eq!(
+        RoundTripMismatch::find(&[1, 2, 3], &[1, 5, 3]),
+        Some(RoundTripMismatch {
+            offset: 1,
+            original: Some(2),
+            written: Some(5),
+        })
+    );
+    assert_eq!(
+        RoundTripMismatch::find(&[1, 2], &[1, 2, 3]),
+        Some(RoundTripMismatch {
+            offset: 2,
+            original: None,
+            written: Some(3),
+        })
+    );
+    assert_eq!(
+        RoundTripMismatch::find(&[1, 2, 3], &[1]),
+        Some(RoundTripMismatch {
+            offset: 1,
+            original: Some(2),
+            written: None,
+        })
+    );
+}
+
+#[test]
+fn nclr_colors_are_read_from_the_palette_data_offset() {
+    let mut ttlp = u32s(&[3, 0, 0x20, 0x14, 0x12345678]);
+    ttlp.extend(u16s(&[0x7FFF; 16]));
+    let mut pmcp = u16s(&[1]);
+    pmcp.extend(NCLR::PCMP_UNKNOWN);
+    pmcp.extend(u16s(&[0]));
+    let data = nds_file(b"RLCN", 0x0100, &[(b"TTLP", ttlp), (b"PMCP", pmcp)]);
+
+    let nclr = NCLR::from_file("a.NCLR", &mut data.as_slice()).unwrap();
+    assert_eq!(nclr.pltt_padding, 0x12345678u32.to_le_bytes());
+    assert!(nclr.palettes[&0].iter().all(|c| c.to_rgb888() == [0xFF; 3]));
+    assert_round_trip::<NCLR>("a.NCLR", &data);
+
+    // Colors can't start inside the header
+    let mut ttlp = u32s(&[3, 0, 0x20, 0x08]);
+    ttlp.extend(u16s(&[0x7FFF; 16]));
+    let mut pmcp = u16s(&[1]);
+    pmcp.extend(NCLR::PCMP_UNKNOWN);
+    pmcp.extend(u16s(&[0]));
+    let data = nds_file(b"RLCN", 0x0100, &[(b"TTLP", ttlp), (b"PMCP", pmcp)]);
+    assert!(NCLR::from_file("a.NCLR", &mut data.as_slice()).is_err());
+}
+
+#[test]
+fn nanr_keeps_header_words_and_frame_padding() {
+    let mut abnk = u16s(&[1, 1]);
+    abnk.extend(u32s(&[0x18, 0x28, 0x30, 0x11, 0x22]));
+    abnk.extend(u16s(&[1, 0, 2, 1]));
+    abnk.extend(u32s(&[1, 0]));
+    abnk.extend(u32s(&[0]));
+    abnk.extend(u16s(&[4, 0x1234]));
+    abnk.extend(u16s(&[3, 0xABCD, 0xFFF8, 0x0010]));
+    let data = nds_file(b"RNAN", 0x0100, &[(b"KNBA", abnk)]);
+
+    let nanr = NANR::from_file("a.NANR", &mut data.as_slice()).unwrap();
+    assert_eq!(nanr.abnk_unknown, [0x11, 0x22]);
+    let frame = &nanr.sequences[0].frames[0];
+    assert_eq!(frame.padding, 0x1234);
+    assert_eq!(
+        frame.data,
+        FrameData::Translation {
+            cell: 3,
+            padding: 0xABCD,
+            x: -8,
+            y: 16,
+        }
+    );
+    assert_round_trip::<NANR>("a.NANR", &data);
+}
@@ -0,0 +1,108 @@
+use nuclear::{
+    error::Error,
+    img::{ncgr::NCGRTiles, nscr::ScreenImage, QuantizeOptions, NSCR},
+};
+
+const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+const BLACK: [u8; 4] = [0, 0, 0, 0xFF];
+const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const GRAY: [u8; 4] = [0x84, 0x84, 0x84, 0xFF];
+
+/// Tile that looks different from every other ID, flipped or not
+fn numbered_tile(id: usize) -> Vec<[u8; 4]> {
+    let mut tile = vec![BLACK; 64];
+    // Flipping moves these to a side where no other tile has them
+    tile[0] = RED;
+    tile[56..].fill(GRAY);
+    for bit in 0..16 {
+        if id >> bit & 1 != 0 {
+            tile[16 + bit] = WHITE;
+        }
+    }
+    tile
+}
+
+fn flip_x(tile: &[[u8; 4]]) -> Vec<[u8; 4]> {
+    tile.chunks(8)
+        .flat_map(|c| c.iter().rev().copied())
+        .collect()
+}
+
+/// Screen made of a row of tiles
+fn screen(name: &str, tiles: &[Vec<[u8; 4]>]) -> ScreenImage {
+    let mut rgba = vec![];
+    for y in 0..8 {
+        for tile in tiles {
+            rgba.extend(tile[y * 8..y * 8 + 8].concat());
+        }
+    }
+    ScreenImage {
+        name: name.to_string(),
+        width: tiles.len() * 8,
+        height: 8,
+        rgba,
+    }
+}
+
+fn rgb(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks(4).flat_map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+#[test]
+fn tiles_are_shared_between_screens() {
+    let (a, b, c) = (numbered_tile(1), numbered_tile(2), numbered_tile(3));
+    let screens = [
+        screen("first", &[a.clone(), b.clone()]),
+        screen("second", &[flip_x(&b), c.clone(), a.clone()]),
+    ];
+    let (nscrs, ncgr, nclr) = NSCR::gritify_shared(&screens, &QuantizeOptions::default()).unwrap();
+
+    let NCGRTiles::Horizontal(tiles) = &ncgr.tiles else {
+        panic!("tiles should be horizontal");
+    };
+    assert_eq!(tiles.len(), 3);
+    assert_eq!(nscrs.len(), 2);
+    let refs: Vec<_> = nscrs[1].tiles.iter().map(|c| (c.tile, c.flip_x)).collect();
+    assert_eq!(refs, [(1, true), (2, false), (0, false)]);
+    for (nscr, screen) in nscrs.iter().zip(&screens) {
+        assert_eq!(nscr.render(&nclr, &ncgr).unwrap(), rgb(&screen.rgba));
+    }
+}
+
+#[test]
+fn screens_past_the_tile_limit_are_reported() {
+    let tiles = |range: std::ops::Range<usize>| -> Vec<_> { range.map(numbered_tile).collect() };
+    let screens = [
+        screen("fits", &tiles(0..600)),
+        screen("goes over", &tiles(600..1100)),
+        screen("repeats", &tiles(0..100)),
+        screen("adds more", &tiles(1100..1101)),
+    ];
+
+    match NSCR::gritify_shared(&screens, &QuantizeOptions::default()) {
+        Err(Error::TooManySharedTiles {
+            count,
+            limit,
+            screens,
+        }) => {
+            assert_eq!((count, limit), (1101, 0x400));
+            assert_eq!(screens, ["goes over", "adds more"]);
+        }
+        c => panic!("expected TooManySharedTiles, got {:?}", c.map(|_| ())),
+    }
+}
+
+#[test]
+fn screens_must_be_made_of_whole_tiles() {
+    let mut odd = screen("odd", &[numbered_tile(0)]);
+    odd.width = 4;
+    odd.height = 16;
+    assert!(matches!(
+        NSCR::gritify_shared(&[odd], &QuantizeOptions::default()),
+        Err(Error::InvalidImageSize {
+            width: 4,
+            height: 16,
+            ..
+        })
+    ));
+}
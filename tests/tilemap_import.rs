@@ -0,0 +1,84 @@
+use nuclear::{error::Error, img::NSCR};
+
+/// 16x16 RGBA image with a different solid color in each 8x8 quarter
+fn quarters() -> Vec<u8> {
+    let colors = [[0xFF, 0, 0], [0, 0xFF, 0], [0, 0, 0xFF], [0xFF, 0xFF, 0xFF]];
+    let mut out = vec![];
+    for y in 0..16 {
+        for x in 0..16 {
+            out.extend(colors[y / 8 * 2 + x / 8]);
+            out.push(0xFF);
+        }
+    }
+    out
+}
+
+#[test]
+fn unchanged_images_change_nothing() {
+    let img = quarters();
+    let (mut nscr, mut ncgr, mut nclr) = NSCR::gritify_rgba(&img, [16, 16], false).unwrap();
+    let tile_count = ncgr.tiles.len(false);
+    let changes = nscr
+        .import_rgba(&img, [16, 16], &mut ncgr, &mut nclr)
+        .unwrap();
+    assert_eq!(changes.changed_refs, 0);
+    assert_eq!(changes.new_tiles, 0);
+    assert_eq!(changes.new_colors, 0);
+    assert_eq!(ncgr.tiles.len(false), tile_count);
+}
+
+#[test]
+fn changed_tiles_are_appended() {
+    let img = quarters();
+    let (mut nscr, mut ncgr, mut nclr) = NSCR::gritify_rgba(&img, [16, 16], false).unwrap();
+    let tile_count = ncgr.tiles.len(false);
+    let first_refs: Vec<u16> = nscr.tiles.iter().map(|c| c.tile).collect();
+
+    // Draw a brown pixel on the last quarter
+    let mut edited = img.clone();
+    let px = (15 * 16 + 15) * 4;
+    edited[px..px + 3].copy_from_slice(&[0x80, 0x40, 0x20]);
+    let changes = nscr
+        .import_rgba(&edited, [16, 16], &mut ncgr, &mut nclr)
+        .unwrap();
+
+    assert_eq!(changes.changed_refs, 1);
+    assert_eq!(changes.new_tiles, 1);
+    assert_eq!(changes.new_colors, 1);
+    assert_eq!(ncgr.tiles.len(false), tile_count + 1);
+    assert_eq!(
+        nscr.tiles[..3].iter().map(|c| c.tile).collect::<Vec<_>>(),
+        first_refs[..3]
+    );
+    assert_eq!(nscr.tiles[3].tile as usize, tile_count);
+}
+
+#[test]
+fn sizes_that_dont_match_the_tilemap_are_rejected() {
+    let img = quarters();
+    let (nscr, ncgr, nclr) = NSCR::gritify_rgba(&img, [16, 16], false).unwrap();
+    let import = |nscr: &NSCR, img: &[u8], size: [usize; 2]| {
+        let (mut nscr, mut ncgr, mut nclr) = (nscr.clone(), ncgr.clone(), nclr.clone());
+        nscr.import_rgba(img, size, &mut ncgr, &mut nclr)
+    };
+
+    assert!(matches!(
+        import(&nscr, &img[..8 * 16 * 4], [16, 8]),
+        Err(Error::InvalidImageSize { .. })
+    ));
+
+    // Sizes that aren't a multiple of 8 can come from a parsed file
+    let mut odd = nscr.clone();
+    odd.width = 12;
+    assert!(matches!(
+        import(&odd, &img[..12 * 16 * 4], [12, 16]),
+        Err(Error::InvalidImageSize { .. })
+    ));
+
+    let mut missing_refs = nscr.clone();
+    missing_refs.tiles.pop();
+    assert!(matches!(
+        import(&missing_refs, &img, [16, 16]),
+        Err(Error::InvalidImageSize { .. })
+    ));
+}